@@ -0,0 +1,7 @@
+CREATE TABLE clip_lists (
+    id VARCHAR NOT NULL PRIMARY KEY,
+    clips_json VARCHAR NOT NULL,
+    history_json VARCHAR NOT NULL,
+    created_on INTEGER NOT NULL,
+    updated_on INTEGER NOT NULL
+);
@@ -0,0 +1,14 @@
+CREATE TABLE compilations (
+    id VARCHAR NOT NULL PRIMARY KEY,
+    file_name VARCHAR NOT NULL,
+    created_on INTEGER NOT NULL
+);
+
+CREATE TABLE compilation_clips (
+    compilation_id VARCHAR NOT NULL REFERENCES compilations (id) ON DELETE CASCADE,
+    video_id VARCHAR NOT NULL,
+    start_time DOUBLE PRECISION NOT NULL,
+    end_time DOUBLE PRECISION NOT NULL
+);
+
+CREATE INDEX compilation_clips_compilation_id ON compilation_clips (compilation_id);
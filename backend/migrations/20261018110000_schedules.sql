@@ -0,0 +1,20 @@
+CREATE TABLE schedules (
+    id INTEGER PRIMARY KEY,
+    "name" VARCHAR NOT NULL,
+    cron VARCHAR NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    options_json VARCHAR NOT NULL,
+    last_run INTEGER,
+    next_run INTEGER,
+    created_on INTEGER NOT NULL
+);
+
+CREATE TABLE schedule_runs (
+    id INTEGER PRIMARY KEY,
+    schedule_id INTEGER NOT NULL REFERENCES schedules (id) ON DELETE CASCADE,
+    video_id VARCHAR,
+    file_name VARCHAR,
+    seed VARCHAR NOT NULL,
+    error VARCHAR,
+    created_on INTEGER NOT NULL
+);
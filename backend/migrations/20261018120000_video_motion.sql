@@ -0,0 +1,6 @@
+CREATE TABLE video_motion (
+    video_id VARCHAR PRIMARY KEY NOT NULL REFERENCES videos (id) ON DELETE CASCADE,
+    sample_interval REAL NOT NULL,
+    scores VARCHAR NOT NULL,
+    created_on INTEGER NOT NULL
+);
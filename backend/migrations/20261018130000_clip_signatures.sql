@@ -0,0 +1,8 @@
+CREATE TABLE clip_signatures (
+    video_id VARCHAR NOT NULL REFERENCES videos (id) ON DELETE CASCADE,
+    start_millis INTEGER NOT NULL,
+    end_millis INTEGER NOT NULL,
+    first_frame_hash INTEGER NOT NULL,
+    last_frame_hash INTEGER NOT NULL,
+    PRIMARY KEY (video_id, start_millis, end_millis)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE songs ADD COLUMN title VARCHAR;
+ALTER TABLE songs ADD COLUMN artist VARCHAR;
+ALTER TABLE songs ADD COLUMN album VARCHAR;
@@ -0,0 +1,12 @@
+CREATE TABLE playlists (
+    id INTEGER PRIMARY KEY,
+    "name" VARCHAR NOT NULL,
+    created_on INTEGER NOT NULL
+);
+
+CREATE TABLE playlist_songs (
+    playlist_id INTEGER NOT NULL REFERENCES playlists (id) ON DELETE CASCADE,
+    song_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (playlist_id, position)
+);
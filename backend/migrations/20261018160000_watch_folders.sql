@@ -0,0 +1,11 @@
+CREATE TABLE watch_folders (
+    id INTEGER PRIMARY KEY,
+    "path" VARCHAR NOT NULL UNIQUE,
+    recurse BOOLEAN NOT NULL,
+    tags VARCHAR NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    last_scan INTEGER,
+    created_on INTEGER NOT NULL
+);
+
+ALTER TABLE videos ADD COLUMN missing_since INTEGER;
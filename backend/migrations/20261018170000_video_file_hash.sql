@@ -0,0 +1,3 @@
+ALTER TABLE videos ADD COLUMN file_hash VARCHAR;
+
+CREATE INDEX videos_file_hash ON videos (file_hash);
@@ -0,0 +1,6 @@
+CREATE TABLE video_integrity (
+    video_id VARCHAR PRIMARY KEY NOT NULL REFERENCES videos (id) ON DELETE CASCADE,
+    mode VARCHAR NOT NULL,
+    errors VARCHAR,
+    checked_on INTEGER NOT NULL
+);
@@ -0,0 +1,2 @@
+ALTER TABLE watch_folders ADD COLUMN folder_tags BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE watch_folders ADD COLUMN folder_tag_depth INTEGER;
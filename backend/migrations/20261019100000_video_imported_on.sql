@@ -0,0 +1,3 @@
+-- the import time of existing videos is unknown, their creation time is the closest guess
+ALTER TABLE videos ADD COLUMN video_imported_on INTEGER;
+UPDATE videos SET video_imported_on = video_created_on;
@@ -0,0 +1,10 @@
+-- signatures of whole clips were rarely reused, because clip ranges change with every
+-- generation. frames on a fixed grid are shared by all clips of a video.
+DROP TABLE clip_signatures;
+
+CREATE TABLE frame_hashes (
+    video_id VARCHAR NOT NULL REFERENCES videos (id) ON DELETE CASCADE,
+    time_millis INTEGER NOT NULL,
+    hash INTEGER NOT NULL,
+    PRIMARY KEY (video_id, time_millis)
+);
@@ -0,0 +1,5 @@
+CREATE TABLE video_motion_failures (
+    video_id VARCHAR NOT NULL PRIMARY KEY REFERENCES videos (id) ON DELETE CASCADE,
+    error VARCHAR NOT NULL,
+    failed_on INTEGER NOT NULL
+);
@@ -0,0 +1,101 @@
+use sqlx::SqlitePool;
+
+use super::unix_timestamp_now;
+use crate::helpers::random::generate_id;
+use crate::server::types::Clip;
+use crate::Result;
+
+/// A stored clip list that can be edited on the server, together with its undo history.
+#[derive(Debug, Clone)]
+pub struct ClipList {
+    pub id: String,
+    pub clips: Vec<Clip>,
+    /// Previous versions of the clip list, most recent last.
+    pub history: Vec<Vec<Clip>>,
+    pub created_on: i64,
+    pub updated_on: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClipListsDatabase {
+    pool: SqlitePool,
+}
+
+impl ClipListsDatabase {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create_clip_list(&self, clips: Vec<Clip>) -> Result<ClipList> {
+        let id = generate_id();
+        let now = unix_timestamp_now();
+        let clips_json = serde_json::to_string(&clips)?;
+        sqlx::query!(
+            "INSERT INTO clip_lists (id, clips_json, history_json, created_on, updated_on)
+             VALUES ($1, $2, '[]', $3, $3)",
+            id,
+            clips_json,
+            now,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(ClipList {
+            id,
+            clips,
+            history: vec![],
+            created_on: now,
+            updated_on: now,
+        })
+    }
+
+    pub async fn get_clip_list(&self, id: &str) -> Result<Option<ClipList>> {
+        let row = sqlx::query!(
+            "SELECT id, clips_json, history_json, created_on, updated_on FROM clip_lists WHERE id = $1",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(Some(ClipList {
+                id: row.id,
+                clips: serde_json::from_str(&row.clips_json)?,
+                history: serde_json::from_str(&row.history_json)?,
+                created_on: row.created_on,
+                updated_on: row.updated_on,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn update_clip_list(
+        &self,
+        id: &str,
+        clips: &[Clip],
+        history: &[Vec<Clip>],
+    ) -> Result<()> {
+        let now = unix_timestamp_now();
+        let clips_json = serde_json::to_string(clips)?;
+        let history_json = serde_json::to_string(history)?;
+        sqlx::query!(
+            "UPDATE clip_lists SET clips_json = $1, history_json = $2, updated_on = $3 WHERE id = $4",
+            clips_json,
+            history_json,
+            now,
+            id,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_clip_list(&self, id: &str) -> Result<()> {
+        sqlx::query!("DELETE FROM clip_lists WHERE id = $1", id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
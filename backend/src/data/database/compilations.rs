@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tracing::info;
+
+use super::unix_timestamp_now;
+use crate::server::types::Clip;
+use crate::Result;
+
+/// A source range that was used in a finished compilation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsedRange {
+    pub video_id: String,
+    pub start_time: f64,
+    pub end_time: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompilationsDatabase {
+    pool: SqlitePool,
+}
+
+impl CompilationsDatabase {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Stores which source ranges ended up in a finished compilation.
+    /// Compiling the same project again replaces the previously recorded ranges.
+    pub async fn record_compilation(
+        &self,
+        id: &str,
+        file_name: &str,
+        clips: &[Clip],
+    ) -> Result<()> {
+        let created_on = unix_timestamp_now();
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            "INSERT INTO compilations (id, file_name, created_on) VALUES ($1, $2, $3)
+             ON CONFLICT(id) DO UPDATE SET file_name = excluded.file_name, created_on = excluded.created_on",
+            id,
+            file_name,
+            created_on,
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM compilation_clips WHERE compilation_id = $1",
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for clip in clips {
+            let (start, end) = clip.range;
+            sqlx::query!(
+                "INSERT INTO compilation_clips (compilation_id, video_id, start_time, end_time)
+                 VALUES ($1, $2, $3, $4)",
+                id,
+                clip.video_id,
+                start,
+                end,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        info!(
+            "recorded {} clips for compilation {id} ('{file_name}')",
+            clips.len()
+        );
+
+        Ok(())
+    }
+
+    /// Returns all source ranges used in the `count` most recent compilations.
+    pub async fn get_recently_used_ranges(&self, count: i64) -> Result<Vec<UsedRange>> {
+        sqlx::query_as!(
+            UsedRange,
+            "SELECT video_id, start_time, end_time FROM compilation_clips
+             WHERE compilation_id IN (
+                SELECT id FROM compilations ORDER BY created_on DESC, rowid DESC LIMIT $1
+             )",
+            count
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(From::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+    use tracing_test::traced_test;
+
+    use crate::data::database::videos::VideoSource;
+    use crate::data::database::Database;
+    use crate::server::types::Clip;
+
+    fn clip(video_id: &str, range: (f64, f64)) -> Clip {
+        Clip {
+            source: VideoSource::Folder,
+            video_id: video_id.to_string(),
+            marker_id: 1,
+            range,
+            index_within_video: 0,
+            index_within_marker: 0,
+            marker_title: "marker".to_string(),
+        }
+    }
+
+    #[sqlx::test]
+    #[traced_test]
+    async fn test_recently_used_ranges(pool: SqlitePool) {
+        let database = Database::with_pool(pool);
+        let compilations = &database.compilations;
+        compilations
+            .record_compilation("first", "first.mp4", &[clip("a", (0.0, 5.0))])
+            .await
+            .unwrap();
+        compilations
+            .record_compilation(
+                "second",
+                "second.mp4",
+                &[clip("b", (10.0, 15.0)), clip("b", (20.0, 25.0))],
+            )
+            .await
+            .unwrap();
+
+        let ranges = compilations.get_recently_used_ranges(1).await.unwrap();
+        assert_eq!(ranges.len(), 2);
+        assert!(ranges.iter().all(|r| r.video_id == "b"));
+
+        let ranges = compilations.get_recently_used_ranges(5).await.unwrap();
+        assert_eq!(ranges.len(), 3);
+
+        compilations
+            .record_compilation("second", "second.mp4", &[clip("c", (0.0, 1.0))])
+            .await
+            .unwrap();
+        let ranges = compilations.get_recently_used_ranges(5).await.unwrap();
+        assert_eq!(ranges.len(), 2);
+    }
+}
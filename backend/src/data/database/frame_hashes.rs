@@ -0,0 +1,75 @@
+use sqlx::SqlitePool;
+
+use crate::Result;
+
+/// Average hashes of single video frames, used to order clips by visual similarity.
+#[derive(Debug, Clone)]
+pub struct FrameHashesDatabase {
+    pool: SqlitePool,
+}
+
+impl FrameHashesDatabase {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_hash(&self, video_id: &str, time_millis: u32) -> Result<Option<u64>> {
+        let hash = sqlx::query_scalar!(
+            "SELECT hash FROM frame_hashes WHERE video_id = $1 AND time_millis = $2",
+            video_id,
+            time_millis,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(hash.map(|hash| hash as u64))
+    }
+
+    pub async fn set_hash(&self, video_id: &str, time_millis: u32, hash: u64) -> Result<()> {
+        let hash = hash as i64;
+        sqlx::query!(
+            "INSERT INTO frame_hashes (video_id, time_millis, hash)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (video_id, time_millis) DO UPDATE SET hash = $3",
+            video_id,
+            time_millis,
+            hash,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::data::database::Database;
+    use crate::service::fixtures::persist_video;
+
+    #[sqlx::test]
+    async fn test_set_and_get_hash(pool: SqlitePool) -> Result<()> {
+        let db = Database::with_pool(pool);
+        let video = persist_video(&db).await?;
+
+        assert_eq!(db.frame_hashes.get_hash(&video.id, 5000).await?, None);
+        db.frame_hashes.set_hash(&video.id, 5000, u64::MAX).await?;
+        assert_eq!(
+            db.frame_hashes.get_hash(&video.id, 5000).await?,
+            Some(u64::MAX)
+        );
+        db.frame_hashes
+            .set_hash(&video.id, 5000, 0x00ff_00ff_00ff_00ff)
+            .await?;
+        assert_eq!(
+            db.frame_hashes.get_hash(&video.id, 5000).await?,
+            Some(0x00ff_00ff_00ff_00ff)
+        );
+        assert_eq!(db.frame_hashes.get_hash(&video.id, 5500).await?, None);
+
+        Ok(())
+    }
+}
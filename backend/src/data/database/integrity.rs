@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tracing::info;
+use utoipa::ToSchema;
+
+use super::unix_timestamp_now;
+use crate::Result;
+
+/// How thoroughly a video is checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum VerificationMode {
+    /// Decodes a few short samples spread over the video.
+    #[default]
+    Sample,
+    /// Decodes the whole video.
+    Full,
+}
+
+impl VerificationMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            VerificationMode::Sample => "sample",
+            VerificationMode::Full => "full",
+        }
+    }
+
+    fn from_str(value: &str) -> Self {
+        match value {
+            "full" => VerificationMode::Full,
+            _ => VerificationMode::Sample,
+        }
+    }
+}
+
+/// The result of the last integrity check of a video.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoIntegrity {
+    pub video_id: String,
+    pub mode: VerificationMode,
+    /// The errors ffmpeg reported while decoding, if any.
+    pub errors: Option<String>,
+    pub checked_on: i64,
+}
+
+impl VideoIntegrity {
+    pub fn is_broken(&self) -> bool {
+        self.errors.is_some()
+    }
+}
+
+struct DbVideoIntegrity {
+    video_id: String,
+    mode: String,
+    errors: Option<String>,
+    checked_on: i64,
+}
+
+impl From<DbVideoIntegrity> for VideoIntegrity {
+    fn from(value: DbVideoIntegrity) -> Self {
+        VideoIntegrity {
+            video_id: value.video_id,
+            mode: VerificationMode::from_str(&value.mode),
+            errors: value.errors,
+            checked_on: value.checked_on,
+        }
+    }
+}
+
+/// A local video that hasn't been checked yet.
+#[derive(Debug, Clone)]
+pub struct UncheckedVideo {
+    pub id: String,
+    pub file_path: String,
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct IntegrityDatabase {
+    pool: SqlitePool,
+}
+
+impl IntegrityDatabase {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_integrity(&self, video_id: &str) -> Result<Option<VideoIntegrity>> {
+        let row = sqlx::query_as!(
+            DbVideoIntegrity,
+            "SELECT video_id, mode, errors, checked_on FROM video_integrity WHERE video_id = $1",
+            video_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(From::from))
+    }
+
+    /// Returns the errors of all of the given videos that are flagged as broken, by video ID.
+    pub async fn get_broken_videos(&self, video_ids: &[&str]) -> Result<HashMap<String, String>> {
+        let mut result = HashMap::new();
+        for video_id in video_ids {
+            if let Some(VideoIntegrity {
+                errors: Some(errors),
+                ..
+            }) = self.get_integrity(video_id).await?
+            {
+                result.insert(video_id.to_string(), errors);
+            }
+        }
+        Ok(result)
+    }
+
+    pub async fn list_broken_videos(&self) -> Result<Vec<VideoIntegrity>> {
+        let rows = sqlx::query_as!(
+            DbVideoIntegrity,
+            "SELECT video_id, mode, errors, checked_on FROM video_integrity
+            WHERE errors IS NOT NULL ORDER BY checked_on DESC"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(From::from).collect())
+    }
+
+    pub async fn list_unchecked_videos(&self) -> Result<Vec<UncheckedVideo>> {
+        sqlx::query_as!(
+            UncheckedVideo,
+            "SELECT v.id, v.file_path, v.duration FROM videos v
+            LEFT JOIN video_integrity i ON v.id = i.video_id
+            WHERE i.video_id IS NULL AND v.source != 'stash'"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(From::from)
+    }
+
+    pub async fn set_integrity(
+        &self,
+        video_id: &str,
+        mode: VerificationMode,
+        errors: Option<&str>,
+    ) -> Result<()> {
+        info!(
+            "storing integrity check for video {video_id}: {}",
+            errors.unwrap_or("no errors")
+        );
+        let mode = mode.as_str();
+        let checked_on = unix_timestamp_now();
+
+        sqlx::query!(
+            "INSERT INTO video_integrity (video_id, mode, errors, checked_on)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (video_id) DO UPDATE SET mode = $2, errors = $3, checked_on = $4",
+            video_id,
+            mode,
+            errors,
+            checked_on,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::data::database::Database;
+    use crate::service::fixtures::persist_video;
+
+    #[sqlx::test]
+    async fn test_set_and_get_integrity(pool: SqlitePool) -> Result<()> {
+        let db = Database::with_pool(pool);
+        let broken = persist_video(&db).await?;
+        let fine = persist_video(&db).await?;
+        assert_eq!(db.integrity.list_unchecked_videos().await?.len(), 2);
+
+        db.integrity
+            .set_integrity(
+                &broken.id,
+                VerificationMode::Sample,
+                Some("moov atom not found"),
+            )
+            .await?;
+        db.integrity
+            .set_integrity(&fine.id, VerificationMode::Full, None)
+            .await?;
+
+        assert!(db.integrity.list_unchecked_videos().await?.is_empty());
+        let integrity = db.integrity.get_integrity(&fine.id).await?.unwrap();
+        assert_eq!(integrity.mode, VerificationMode::Full);
+        assert!(!integrity.is_broken());
+
+        let broken_videos = db
+            .integrity
+            .get_broken_videos(&[&broken.id, &fine.id])
+            .await?;
+        assert_eq!(
+            broken_videos,
+            HashMap::from([(broken.id.clone(), "moov atom not found".to_string())])
+        );
+        assert_eq!(db.integrity.list_broken_videos().await?.len(), 1);
+
+        Ok(())
+    }
+}
@@ -408,6 +408,31 @@ impl MarkersDatabase {
         Ok(())
     }
 
+    /// Moves all markers of one video to another video, e.g. when merging duplicates.
+    /// Markers the other video already has (same start and end) are dropped instead.
+    /// Returns the number of markers that were moved.
+    pub async fn move_markers(
+        &self,
+        from_video_id: &str,
+        to_video_id: &str,
+        connection: &mut SqliteConnection,
+    ) -> Result<u64> {
+        let result = sqlx::query!(
+            "UPDATE OR IGNORE markers SET video_id = $1 WHERE video_id = $2",
+            to_video_id,
+            from_video_id
+        )
+        .execute(&mut *connection)
+        .await?;
+        sqlx::query!("DELETE FROM markers WHERE video_id = $1", from_video_id)
+            .execute(&mut *connection)
+            .await?;
+        self.fix_marker_video_indices(to_video_id, connection)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
     async fn fix_marker_video_indices(
         &self,
         video_id: &str,
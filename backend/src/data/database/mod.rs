@@ -4,26 +4,42 @@ use std::time::SystemTime;
 use color_eyre::eyre::OptionExt;
 use performers::PerformersDatabase;
 use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode};
-use sqlx::SqlitePool;
+use sqlx::{Sqlite, SqlitePool, Transaction};
 use tracing::info;
 
+use self::clip_lists::ClipListsDatabase;
+use self::compilations::CompilationsDatabase;
 use self::ffprobe::FfProbeInfoDatabase;
+use self::frame_hashes::FrameHashesDatabase;
+use self::integrity::IntegrityDatabase;
 use self::markers::MarkersDatabase;
+use self::motion::MotionDatabase;
 use self::music::MusicDatabase;
+use self::playlists::PlaylistsDatabase;
 use self::progress::ProgressDatabase;
+use self::schedules::SchedulesDatabase;
 use self::settings::SettingsDatabase;
 pub use self::settings::{HandyConfig, Settings};
 use self::videos::VideosDatabase;
+use self::watch_folders::WatchFoldersDatabase;
 use crate::server::types::Progress;
 use crate::Result;
 
+pub mod clip_lists;
+pub mod compilations;
 pub mod ffprobe;
+pub mod frame_hashes;
+pub mod integrity;
 pub mod markers;
+pub mod motion;
 pub mod music;
 pub mod performers;
+pub mod playlists;
 pub mod progress;
+pub mod schedules;
 pub mod settings;
 pub mod videos;
+pub mod watch_folders;
 
 #[derive(Clone)]
 pub struct Database {
@@ -34,6 +50,14 @@ pub struct Database {
     pub ffprobe: FfProbeInfoDatabase,
     pub settings: SettingsDatabase,
     pub performers: PerformersDatabase,
+    pub clip_lists: ClipListsDatabase,
+    pub compilations: CompilationsDatabase,
+    pub schedules: SchedulesDatabase,
+    pub motion: MotionDatabase,
+    pub frame_hashes: FrameHashesDatabase,
+    pub playlists: PlaylistsDatabase,
+    pub watch_folders: WatchFoldersDatabase,
+    pub integrity: IntegrityDatabase,
 }
 
 impl Database {
@@ -54,9 +78,22 @@ impl Database {
             videos: VideosDatabase::new(pool.clone()),
             settings: SettingsDatabase::new(pool.clone()),
             performers: PerformersDatabase::new(pool.clone()),
+            clip_lists: ClipListsDatabase::new(pool.clone()),
+            compilations: CompilationsDatabase::new(pool.clone()),
+            schedules: SchedulesDatabase::new(pool.clone()),
+            motion: MotionDatabase::new(pool.clone()),
+            frame_hashes: FrameHashesDatabase::new(pool.clone()),
+            playlists: PlaylistsDatabase::new(pool.clone()),
+            watch_folders: WatchFoldersDatabase::new(pool.clone()),
+            integrity: IntegrityDatabase::new(pool.clone()),
         })
     }
 
+    /// Starts a transaction for changes that span several tables.
+    pub async fn begin(&self) -> Result<Transaction<'static, Sqlite>> {
+        Ok(self.progress.pool.begin().await?)
+    }
+
     pub async fn sqlite_version(&self) -> Result<String> {
         let version = sqlx::query_scalar!("select sqlite_version()")
             .fetch_one(&self.progress.pool)
@@ -75,6 +112,14 @@ impl Database {
             videos: VideosDatabase::new(pool.clone()),
             settings: SettingsDatabase::new(pool.clone()),
             performers: PerformersDatabase::new(pool.clone()),
+            clip_lists: ClipListsDatabase::new(pool.clone()),
+            compilations: CompilationsDatabase::new(pool.clone()),
+            schedules: SchedulesDatabase::new(pool.clone()),
+            motion: MotionDatabase::new(pool.clone()),
+            frame_hashes: FrameHashesDatabase::new(pool.clone()),
+            playlists: PlaylistsDatabase::new(pool.clone()),
+            watch_folders: WatchFoldersDatabase::new(pool.clone()),
+            integrity: IntegrityDatabase::new(pool.clone()),
         }
     }
 }
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tracing::info;
+use utoipa::ToSchema;
+
+use super::unix_timestamp_now;
+use crate::Result;
+
+/// Motion scores of a video, sampled every `sample_interval` seconds. The score at index `i`
+/// describes how much the picture changed between `i * sample_interval` and the sample before.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoMotion {
+    pub video_id: String,
+    pub sample_interval: f64,
+    pub scores: Vec<f64>,
+}
+
+struct DbVideoMotion {
+    video_id: String,
+    sample_interval: f64,
+    scores: String,
+}
+
+impl TryFrom<DbVideoMotion> for VideoMotion {
+    type Error = color_eyre::Report;
+
+    fn try_from(value: DbVideoMotion) -> Result<Self> {
+        Ok(VideoMotion {
+            video_id: value.video_id,
+            sample_interval: value.sample_interval,
+            scores: serde_json::from_str(&value.scores)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MotionDatabase {
+    pool: SqlitePool,
+}
+
+impl MotionDatabase {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_motion(&self, video_id: &str) -> Result<Option<VideoMotion>> {
+        let row = sqlx::query_as!(
+            DbVideoMotion,
+            "SELECT video_id, sample_interval, scores FROM video_motion WHERE video_id = $1",
+            video_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(TryFrom::try_from).transpose()
+    }
+
+    /// Returns the cached motion analysis for all of the given videos that have one.
+    pub async fn get_motion_for_videos(&self, video_ids: &[&str]) -> Result<Vec<VideoMotion>> {
+        let mut result = vec![];
+        for video_id in video_ids {
+            if let Some(motion) = self.get_motion(video_id).await? {
+                result.push(motion);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Returns the IDs of all local videos that weren't analyzed yet. Videos whose analysis
+    /// failed before are left out.
+    pub async fn list_unanalyzed_videos(&self) -> Result<Vec<String>> {
+        sqlx::query_scalar!(
+            "SELECT v.id FROM videos v
+            LEFT JOIN video_motion m ON v.id = m.video_id
+            LEFT JOIN video_motion_failures f ON v.id = f.video_id
+            WHERE m.video_id IS NULL AND f.video_id IS NULL
+              AND v.source != 'stash' AND v.missing_since IS NULL"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(From::from)
+    }
+
+    pub async fn set_motion(&self, motion: &VideoMotion) -> Result<()> {
+        info!(
+            "storing {} motion samples for video {}",
+            motion.scores.len(),
+            motion.video_id
+        );
+        let scores = serde_json::to_string(&motion.scores)?;
+        let created_on = unix_timestamp_now();
+
+        sqlx::query!(
+            "INSERT INTO video_motion (video_id, sample_interval, scores, created_on)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (video_id) DO UPDATE SET sample_interval = $2, scores = $3, created_on = $4",
+            motion.video_id,
+            motion.sample_interval,
+            scores,
+            created_on,
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM video_motion_failures WHERE video_id = $1",
+            motion.video_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records that the video couldn't be analyzed, so it isn't retried in the background.
+    pub async fn set_failure(&self, video_id: &str, error: &str) -> Result<()> {
+        let failed_on = unix_timestamp_now();
+        sqlx::query!(
+            "INSERT INTO video_motion_failures (video_id, error, failed_on)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (video_id) DO UPDATE SET error = $2, failed_on = $3",
+            video_id,
+            error,
+            failed_on,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::data::database::Database;
+    use crate::service::fixtures::persist_video;
+
+    #[sqlx::test]
+    async fn test_set_and_get_motion(pool: SqlitePool) -> Result<()> {
+        let db = Database::with_pool(pool);
+        let video = persist_video(&db).await?;
+        assert_eq!(db.motion.get_motion(&video.id).await?, None);
+        assert_eq!(
+            db.motion.list_unanalyzed_videos().await?,
+            vec![video.id.clone()]
+        );
+
+        let mut motion = VideoMotion {
+            video_id: video.id.clone(),
+            sample_interval: 0.5,
+            scores: vec![0.0, 0.1, 0.25],
+        };
+        db.motion.set_failure(&video.id, "invalid data").await?;
+        assert!(db.motion.list_unanalyzed_videos().await?.is_empty());
+        db.motion.set_motion(&motion).await?;
+        assert!(db.motion.list_unanalyzed_videos().await?.is_empty());
+        motion.scores.push(0.5);
+        db.motion.set_motion(&motion).await?;
+
+        let videos = db
+            .motion
+            .get_motion_for_videos(&[&video.id, "missing"])
+            .await?;
+        assert_eq!(videos, vec![motion]);
+
+        Ok(())
+    }
+}
@@ -5,6 +5,7 @@ use tracing::info;
 
 use crate::server::types::Beats;
 use crate::service::commands::ffmpeg::FfmpegLocation;
+use crate::service::directories::Directories;
 use crate::service::music;
 use crate::Result;
 
@@ -15,6 +16,9 @@ pub struct DbSong {
     pub file_path: String,
     pub duration: f64,
     pub beats: Option<String>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
 }
 
 #[derive(Debug)]
@@ -23,6 +27,9 @@ pub struct CreateSong {
     pub file_path: String,
     pub duration: f64,
     pub beats: Option<Beats>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -36,16 +43,19 @@ impl MusicDatabase {
     }
 
     pub async fn persist_song(&self, song: CreateSong) -> Result<DbSong> {
-        let beats = serde_json::to_string(&song.beats)?;
+        let beats = song.beats.as_ref().map(serde_json::to_string).transpose()?;
 
         let rowid = sqlx::query_scalar!(
-            "INSERT INTO songs (url, file_path, duration, beats) 
-             VALUES ($1, $2, $3, $4)
+            "INSERT INTO songs (url, file_path, duration, beats, title, artist, album)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
              RETURNING rowid",
             song.url,
             song.file_path,
             song.duration,
             beats,
+            song.title,
+            song.artist,
+            song.album,
         )
         .fetch_one(&self.pool)
         .await?;
@@ -56,13 +66,16 @@ impl MusicDatabase {
             file_path: song.file_path,
             duration: song.duration,
             beats: None,
+            title: song.title,
+            artist: song.artist,
+            album: song.album,
         })
     }
 
     pub async fn get_song_by_url(&self, url: &str) -> Result<Option<DbSong>> {
         sqlx::query_as!(
             DbSong,
-            "SELECT rowid, url, file_path, duration, beats FROM songs WHERE url = $1",
+            "SELECT rowid, url, file_path, duration, beats, title, artist, album FROM songs WHERE url = $1",
             url
         )
         .fetch_optional(&self.pool)
@@ -70,10 +83,17 @@ impl MusicDatabase {
         .map_err(From::from)
     }
 
+    pub async fn song_exists_by_path(&self, path: &str) -> Result<bool> {
+        let count = sqlx::query_scalar!("SELECT COUNT(*) FROM songs WHERE file_path = $1", path)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count > 0)
+    }
+
     pub async fn get_song(&self, id: i64) -> Result<DbSong> {
         sqlx::query_as!(
             DbSong,
-            "SELECT rowid, url, file_path, duration, beats FROM songs WHERE rowid = $1",
+            "SELECT rowid, url, file_path, duration, beats, title, artist, album FROM songs WHERE rowid = $1",
             id
         )
         .fetch_one(&self.pool)
@@ -98,7 +118,7 @@ impl MusicDatabase {
 
         let stream = sqlx::query_as!(
             DbSong,
-            "SELECT rowid, url, file_path, duration, beats FROM songs"
+            "SELECT rowid, url, file_path, duration, beats, title, artist, album FROM songs"
         )
         .fetch(&self.pool);
 
@@ -111,6 +131,43 @@ impl MusicDatabase {
         Ok(videos)
     }
 
+    pub async fn find_song(&self, id: i64) -> Result<Option<DbSong>> {
+        sqlx::query_as!(
+            DbSong,
+            "SELECT rowid, url, file_path, duration, beats, title, artist, album FROM songs WHERE rowid = $1",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(From::from)
+    }
+
+    pub async fn update_song_metadata(
+        &self,
+        id: i64,
+        title: Option<&str>,
+        artist: Option<&str>,
+        album: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query!(
+            "UPDATE songs SET title = $1, artist = $2, album = $3 WHERE rowid = $4",
+            title,
+            artist,
+            album,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_song(&self, id: i64) -> Result<()> {
+        sqlx::query!("DELETE FROM songs WHERE rowid = $1", id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
     pub async fn get_songs(&self, song_ids: &[i64]) -> Result<Vec<DbSong>> {
         let mut songs = vec![];
         // TODO wait for SELECT ... FROM foo IN ... support in sqlx
@@ -146,10 +203,16 @@ impl MusicDatabase {
         Ok(())
     }
 
-    pub async fn generate_all_beats(&self, ffmpeg: FfmpegLocation) -> Result<()> {
-        let rows = sqlx::query!("SELECT rowid, file_path FROM songs WHERE beats IS NULL")
-            .fetch_all(&self.pool)
-            .await?;
+    pub async fn generate_all_beats(
+        &self,
+        ffmpeg: FfmpegLocation,
+        directories: Directories,
+    ) -> Result<()> {
+        let rows = sqlx::query!(
+            "SELECT rowid, file_path FROM songs WHERE beats IS NULL OR beats = 'null'"
+        )
+        .fetch_all(&self.pool)
+        .await?;
         if rows.is_empty() {
             return Ok(());
         }
@@ -157,8 +220,12 @@ impl MusicDatabase {
         let mut handles = vec![];
         for row in rows {
             let ffmpeg = ffmpeg.clone();
+            let directories = directories.clone();
             handles.push(spawn_blocking(move || {
-                (music::detect_beats(row.file_path, &ffmpeg), row.rowid)
+                (
+                    music::detect_beats(row.file_path, &directories, &ffmpeg),
+                    row.rowid,
+                )
             }));
         }
 
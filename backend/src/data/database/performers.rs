@@ -1,5 +1,7 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
-use sqlx::SqlitePool;
+use sqlx::{QueryBuilder, Row, SqliteConnection, SqlitePool};
 use tracing::info;
 
 use crate::Result;
@@ -108,6 +110,57 @@ impl PerformersDatabase {
         }
         Ok(())
     }
+
+    /// Moves the performers of one video to another video. Performers the other video
+    /// already has stay with the old video, which is about to be deleted.
+    pub async fn move_performers(
+        &self,
+        from_video_id: &str,
+        to_video_id: &str,
+        connection: &mut SqliteConnection,
+    ) -> Result<()> {
+        sqlx::query!(
+            "UPDATE OR IGNORE video_performers SET video_id = $1 WHERE video_id = $2",
+            to_video_id,
+            from_video_id
+        )
+        .execute(connection)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the performer names for each of the given videos.
+    pub async fn find_names_for_videos(
+        &self,
+        video_ids: &[&str],
+    ) -> Result<HashMap<String, Vec<String>>> {
+        let mut performers: HashMap<String, Vec<String>> = HashMap::new();
+        if video_ids.is_empty() {
+            return Ok(performers);
+        }
+
+        let mut query_builder = QueryBuilder::new(
+            "SELECT vp.video_id, p.name FROM video_performers vp
+             INNER JOIN performers p ON p.id = vp.performer_id
+             WHERE vp.video_id IN (",
+        );
+        let mut list = query_builder.separated(",");
+        for id in video_ids {
+            list.push_bind(id);
+        }
+        list.push_unseparated(") ");
+        query_builder.push(" ORDER BY p.name");
+
+        let rows = query_builder.build().fetch_all(&self.pool).await?;
+        for row in rows {
+            performers
+                .entry(row.try_get("video_id")?)
+                .or_default()
+                .push(row.try_get("name")?);
+        }
+
+        Ok(performers)
+    }
 }
 
 #[cfg(test)]
@@ -177,6 +230,9 @@ mod tests {
             .insert_for_video(&[performer], &video.id)
             .await?;
 
+        let names = db.performers.find_names_for_videos(&[&video.id]).await?;
+        assert_eq!(names[&video.id], vec!["Performer".to_string()]);
+
         Ok(())
     }
 }
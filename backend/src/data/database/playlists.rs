@@ -0,0 +1,189 @@
+use sqlx::{Sqlite, SqlitePool, Transaction};
+use tracing::info;
+
+use super::unix_timestamp_now;
+use crate::Result;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbPlaylist {
+    pub id: i64,
+    pub name: String,
+    /// The songs of the playlist, in order.
+    pub song_ids: Vec<i64>,
+    pub created_on: i64,
+}
+
+struct DbPlaylistRow {
+    id: i64,
+    name: String,
+    created_on: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlaylistsDatabase {
+    pool: SqlitePool,
+}
+
+impl PlaylistsDatabase {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    async fn set_songs(
+        tx: &mut Transaction<'_, Sqlite>,
+        playlist_id: i64,
+        song_ids: &[i64],
+    ) -> Result<()> {
+        sqlx::query!(
+            "DELETE FROM playlist_songs WHERE playlist_id = $1",
+            playlist_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        for (position, song_id) in song_ids.iter().enumerate() {
+            let position = position as i64;
+            sqlx::query!(
+                "INSERT INTO playlist_songs (playlist_id, song_id, position) VALUES ($1, $2, $3)",
+                playlist_id,
+                song_id,
+                position,
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn song_ids(&self, playlist_id: i64) -> Result<Vec<i64>> {
+        sqlx::query_scalar!(
+            "SELECT song_id FROM playlist_songs WHERE playlist_id = $1 ORDER BY position ASC",
+            playlist_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(From::from)
+    }
+
+    async fn with_songs(&self, row: DbPlaylistRow) -> Result<DbPlaylist> {
+        Ok(DbPlaylist {
+            song_ids: self.song_ids(row.id).await?,
+            id: row.id,
+            name: row.name,
+            created_on: row.created_on,
+        })
+    }
+
+    pub async fn create_playlist(&self, name: &str, song_ids: &[i64]) -> Result<i64> {
+        let created_on = unix_timestamp_now();
+        let mut tx = self.pool.begin().await?;
+        let id = sqlx::query_scalar!(
+            r#"INSERT INTO playlists (name, created_on) VALUES ($1, $2) RETURNING id AS "id!""#,
+            name,
+            created_on,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        Self::set_songs(&mut tx, id, song_ids).await?;
+        tx.commit().await?;
+        info!(
+            "created playlist {id} ('{name}') with {} songs",
+            song_ids.len()
+        );
+
+        Ok(id)
+    }
+
+    pub async fn get_playlist(&self, id: i64) -> Result<Option<DbPlaylist>> {
+        let row = sqlx::query_as!(
+            DbPlaylistRow,
+            r#"SELECT id AS "id!", name, created_on FROM playlists WHERE id = $1"#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(Some(self.with_songs(row).await?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn list_playlists(&self) -> Result<Vec<DbPlaylist>> {
+        let rows = sqlx::query_as!(
+            DbPlaylistRow,
+            r#"SELECT id AS "id!", name, created_on FROM playlists ORDER BY name ASC"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut playlists = Vec::with_capacity(rows.len());
+        for row in rows {
+            playlists.push(self.with_songs(row).await?);
+        }
+        Ok(playlists)
+    }
+
+    pub async fn update_playlist(&self, id: i64, name: &str, song_ids: &[i64]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!("UPDATE playlists SET name = $1 WHERE id = $2", name, id)
+            .execute(&mut *tx)
+            .await?;
+        Self::set_songs(&mut tx, id, song_ids).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_playlist(&self, id: i64) -> Result<()> {
+        sqlx::query!("DELETE FROM playlists WHERE id = $1", id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Removes a song from all playlists, used when the song is deleted.
+    pub async fn remove_song(&self, song_id: i64) -> Result<()> {
+        sqlx::query!("DELETE FROM playlist_songs WHERE song_id = $1", song_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::data::database::Database;
+
+    #[sqlx::test]
+    async fn test_playlist_lifecycle(pool: SqlitePool) -> Result<()> {
+        let db = Database::with_pool(pool);
+        let id = db.playlists.create_playlist("Workout", &[3, 1, 2]).await?;
+
+        let playlist = db.playlists.get_playlist(id).await?.unwrap();
+        assert_eq!(playlist.name, "Workout");
+        assert_eq!(playlist.song_ids, vec![3, 1, 2]);
+
+        db.playlists
+            .update_playlist(id, "Cooldown", &[2, 3])
+            .await?;
+        db.playlists.create_playlist("Another", &[3]).await?;
+        db.playlists.remove_song(3).await?;
+        let playlists = db.playlists.list_playlists().await?;
+        assert_eq!(playlists.len(), 2);
+        assert_eq!(playlists[0].song_ids, Vec::<i64>::new());
+        assert_eq!(playlists[1].name, "Cooldown");
+        assert_eq!(playlists[1].song_ids, vec![2]);
+
+        db.playlists.delete_playlist(id).await?;
+        assert_eq!(db.playlists.get_playlist(id).await?, None);
+
+        Ok(())
+    }
+}
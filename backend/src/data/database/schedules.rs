@@ -0,0 +1,246 @@
+use sqlx::SqlitePool;
+use tracing::info;
+
+use super::unix_timestamp_now;
+use crate::Result;
+
+#[derive(Debug, Clone)]
+pub struct DbSchedule {
+    pub id: i64,
+    pub name: String,
+    pub cron: String,
+    pub enabled: bool,
+    pub options_json: String,
+    pub last_run: Option<i64>,
+    pub next_run: Option<i64>,
+    pub created_on: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateSchedule {
+    pub name: String,
+    pub cron: String,
+    pub enabled: bool,
+    pub options_json: String,
+    pub next_run: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DbScheduleRun {
+    pub id: i64,
+    pub schedule_id: i64,
+    pub video_id: Option<String>,
+    pub file_name: Option<String>,
+    pub seed: String,
+    pub error: Option<String>,
+    pub created_on: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateScheduleRun {
+    pub schedule_id: i64,
+    pub video_id: Option<String>,
+    pub file_name: Option<String>,
+    pub seed: String,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SchedulesDatabase {
+    pool: SqlitePool,
+}
+
+impl SchedulesDatabase {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create_schedule(&self, schedule: &CreateSchedule) -> Result<i64> {
+        let created_on = unix_timestamp_now();
+        let id = sqlx::query_scalar!(
+            "INSERT INTO schedules (name, cron, enabled, options_json, next_run, created_on)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             RETURNING id",
+            schedule.name,
+            schedule.cron,
+            schedule.enabled,
+            schedule.options_json,
+            schedule.next_run,
+            created_on,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        info!("created schedule {id} ('{}')", schedule.name);
+
+        Ok(id)
+    }
+
+    pub async fn get_schedule(&self, id: i64) -> Result<Option<DbSchedule>> {
+        sqlx::query_as!(
+            DbSchedule,
+            r#"SELECT id AS "id!", name, cron, enabled, options_json, last_run, next_run, created_on
+               FROM schedules WHERE id = $1"#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(From::from)
+    }
+
+    pub async fn list_schedules(&self) -> Result<Vec<DbSchedule>> {
+        sqlx::query_as!(
+            DbSchedule,
+            r#"SELECT id AS "id!", name, cron, enabled, options_json, last_run, next_run, created_on
+               FROM schedules ORDER BY id ASC"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(From::from)
+    }
+
+    /// Lists all enabled schedules whose next run is at or before `now`.
+    pub async fn get_due_schedules(&self, now: i64) -> Result<Vec<DbSchedule>> {
+        sqlx::query_as!(
+            DbSchedule,
+            r#"SELECT id AS "id!", name, cron, enabled, options_json, last_run, next_run, created_on
+               FROM schedules
+               WHERE enabled = true AND next_run IS NOT NULL AND next_run <= $1
+               ORDER BY next_run ASC"#,
+            now
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(From::from)
+    }
+
+    pub async fn update_schedule(&self, id: i64, schedule: &CreateSchedule) -> Result<()> {
+        sqlx::query!(
+            "UPDATE schedules SET name = $1, cron = $2, enabled = $3, options_json = $4, next_run = $5
+             WHERE id = $6",
+            schedule.name,
+            schedule.cron,
+            schedule.enabled,
+            schedule.options_json,
+            schedule.next_run,
+            id,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_run_times(&self, id: i64, last_run: i64, next_run: Option<i64>) -> Result<()> {
+        sqlx::query!(
+            "UPDATE schedules SET last_run = $1, next_run = $2 WHERE id = $3",
+            last_run,
+            next_run,
+            id,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_schedule(&self, id: i64) -> Result<()> {
+        sqlx::query!("DELETE FROM schedules WHERE id = $1", id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn insert_run(&self, run: &CreateScheduleRun) -> Result<i64> {
+        let created_on = unix_timestamp_now();
+        let id = sqlx::query_scalar!(
+            r#"INSERT INTO schedule_runs (schedule_id, video_id, file_name, seed, error, created_on)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             RETURNING id AS "id!""#,
+            run.schedule_id,
+            run.video_id,
+            run.file_name,
+            run.seed,
+            run.error,
+            created_on,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    pub async fn list_runs(&self, schedule_id: i64) -> Result<Vec<DbScheduleRun>> {
+        sqlx::query_as!(
+            DbScheduleRun,
+            r#"SELECT id AS "id!", schedule_id, video_id, file_name, seed, error, created_on
+               FROM schedule_runs WHERE schedule_id = $1
+               ORDER BY created_on DESC, id DESC"#,
+            schedule_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(From::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::data::database::Database;
+
+    fn schedule(next_run: Option<i64>) -> CreateSchedule {
+        CreateSchedule {
+            name: "Weekly mix".to_string(),
+            cron: "0 10 * * 1".to_string(),
+            enabled: true,
+            options_json: "{}".to_string(),
+            next_run,
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_due_schedules_and_runs(pool: SqlitePool) -> Result<()> {
+        let db = Database::with_pool(pool);
+        let due = db.schedules.create_schedule(&schedule(Some(100))).await?;
+        let later = db.schedules.create_schedule(&schedule(Some(500))).await?;
+        let disabled = db
+            .schedules
+            .create_schedule(&CreateSchedule {
+                enabled: false,
+                ..schedule(Some(100))
+            })
+            .await?;
+
+        let schedules = db.schedules.get_due_schedules(200).await?;
+        assert_eq!(schedules.len(), 1);
+        assert_eq!(schedules[0].id, due);
+
+        db.schedules.set_run_times(due, 200, Some(800)).await?;
+        assert!(db.schedules.get_due_schedules(200).await?.is_empty());
+        assert_eq!(db.schedules.get_due_schedules(600).await?[0].id, later);
+
+        db.schedules
+            .insert_run(&CreateScheduleRun {
+                schedule_id: due,
+                video_id: Some("video".to_string()),
+                file_name: Some("mix.mp4".to_string()),
+                seed: "seed".to_string(),
+                error: None,
+            })
+            .await?;
+        let runs = db.schedules.list_runs(due).await?;
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].seed, "seed");
+
+        db.schedules.delete_schedule(due).await?;
+        assert!(db.schedules.get_schedule(due).await?.is_none());
+        assert!(db.schedules.list_runs(due).await?.is_empty());
+        assert_eq!(db.schedules.list_schedules().await?.len(), 2);
+        assert!(db.schedules.get_schedule(disabled).await?.is_some());
+
+        Ok(())
+    }
+}
@@ -6,9 +6,18 @@ use crate::service::stash_config::StashConfig;
 use crate::Result;
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default, ToSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct Settings {
     pub stash: StashConfig,
     pub handy: Option<HandyConfig>,
+    /// Patterns like `{studio} - {performer} - {title}` that the file names of imported
+    /// videos are matched against.
+    #[serde(default)]
+    pub file_name_templates: Vec<String>,
+    /// Analyze the motion of new local videos in the background. Every video is decoded
+    /// once, so this is off by default.
+    #[serde(default)]
+    pub background_motion_analysis: bool,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default, ToSchema)]
@@ -6,7 +6,7 @@ use camino::Utf8Path;
 use color_eyre::eyre::eyre;
 use futures::TryStreamExt;
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, QueryBuilder, Row, SqlitePool};
+use sqlx::{FromRow, QueryBuilder, Row, SqliteConnection, SqlitePool};
 use tracing::{debug, info};
 use utoipa::{IntoParams, ToSchema};
 
@@ -66,6 +66,27 @@ pub struct DbVideo {
     pub video_created_on: i64,
     pub video_title: Option<String>,
     pub video_tags: Option<String>,
+    pub missing_since: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoFileStatus {
+    pub id: String,
+    pub file_path: String,
+    pub missing_since: Option<i64>,
+    pub file_hash: Option<String>,
+}
+
+/// The hashes of a video that are used to find duplicates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoHashes {
+    pub id: String,
+    pub file_path: String,
+    pub title: Option<String>,
+    pub duration: f64,
+    pub file_hash: Option<String>,
+    pub perceptual_hash: Option<u64>,
+    pub marker_count: i64,
 }
 
 pub fn tags_from_string(tags: Option<&str>) -> Vec<String> {
@@ -152,7 +173,7 @@ impl VideosDatabase {
         let video = sqlx::query_as!(
             DbVideo,
             "SELECT id, file_path, interactive, source AS \"source: VideoSource\",
-                    duration, video_preview_image, stash_scene_id, video_title, video_tags, video_created_on
+                    duration, video_preview_image, stash_scene_id, video_title, video_tags, video_created_on, missing_since
              FROM videos
              WHERE id = $1",
             id)
@@ -162,14 +183,30 @@ impl VideosDatabase {
     }
 
     pub async fn delete_video(&self, id: &str) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        self.delete_video_with(id, &mut transaction).await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Deletes the video and everything that references it, using the given connection so
+    /// it can be part of a larger transaction.
+    pub async fn delete_video_with(
+        &self,
+        id: &str,
+        connection: &mut SqliteConnection,
+    ) -> Result<()> {
         sqlx::query!("DELETE FROM ffprobe_info WHERE video_id = $1", id)
-            .execute(&self.pool)
+            .execute(&mut *connection)
             .await?;
         sqlx::query!("DELETE FROM markers WHERE video_id = $1", id)
-            .execute(&self.pool)
+            .execute(&mut *connection)
+            .await?;
+        sqlx::query!("DELETE FROM video_performers WHERE video_id = $1", id)
+            .execute(&mut *connection)
             .await?;
         sqlx::query!("DELETE FROM videos WHERE id = $1", id)
-            .execute(&self.pool)
+            .execute(&mut *connection)
             .await?;
         Ok(())
     }
@@ -210,6 +247,105 @@ impl VideosDatabase {
         Ok(!records.is_empty())
     }
 
+    /// Lists the paths of all videos that aren't from stash, and since when their file is
+    /// missing (if it is).
+    pub async fn list_local_video_files(&self) -> Result<Vec<VideoFileStatus>> {
+        sqlx::query_as!(
+            VideoFileStatus,
+            "SELECT id, file_path, missing_since, file_hash FROM videos WHERE source != 'stash'"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(From::from)
+    }
+
+    pub async fn set_missing_since(&self, id: &str, missing_since: Option<i64>) -> Result<()> {
+        sqlx::query!(
+            "UPDATE videos SET missing_since = $1 WHERE id = $2",
+            missing_since,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_file_hash(&self, id: &str, file_hash: &str) -> Result<()> {
+        sqlx::query!(
+            "UPDATE videos SET file_hash = $1 WHERE id = $2",
+            file_hash,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_perceptual_hash(&self, id: &str, perceptual_hash: u64) -> Result<()> {
+        let perceptual_hash = perceptual_hash as i64;
+        sqlx::query!(
+            "UPDATE videos SET perceptual_hash = $1 WHERE id = $2",
+            perceptual_hash,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the local videos that have the given content hash.
+    pub async fn find_by_file_hash(&self, file_hash: &str) -> Result<Vec<VideoFileStatus>> {
+        sqlx::query_as!(
+            VideoFileStatus,
+            "SELECT id, file_path, missing_since, file_hash FROM videos
+            WHERE file_hash = $1 AND source != 'stash'",
+            file_hash
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(From::from)
+    }
+
+    pub async fn list_video_hashes(&self) -> Result<Vec<VideoHashes>> {
+        let records = sqlx::query!(
+            "SELECT v.id, v.file_path, v.video_title, v.duration, v.file_hash, v.perceptual_hash,
+                (SELECT COUNT(*) FROM markers m WHERE m.video_id = v.id) AS \"marker_count!: i64\"
+            FROM videos v
+            WHERE v.source != 'stash'"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records
+            .into_iter()
+            .map(|r| VideoHashes {
+                id: r.id,
+                file_path: r.file_path,
+                title: r.video_title,
+                duration: r.duration,
+                file_hash: r.file_hash,
+                perceptual_hash: r.perceptual_hash.map(|h| h as u64),
+                marker_count: r.marker_count,
+            })
+            .collect())
+    }
+
+    /// Points the video to a new file, e.g. after it was moved. Clears the missing flag.
+    pub async fn set_file_path(&self, id: &str, file_path: &str) -> Result<()> {
+        sqlx::query!(
+            "UPDATE videos SET file_path = $1, missing_since = NULL WHERE id = $2",
+            file_path,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn get_video_ids_with_markers(&self) -> Result<Vec<String>> {
         let records = sqlx::query_scalar!("SELECT DISTINCT video_id FROM markers")
             .fetch_all(&self.pool)
@@ -239,6 +375,7 @@ impl VideosDatabase {
                 video_created_on: records[0].video_created_on,
                 video_tags: records[0].video_tags.clone(),
                 video_title: records[0].video_title.clone(),
+                missing_since: records[0].missing_since,
             };
             let markers = records
                 .into_iter()
@@ -265,7 +402,7 @@ impl VideosDatabase {
                 sqlx::query_as!(
                     DbVideo,
                     "SELECT id, file_path, interactive, source AS \"source: VideoSource\", duration, video_preview_image,
-                                stash_scene_id, video_title, video_tags, video_created_on
+                                stash_scene_id, video_title, video_tags, video_created_on, missing_since
                     FROM videos
                     WHERE duration = -1.0")
                     .fetch_all(&self.pool)
@@ -275,7 +412,7 @@ impl VideosDatabase {
                 sqlx::query_as!(
                     DbVideo,
                     "SELECT id, file_path, interactive, source AS \"source: VideoSource\", duration, video_preview_image,
-                            stash_scene_id, video_title, video_tags, video_created_on
+                            stash_scene_id, video_title, video_tags, video_created_on, missing_since
                     FROM videos
                     WHERE video_preview_image IS NULL"
                 )
@@ -286,7 +423,7 @@ impl VideosDatabase {
                 sqlx::query_as!(
                     DbVideo,
                     "SELECT id, file_path, interactive, source AS \"source: VideoSource\",
-                            duration, video_preview_image, stash_scene_id, video_title, video_tags, video_created_on
+                            duration, video_preview_image, stash_scene_id, video_title, video_tags, video_created_on, missing_since
                     FROM videos
                     WHERE video_title IS NULL")
                     .fetch_all(&self.pool)
@@ -296,7 +433,7 @@ impl VideosDatabase {
                 sqlx::query_as!(
                     DbVideo,
                     "SELECT id, file_path, interactive, source AS \"source: VideoSource\",
-                            duration, video_preview_image, stash_scene_id, video_title, video_tags, video_created_on
+                            duration, video_preview_image, stash_scene_id, video_title, video_tags, video_created_on, missing_since
                     FROM videos v
                     WHERE (SELECT count(*) FROM video_performers vp WHERE vp.video_id = v.id) = 0 AND
                           v.stash_scene_id IS NOT NULL
@@ -309,7 +446,7 @@ impl VideosDatabase {
                 sqlx::query_as!(
                     DbVideo,
                     "SELECT id, file_path, interactive, source AS \"source: VideoSource\",
-                            duration, video_preview_image, stash_scene_id, video_title, video_tags, video_created_on
+                            duration, video_preview_image, stash_scene_id, video_title, video_tags, video_created_on, missing_since
                     FROM videos
                     WHERE video_tags NOT LIKE '[%'"
                 )
@@ -339,11 +476,12 @@ impl VideosDatabase {
     }
 
     pub async fn persist_video(&self, video: &CreateVideo) -> Result<DbVideo> {
-        let created_on = video.created_on.unwrap_or_else(|| unix_timestamp_now());
+        let imported_on = unix_timestamp_now();
+        let created_on = video.created_on.unwrap_or(imported_on);
         let inserted = sqlx::query!(
             "INSERT INTO videos
-            (id, file_path, interactive, source, duration, video_preview_image, stash_scene_id, video_title, video_tags, video_created_on)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            (id, file_path, interactive, source, duration, video_preview_image, stash_scene_id, video_title, video_tags, video_created_on, video_imported_on)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
             RETURNING video_created_on",
             video.id,
             video.file_path,
@@ -355,6 +493,7 @@ impl VideosDatabase {
             video.title,
             video.tags,
             created_on,
+            imported_on,
         )
         .fetch_one(&self.pool)
         .await?;
@@ -370,6 +509,7 @@ impl VideosDatabase {
             video_created_on: inserted.video_created_on,
             video_tags: video.tags.clone(),
             video_title: video.title.clone(),
+            missing_since: None,
         })
     }
 
@@ -468,6 +608,7 @@ impl VideosDatabase {
             stash_scene_id: Option<i64>,
             video_tags: Option<String>,
             video_title: Option<String>,
+            missing_since: Option<i64>,
             marker_count: i64,
         }
 
@@ -490,7 +631,7 @@ impl VideosDatabase {
 
         let mut query_builder = QueryBuilder::new(
             "SELECT v.id, v.file_path, v.interactive, v.duration, v.video_created_on, v.source, v.video_preview_image,
-                    v.stash_scene_id, v.video_tags, v.video_title, COUNT(m.video_id) AS marker_count
+                    v.stash_scene_id, v.video_tags, v.video_title, v.missing_since, COUNT(m.video_id) AS marker_count
             FROM videos v
             LEFT JOIN markers m ON v.id = m.video_id ",
         );
@@ -566,6 +707,7 @@ impl VideosDatabase {
                     video_created_on: row.video_created_on,
                     video_tags: row.video_tags,
                     video_title: row.video_title,
+                    missing_since: row.missing_since,
                 };
                 videos.push(ListVideoDto {
                     video: video.into(),
@@ -588,6 +730,19 @@ impl VideosDatabase {
         Ok(())
     }
 
+    /// Sets whether the video has a funscript. Returns whether the flag changed.
+    pub async fn set_video_interactive(&self, id: &str, interactive: bool) -> Result<bool> {
+        let result = sqlx::query!(
+            "UPDATE videos SET interactive = $1 WHERE id = $2 AND interactive != $1",
+            interactive,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     pub async fn set_video_preview_image(
         &self,
         id: &str,
@@ -642,4 +797,25 @@ impl VideosDatabase {
         let videos: Vec<_> = rows.iter().map(|v| DbVideo::from_row(v).unwrap()).collect();
         Ok(videos)
     }
+
+    /// Returns the IDs of the given videos that were imported at or after `since`.
+    pub async fn get_imported_since(&self, ids: &[&str], since: i64) -> Result<HashSet<String>> {
+        if ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let mut query_builder = QueryBuilder::new("SELECT id FROM videos WHERE id IN (");
+        let mut list = query_builder.separated(",");
+        for id in ids {
+            list.push_bind(id);
+        }
+        list.push_unseparated(") ");
+        query_builder.push(" AND video_imported_on >= ");
+        query_builder.push_bind(since);
+
+        let rows = query_builder.build().fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|row| row.try_get("id").map_err(From::from))
+            .collect()
+    }
 }
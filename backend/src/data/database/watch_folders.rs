@@ -0,0 +1,166 @@
+use sqlx::SqlitePool;
+use tracing::info;
+
+use super::unix_timestamp_now;
+use crate::Result;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbWatchFolder {
+    pub id: i64,
+    pub path: String,
+    pub recurse: bool,
+    /// JSON array of tags that imported videos get.
+    pub tags: String,
+    pub enabled: bool,
+    /// Whether imported videos are tagged with the folders they're in.
+    pub folder_tags: bool,
+    /// How many folders below the watch folder are used as tags, all if not set.
+    pub folder_tag_depth: Option<i64>,
+    pub last_scan: Option<i64>,
+    pub created_on: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateWatchFolder {
+    pub path: String,
+    pub recurse: bool,
+    pub tags: String,
+    pub enabled: bool,
+    pub folder_tags: bool,
+    pub folder_tag_depth: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchFoldersDatabase {
+    pool: SqlitePool,
+}
+
+impl WatchFoldersDatabase {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create_watch_folder(&self, folder: &CreateWatchFolder) -> Result<i64> {
+        let created_on = unix_timestamp_now();
+        let id = sqlx::query_scalar!(
+            r#"INSERT INTO watch_folders
+             (path, recurse, tags, enabled, folder_tags, folder_tag_depth, created_on)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
+             RETURNING id AS "id!""#,
+            folder.path,
+            folder.recurse,
+            folder.tags,
+            folder.enabled,
+            folder.folder_tags,
+            folder.folder_tag_depth,
+            created_on,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        info!("created watch folder {id} for {}", folder.path);
+
+        Ok(id)
+    }
+
+    pub async fn get_watch_folder(&self, id: i64) -> Result<Option<DbWatchFolder>> {
+        sqlx::query_as!(
+            DbWatchFolder,
+            r#"SELECT id AS "id!", path, recurse, tags, enabled, folder_tags, folder_tag_depth,
+                last_scan, created_on
+               FROM watch_folders WHERE id = $1"#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(From::from)
+    }
+
+    pub async fn list_watch_folders(&self) -> Result<Vec<DbWatchFolder>> {
+        sqlx::query_as!(
+            DbWatchFolder,
+            r#"SELECT id AS "id!", path, recurse, tags, enabled, folder_tags, folder_tag_depth,
+                last_scan, created_on
+               FROM watch_folders ORDER BY path ASC"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(From::from)
+    }
+
+    pub async fn update_watch_folder(&self, id: i64, folder: &CreateWatchFolder) -> Result<()> {
+        sqlx::query!(
+            "UPDATE watch_folders SET path = $1, recurse = $2, tags = $3, enabled = $4,
+             folder_tags = $5, folder_tag_depth = $6
+             WHERE id = $7",
+            folder.path,
+            folder.recurse,
+            folder.tags,
+            folder.enabled,
+            folder.folder_tags,
+            folder.folder_tag_depth,
+            id,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_last_scan(&self, id: i64, last_scan: i64) -> Result<()> {
+        sqlx::query!(
+            "UPDATE watch_folders SET last_scan = $1 WHERE id = $2",
+            last_scan,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_watch_folder(&self, id: i64) -> Result<()> {
+        sqlx::query!("DELETE FROM watch_folders WHERE id = $1", id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::data::database::Database;
+
+    #[sqlx::test]
+    async fn test_watch_folder_lifecycle(pool: SqlitePool) -> Result<()> {
+        let db = Database::with_pool(pool);
+        let mut folder = CreateWatchFolder {
+            path: "/videos/new".to_string(),
+            recurse: true,
+            tags: "[\"new\"]".to_string(),
+            enabled: true,
+            folder_tags: true,
+            folder_tag_depth: Some(1),
+        };
+        let id = db.watch_folders.create_watch_folder(&folder).await?;
+
+        folder.enabled = false;
+        folder.folder_tag_depth = None;
+        db.watch_folders.update_watch_folder(id, &folder).await?;
+        db.watch_folders.set_last_scan(id, 1000).await?;
+        let stored = db.watch_folders.get_watch_folder(id).await?.unwrap();
+        assert!(!stored.enabled);
+        assert!(stored.folder_tags);
+        assert_eq!(stored.folder_tag_depth, None);
+        assert_eq!(stored.last_scan, Some(1000));
+        assert_eq!(db.watch_folders.list_watch_folders().await?, vec![stored]);
+
+        db.watch_folders.delete_watch_folder(id).await?;
+        assert_eq!(db.watch_folders.get_watch_folder(id).await?, None);
+
+        Ok(())
+    }
+}
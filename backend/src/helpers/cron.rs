@@ -0,0 +1,182 @@
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use color_eyre::eyre::{bail, eyre};
+use color_eyre::Report;
+use time::{Date, Duration, OffsetDateTime, UtcOffset};
+
+/// How many days to look ahead when searching for the next matching time.
+/// Large enough for rules that only match on February 29th.
+const MAX_DAYS_AHEAD: usize = 366 * 8;
+
+/// A cron expression with the five standard fields: minute, hour, day of month, month
+/// and day of week (0 or 7 is Sunday). Supports `*`, lists (`1,15`), ranges (`1-5`) and
+/// steps (`*/15`). All times are evaluated in UTC.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minutes: BTreeSet<u8>,
+    hours: BTreeSet<u8>,
+    days_of_month: BTreeSet<u8>,
+    months: BTreeSet<u8>,
+    days_of_week: BTreeSet<u8>,
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+}
+
+fn parse_field(field: &str, min: u8, max: u8) -> Result<BTreeSet<u8>, Report> {
+    let mut values = BTreeSet::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u8>()?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            bail!("step must not be zero in cron field '{field}'");
+        }
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (start.parse()?, end.parse()?)
+        } else {
+            let value = range.parse()?;
+            (value, if step > 1 { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            bail!("value out of range ({min}-{max}) in cron field '{field}'");
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+
+    Ok(values)
+}
+
+impl FromStr for CronSchedule {
+    type Err = Report;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<_> = expression.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(eyre!(
+                "cron expression '{expression}' must have exactly five fields"
+            ));
+        };
+
+        let mut days_of_week = parse_field(day_of_week, 0, 7)?;
+        if days_of_week.remove(&7) {
+            days_of_week.insert(0);
+        }
+
+        Ok(CronSchedule {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days_of_month: parse_field(day_of_month, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            days_of_week,
+            day_of_month_restricted: day_of_month != "*",
+            day_of_week_restricted: day_of_week != "*",
+        })
+    }
+}
+
+impl CronSchedule {
+    fn matches_day(&self, date: Date) -> bool {
+        if !self.months.contains(&u8::from(date.month())) {
+            return false;
+        }
+        let day_of_month = self.days_of_month.contains(&date.day());
+        let day_of_week = self
+            .days_of_week
+            .contains(&date.weekday().number_days_from_sunday());
+
+        // like cron: if both day fields are restricted, either of them has to match
+        match (self.day_of_month_restricted, self.day_of_week_restricted) {
+            (true, true) => day_of_month || day_of_week,
+            (true, false) => day_of_month,
+            (false, true) => day_of_week,
+            (false, false) => true,
+        }
+    }
+
+    /// Returns the first time strictly after `after` that matches the schedule.
+    pub fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        let start = after
+            .to_offset(UtcOffset::UTC)
+            .replace_second(0)
+            .ok()?
+            .replace_nanosecond(0)
+            .ok()?
+            + Duration::minutes(1);
+        let mut date = start.date();
+        let (mut from_hour, mut from_minute) = (start.hour(), start.minute());
+
+        for _ in 0..MAX_DAYS_AHEAD {
+            if self.matches_day(date) {
+                for &hour in self.hours.range(from_hour..) {
+                    let min_minute = if hour == from_hour { from_minute } else { 0 };
+                    if let Some(&minute) = self.minutes.range(min_minute..).next() {
+                        return Some(date.with_hms(hour, minute, 0).ok()?.assume_utc());
+                    }
+                }
+            }
+            date = date.next_day()?;
+            from_hour = 0;
+            from_minute = 0;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_invalid_expressions() {
+        assert!("* * * *".parse::<CronSchedule>().is_err());
+        assert!("60 * * * *".parse::<CronSchedule>().is_err());
+        assert!("*/0 * * * *".parse::<CronSchedule>().is_err());
+        assert!("0 10 * * MON".parse::<CronSchedule>().is_err());
+        assert!("0 10 * * 1-5".parse::<CronSchedule>().is_ok());
+    }
+
+    #[test]
+    fn test_every_monday() {
+        let schedule: CronSchedule = "0 10 * * 1".parse().unwrap();
+        // 2026-10-18 is a Sunday
+        let next = schedule.next_after(datetime!(2026-10-18 12:30 UTC));
+        assert_eq!(next, Some(datetime!(2026-10-19 10:00 UTC)));
+        let next = schedule.next_after(datetime!(2026-10-19 10:00 UTC));
+        assert_eq!(next, Some(datetime!(2026-10-26 10:00 UTC)));
+    }
+
+    #[test]
+    fn test_steps_and_lists() {
+        let schedule: CronSchedule = "*/15 8,20 * * *".parse().unwrap();
+        let next = schedule.next_after(datetime!(2026-10-18 08:14:59 UTC));
+        assert_eq!(next, Some(datetime!(2026-10-18 08:15 UTC)));
+        let next = schedule.next_after(datetime!(2026-10-18 08:45 UTC));
+        assert_eq!(next, Some(datetime!(2026-10-18 20:00 UTC)));
+        let next = schedule.next_after(datetime!(2026-10-18 20:50 UTC));
+        assert_eq!(next, Some(datetime!(2026-10-19 08:00 UTC)));
+    }
+
+    #[test]
+    fn test_day_of_month_or_day_of_week() {
+        // the 1st of the month or any Saturday
+        let schedule: CronSchedule = "0 0 1 * 6".parse().unwrap();
+        let next = schedule.next_after(datetime!(2026-10-18 00:00 UTC));
+        assert_eq!(next, Some(datetime!(2026-10-24 00:00 UTC)));
+        let next = schedule.next_after(datetime!(2026-10-31 00:00 UTC));
+        assert_eq!(next, Some(datetime!(2026-11-01 00:00 UTC)));
+    }
+
+    #[test]
+    fn test_leap_day() {
+        let schedule: CronSchedule = "0 0 29 2 *".parse().unwrap();
+        let next = schedule.next_after(datetime!(2026-10-18 00:00 UTC));
+        assert_eq!(next, Some(datetime!(2028-02-29 00:00 UTC)));
+    }
+}
@@ -3,9 +3,11 @@ use lazy_static::lazy_static;
 
 use crate::Result;
 
+pub mod cron;
 pub mod estimator;
 pub mod log;
 pub mod math;
+pub mod oshash;
 pub mod random;
 pub mod sentry;
 pub mod util;
@@ -13,7 +15,8 @@ pub mod util;
 lazy_static! {
     static ref PARALLELISM: usize = {
         let cpus = num_cpus::get();
-        cpus / 2
+        // at least one, or nothing would ever run on single core machines
+        (cpus / 2).max(1)
     };
 }
 
@@ -0,0 +1,92 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+use camino::Utf8Path;
+
+use crate::Result;
+
+/// Number of bytes read from the start and from the end of the file.
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+fn sum_words(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.chunks(8).fold(hash, |hash, word| {
+        let mut buffer = [0; 8];
+        buffer[..word.len()].copy_from_slice(word);
+        hash.wrapping_add(u64::from_le_bytes(buffer))
+    })
+}
+
+fn read_chunk(file: &mut File, offset: u64, length: u64) -> Result<Vec<u8>> {
+    let mut buffer = vec![0; length as usize];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Computes the OpenSubtitles hash of a file: its size plus the sum of the 64-bit words of
+/// the first and last 64 KiB. Only reads 128 KiB, so it's fast even for huge videos, and it's
+/// the same hash that stash stores as `oshash`.
+pub fn oshash(path: impl AsRef<Utf8Path>) -> Result<String> {
+    let mut file = File::open(path.as_ref())?;
+    let size = file.metadata()?.len();
+    let chunk_size = CHUNK_SIZE.min(size);
+
+    let head = read_chunk(&mut file, 0, chunk_size)?;
+    let tail = read_chunk(&mut file, size - chunk_size, chunk_size)?;
+    let hash = sum_words(sum_words(size, &head), &tail);
+
+    Ok(format!("{hash:016x}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use camino::Utf8PathBuf;
+
+    use super::*;
+
+    fn write_file(name: &str, content: &[u8]) -> Utf8PathBuf {
+        let path = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .unwrap()
+            .join(format!("clip-mash-oshash-{name}"));
+        File::create(&path).unwrap().write_all(content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_sum_words() {
+        assert_eq!(sum_words(0, &[1, 0, 0, 0, 0, 0, 0, 0, 2]), 3);
+        assert_eq!(sum_words(u64::MAX, &[1, 0, 0, 0, 0, 0, 0, 0]), 0);
+    }
+
+    #[test]
+    fn test_oshash() {
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let path = write_file("large", &content);
+        let hash = oshash(&path).unwrap();
+        assert_eq!(hash.len(), 16);
+        assert_eq!(hash, oshash(&path).unwrap());
+
+        let mut changed = content.clone();
+        changed[10] += 1;
+        let changed_path = write_file("changed", &changed);
+        assert_ne!(hash, oshash(&changed_path).unwrap());
+
+        // bytes in the middle of the file aren't part of the hash
+        let mut middle = content.clone();
+        middle[100_000] += 1;
+        let middle_path = write_file("middle", &middle);
+        assert_eq!(hash, oshash(&middle_path).unwrap());
+
+        let small_path = write_file("small", &[1, 2, 3]);
+        assert_eq!(
+            oshash(&small_path).unwrap(),
+            format!("{:016x}", 3 + 2 * 0x030201)
+        );
+
+        for path in [path, changed_path, middle_path, small_path] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
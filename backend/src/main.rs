@@ -64,7 +64,7 @@ fn get_address() -> SocketAddr {
 async fn run() -> Result<()> {
     use clip_mash::server::{handlers, static_files};
     use clip_mash::service::commands::ffmpeg;
-    use clip_mash::service::migrations;
+    use clip_mash::service::{integrity, migrations, motion, scheduler, watch_folders};
 
     let directories = Directories::new()?;
     let ffmpeg_location = ffmpeg::download_ffmpeg(&directories).await?;
@@ -87,6 +87,13 @@ async fn run() -> Result<()> {
         directories.clone(),
         ffmpeg_location.clone(),
     );
+    scheduler::run_async(
+        database.clone(),
+        directories.clone(),
+        ffmpeg_location.clone(),
+    );
+    integrity::run_async(database.clone(), ffmpeg_location.clone());
+    motion::run_async(database.clone(), ffmpeg_location.clone());
 
     let state = Arc::new(AppState {
         database,
@@ -94,6 +101,23 @@ async fn run() -> Result<()> {
         ffmpeg_location,
         new_version_checker: NewVersionChecker::new(),
     });
+    watch_folders::run_async(state.clone());
+
+    let watch_folder_routes = Router::new()
+        .route(
+            "/",
+            get(handlers::watch_folder::list_watch_folders)
+                .post(handlers::watch_folder::create_watch_folder),
+        )
+        .route(
+            "/{id}",
+            put(handlers::watch_folder::update_watch_folder)
+                .delete(handlers::watch_folder::delete_watch_folder),
+        )
+        .route(
+            "/{id}/scan",
+            post(handlers::watch_folder::scan_watch_folder),
+        );
 
     let library_routes = Router::new()
         .route("/video", get(handlers::library::list_videos))
@@ -112,6 +136,10 @@ async fn run() -> Result<()> {
             post(handlers::files::cleanup_folder),
         )
         .route("/video/cleanup", post(handlers::library::cleanup_videos))
+        .route(
+            "/video/folder-tags",
+            post(handlers::library::apply_folder_tags),
+        )
         .route("/video/stash", get(handlers::library::list_stash_videos))
         .route("/video/tags", get(handlers::library::list_video_tags))
         .route("/video/{id}", get(handlers::library::get_video))
@@ -120,6 +148,21 @@ async fn run() -> Result<()> {
             "/video/{id}/detect-markers",
             post(handlers::library::detect_markers),
         )
+        .route(
+            "/video/{id}/chapters",
+            get(handlers::library::list_video_chapters),
+        )
+        .route(
+            "/video/{id}/chapters/markers",
+            post(handlers::library::create_chapter_markers),
+        )
+        .route(
+            "/video/{id}/motion",
+            post(handlers::library::analyze_video_motion),
+        )
+        .route("/video/{id}/verify", post(handlers::library::verify_video))
+        .route("/integrity", get(handlers::library::list_broken_videos))
+        .route("/integrity/verify", post(handlers::library::verify_videos))
         .route("/video/{id}/file", get(handlers::library::get_video_file))
         .route(
             "/video/{id}/preview",
@@ -136,12 +179,24 @@ async fn run() -> Result<()> {
         )
         .route("/marker/{id}/split", post(handlers::library::split_marker))
         .route("/performers", get(handlers::library::list_performers))
+        .route("/health", get(handlers::library::check_library_health))
+        .route("/duplicates", get(handlers::library::list_duplicate_videos))
+        .route(
+            "/duplicates/merge",
+            post(handlers::library::merge_duplicate_videos),
+        )
+        .route("/health/remap", post(handlers::library::remap_video_paths))
+        .route(
+            "/health/relink",
+            post(handlers::library::relink_missing_videos),
+        )
         .route("/directory", get(handlers::files::list_file_entries))
         .route("/stats", get(handlers::files::get_file_stats))
         .route(
             "/migrate/preview",
             post(handlers::library::migrate_preview_images),
-        );
+        )
+        .nest("/watch-folder", watch_folder_routes);
 
     let project_routes = Router::new()
         .route("/clips", post(handlers::project::fetch_clips))
@@ -165,7 +220,26 @@ async fn run() -> Result<()> {
             "/description/{type}",
             post(handlers::project::generate_description),
         )
-        .route("/random-seed", get(handlers::project::generate_random_seed));
+        .route("/random-seed", get(handlers::project::generate_random_seed))
+        .route("/variants", post(handlers::project::create_variants))
+        .route(
+            "/per-tag",
+            post(handlers::project::create_per_tag_compilations),
+        )
+        .route("/clip-list", post(handlers::project::create_clip_list))
+        .route("/clip-list/{id}", get(handlers::project::get_clip_list))
+        .route(
+            "/clip-list/{id}",
+            delete(handlers::project::delete_clip_list),
+        )
+        .route(
+            "/clip-list/{id}/edit",
+            post(handlers::project::edit_clip_list),
+        )
+        .route(
+            "/clip-list/{id}/undo",
+            post(handlers::project::undo_clip_list_edit),
+        );
 
     let stash_routes = Router::new().route("/health", get(handlers::stash::get_stash_health));
 
@@ -182,7 +256,34 @@ async fn run() -> Result<()> {
         .route("/{id}/stream", get(handlers::music::stream_song))
         .route("/download", post(handlers::music::download_music))
         .route("/upload", post(handlers::music::upload_music))
-        .route("/{id}/beats", get(handlers::music::get_beats));
+        .route("/import", post(handlers::music::import_music))
+        .route("/from-video/{id}", post(handlers::music::song_from_video))
+        .route(
+            "/{id}",
+            put(handlers::music::update_song).delete(handlers::music::delete_song),
+        )
+        .route(
+            "/playlist",
+            get(handlers::music::list_playlists).post(handlers::music::create_playlist),
+        )
+        .route(
+            "/playlist/{id}",
+            put(handlers::music::update_playlist).delete(handlers::music::delete_playlist),
+        )
+        .route(
+            "/{id}/beats",
+            get(handlers::music::get_beats).put(handlers::music::edit_beats),
+        )
+        .route("/{id}/beats/reset", post(handlers::music::reset_beats))
+        .route("/{id}/waveform", get(handlers::music::get_waveform));
+
+    let schedule_routes = Router::new()
+        .route("/", get(handlers::schedule::list_schedules))
+        .route("/", post(handlers::schedule::create_schedule))
+        .route("/{id}", put(handlers::schedule::update_schedule))
+        .route("/{id}", delete(handlers::schedule::delete_schedule))
+        .route("/{id}/runs", get(handlers::schedule::list_schedule_runs))
+        .route("/{id}/run", post(handlers::schedule::run_schedule));
 
     let progress_routes = Router::new()
         .route("/{id}/stream", get(handlers::progress::get_progress_stream))
@@ -203,7 +304,8 @@ async fn run() -> Result<()> {
         .nest("/system", system_routes)
         .nest("/song", music_routes)
         .nest("/progress", progress_routes)
-        .nest("/handy", handy_routes);
+        .nest("/handy", handy_routes)
+        .nest("/schedule", schedule_routes);
 
     let app = Router::new()
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
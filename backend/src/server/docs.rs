@@ -1,26 +1,42 @@
 use utoipa::OpenApi;
 
 use super::handlers::files::{FileSystemEntry, ListFileEntriesResponse};
-use super::handlers::library::{CreateMarkerRequest, VideoCleanupResponse};
-use super::handlers::music::SongUpload;
+use super::handlers::library::{
+    ApplyFolderTagsBody, ApplyFolderTagsResponse, CreateChapterMarkersBody, CreateMarkerRequest,
+    VerifyVideosBody, VideoCleanupResponse,
+};
+use super::handlers::music::{ImportMusicBody, SongUpload};
 use super::handlers::project::{CreateFunscriptBody, DescriptionData, ProjectCreateResponse};
 use super::types::*;
+use crate::data::database::integrity::{VerificationMode, VideoIntegrity};
 use crate::data::database::markers::MarkerCount;
+use crate::data::database::motion::VideoMotion;
 use crate::data::database::videos::{TagCount, VideoSource, VideoUpdate};
 use crate::data::database::{HandyConfig, Settings};
 use crate::server::handlers::handy::{HandyConnectedResponse, StartHandyParameters};
 use crate::server::handlers::library::ListPerformerResponse;
-use crate::server::handlers::{files, handy, library, music, progress, project, stash, system};
+use crate::server::handlers::{
+    files, handy, library, music, progress, project, schedule, stash, system, watch_folder,
+};
+use crate::service::commands::ffprobe::VideoChapter;
 use crate::service::description_generator::DescriptionType;
 use crate::service::directories::FolderType;
-use crate::service::generator::PaddingType;
+use crate::service::duplicates::{
+    DuplicateGroup, DuplicateReason, DuplicateVideo, MergeDuplicatesBody, MergeDuplicatesResult,
+};
+use crate::service::generator::{BrokenVideoHandling, PaddingType};
 use crate::service::handy::patterns::accellerate::AccellerateParameters;
 use crate::service::handy::patterns::cycle_accellerate::CycleAccellerateParameters;
 use crate::service::handy::patterns::random::RandomParameters;
 use crate::service::handy::patterns::{ControllerStatus, HandyPattern, Range};
+use crate::service::library_health::{
+    LibraryHealthReport, MissingVideo, RelinkVideosBody, RelinkVideosResult, RelocatedVideo,
+    RemapPathsBody,
+};
 use crate::service::new_version_checker::AppVersion;
 use crate::service::stash_config::StashConfig;
-use crate::service::video::AddVideosRequest;
+use crate::service::video::{AddVideosRequest, FolderTagOptions};
+use crate::service::watch_folders::WatchFolderScanResult;
 
 #[derive(OpenApi)]
 #[openapi(
@@ -31,6 +47,9 @@ use crate::service::video::AddVideosRequest;
         library::delete_marker,
         library::delete_video,
         library::detect_markers,
+        library::list_video_chapters,
+        library::create_chapter_markers,
+        library::analyze_video_motion,
         library::get_video,
         library::list_markers,
         library::list_videos,
@@ -44,6 +63,15 @@ use crate::service::video::AddVideosRequest;
         library::list_marker_titles,
         library::list_performers,
         library::list_video_tags,
+        library::check_library_health,
+        library::remap_video_paths,
+        library::relink_missing_videos,
+        library::list_duplicate_videos,
+        library::apply_folder_tags,
+        library::verify_video,
+        library::verify_videos,
+        library::list_broken_videos,
+        library::merge_duplicate_videos,
         files::list_file_entries,
         files::get_file_stats,
         files::cleanup_folder,
@@ -59,11 +87,29 @@ use crate::service::video::AddVideosRequest;
         project::list_finished_videos,
         project::generate_description,
         project::generate_random_seed,
+        project::create_variants,
+        project::create_per_tag_compilations,
+        project::create_clip_list,
+        project::get_clip_list,
+        project::edit_clip_list,
+        project::undo_clip_list_edit,
+        project::delete_clip_list,
         stash::get_stash_health,
         music::list_songs,
         music::get_beats,
+        music::edit_beats,
+        music::reset_beats,
+        music::get_waveform,
         music::upload_music,
         music::download_music,
+        music::import_music,
+        music::song_from_video,
+        music::update_song,
+        music::delete_song,
+        music::list_playlists,
+        music::create_playlist,
+        music::update_playlist,
+        music::delete_playlist,
         system::get_version,
         system::get_config,
         system::set_config,
@@ -74,6 +120,17 @@ use crate::service::video::AddVideosRequest;
         handy::pause_handy,
         handy::handy_status,
         handy::handy_connected,
+        schedule::list_schedules,
+        schedule::create_schedule,
+        schedule::update_schedule,
+        schedule::delete_schedule,
+        schedule::list_schedule_runs,
+        schedule::run_schedule,
+        watch_folder::list_watch_folders,
+        watch_folder::create_watch_folder,
+        watch_folder::update_watch_folder,
+        watch_folder::delete_watch_folder,
+        watch_folder::scan_watch_folder,
     ),
     components(
         schemas(
@@ -81,6 +138,52 @@ use crate::service::video::AddVideosRequest;
             MarkerDto,
             VideoDto,
             CreateClipsBody,
+            PinnedClip,
+            FreshnessOptions,
+            FreshnessMode,
+            MotionOptions,
+            VideoMotion,
+            RenderSettings,
+            ClipStatistics,
+            ScreenTimeShare,
+            VideoScreenTimeShare,
+            RenderEstimate,
+            VariantSeeds,
+            CreateVariantsBody,
+            VariantDto,
+            BatchRenderOptions,
+            CreatePerTagCompilationsBody,
+            TagCompilationDto,
+            ScheduleOptions,
+            CreateScheduleBody,
+            ScheduleDto,
+            WatchFolderDto,
+            CreateWatchFolderBody,
+            WatchFolderScanResult,
+            LibraryHealthReport,
+            MissingVideo,
+            RemapPathsBody,
+            RelocatedVideo,
+            RelinkVideosBody,
+            RelinkVideosResult,
+            FolderTagOptions,
+            ApplyFolderTagsBody,
+            ApplyFolderTagsResponse,
+            VerificationMode,
+            VideoIntegrity,
+            VerifyVideosBody,
+            BrokenVideoHandling,
+            DuplicateGroup,
+            DuplicateReason,
+            DuplicateVideo,
+            MergeDuplicatesBody,
+            MergeDuplicatesResult,
+            VideoChapter,
+            CreateChapterMarkersBody,
+            ScheduleRunDto,
+            ClipListEdit,
+            CreateClipListBody,
+            ClipListDto,
             ClipsResponse,
             ClipOrder,
             ClipOptions,
@@ -90,14 +193,20 @@ use crate::service::video::AddVideosRequest;
             RoundRobinClipOptions,
             WeightedRandomClipOptions,
             EqualLengthClipOptions,
+            TemplateClipOptions,
+            TemplateSection,
             RoundRobinClipOptions,
             WeightedRandomClipOptions,
             EqualLengthClipOptions,
             ClipLengthOptions,
             RandomizedClipOptions,
             SongClipOptions,
+            SongEnergyClipOptions,
+            SongEnergy,
             MeasureCount,
             Beats,
+            BeatGridEdit,
+            SongWaveform,
             CreateVideoBody,
             EncodingEffort,
             VideoCodec,
@@ -121,6 +230,10 @@ use crate::service::video::AddVideosRequest;
             VideoDetailsDto,
             CreateMarkerRequest,
             SongUpload,
+            ImportMusicBody,
+            UpdateSongBody,
+            CreatePlaylistBody,
+            PlaylistDto,
             AppVersion,
             MarkerCount,
             FileSystemEntry,
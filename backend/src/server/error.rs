@@ -7,12 +7,15 @@ use reqwest::StatusCode;
 use serde_json::json;
 use tracing::error;
 
+/// Error messages by the name of the field they're about.
+pub type ValidationErrors = HashMap<&'static str, &'static str>;
+
 #[derive(Debug)]
 pub enum AppError {
     Io(io::Error),
     Report(color_eyre::Report),
     StatusCode(StatusCode),
-    Validation(HashMap<&'static str, &'static str>),
+    Validation(ValidationErrors),
     Url(url::ParseError),
 }
 
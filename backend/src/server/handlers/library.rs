@@ -8,10 +8,12 @@ use axum::Json;
 use camino::Utf8Path;
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, info, warn};
+use tracing::{debug, error, info, warn};
 use utoipa::{IntoParams, ToSchema};
 
+use crate::data::database::integrity::{VerificationMode, VideoIntegrity};
 use crate::data::database::markers::{ListMarkersFilter, MarkerCount};
+use crate::data::database::motion::VideoMotion;
 use crate::data::database::videos::{TagCount, VideoSearchQuery, VideoSource, VideoUpdate};
 use crate::data::stash_api::StashApi;
 use crate::server::error::AppError;
@@ -20,11 +22,21 @@ use crate::server::types::{
     CreateMarker, ListVideoDto, MarkerDto, MarkerDtoConverter, Page, PageParameters, StashVideoDto,
     UpdateMarker, VideoDetailsDto, VideoDetailsDtoConverter, VideoDto,
 };
+use crate::service::commands::ffprobe::VideoChapter;
+use crate::service::duplicates::{
+    DuplicateGroup, DuplicatesService, MergeDuplicatesBody, MergeDuplicatesResult,
+};
 use crate::service::encoding_optimization::EncodingOptimizationService;
+use crate::service::integrity::IntegrityVerifier;
+use crate::service::library_health::{
+    LibraryHealthReport, LibraryHealthService, RelinkVideosBody, RelinkVideosResult,
+    RelocatedVideo, RemapPathsBody,
+};
 use crate::service::migrations::Migrator;
+use crate::service::motion::MotionAnalyzer;
 use crate::service::preview_image::PreviewGenerator;
 use crate::service::scene_detection;
-use crate::service::video::{AddVideosRequest, VideoService};
+use crate::service::video::{AddVideosRequest, FolderTagOptions, VideoService};
 
 #[utoipa::path(
     get,
@@ -127,6 +139,49 @@ pub async fn add_new_videos(
     Ok(Json(new_videos))
 }
 
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyFolderTagsBody {
+    pub path: String,
+    pub recurse: bool,
+    pub folder_tags: FolderTagOptions,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyFolderTagsResponse {
+    /// Number of videos that got new tags.
+    pub updated: usize,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/library/video/folder-tags",
+    request_body = ApplyFolderTagsBody,
+    responses(
+        (status = 200, description = "How many videos got new tags", body = ApplyFolderTagsResponse),
+    )
+)]
+#[axum::debug_handler]
+/// Tags the videos that are already in the library with the folders they're in
+pub async fn apply_folder_tags(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<ApplyFolderTagsBody>,
+) -> Result<Json<ApplyFolderTagsResponse>, AppError> {
+    let path = Utf8Path::new(&body.path);
+    if !path.is_dir() {
+        return Err(AppError::Validation(HashMap::from([(
+            "path",
+            "Path must be an existing folder",
+        )])));
+    }
+    let video_service = VideoService::new(state).await?;
+    let updated = video_service
+        .apply_folder_tags(path, body.recurse, &body.folder_tags)
+        .await?;
+    Ok(Json(ApplyFolderTagsResponse { updated }))
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct ListPerformerResponse {
     pub title: String,
@@ -328,6 +383,225 @@ pub async fn detect_markers(
     Ok(Json(created_markers))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/library/video/{id}/chapters",
+    params(
+        ("id" = String, Path, description = "The ID of the video to read the chapters of")
+    ),
+    responses(
+        (status = 200, description = "The chapters that are embedded in the video file", body = Vec<VideoChapter>),
+    )
+)]
+#[axum::debug_handler]
+/// Lists the chapters that are embedded in the video file, so they can be turned into markers.
+pub async fn list_video_chapters(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<VideoChapter>>, AppError> {
+    let Some(video) = state.database.videos.get_video(&id).await? else {
+        return Err(AppError::StatusCode(StatusCode::NOT_FOUND));
+    };
+    let video_service = VideoService::new(state).await?;
+    let chapters = video_service.read_chapters(&video).await?;
+    Ok(Json(chapters))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateChapterMarkersBody {
+    /// Indices of the chapters (as returned by the chapters endpoint) to create markers for.
+    /// Markers are created for all chapters if not set.
+    pub chapters: Option<Vec<usize>>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/library/video/{id}/chapters/markers",
+    params(
+        ("id" = String, Path, description = "The ID of the video to create markers for")
+    ),
+    request_body = CreateChapterMarkersBody,
+    responses(
+        (status = 200, description = "All newly created markers", body = Vec<MarkerDto>),
+    )
+)]
+#[axum::debug_handler]
+/// Creates markers from the chapters that are embedded in the video file, titled like the
+/// chapters.
+pub async fn create_chapter_markers(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<CreateChapterMarkersBody>,
+) -> Result<Json<Vec<MarkerDto>>, AppError> {
+    let Some(video) = state.database.videos.get_video(&id).await? else {
+        return Err(AppError::StatusCode(StatusCode::NOT_FOUND));
+    };
+    let video_service = VideoService::new(state.clone()).await?;
+    let mut chapters = video_service.read_chapters(&video).await?;
+    if let Some(indices) = body.chapters {
+        if indices.iter().any(|&index| index >= chapters.len()) {
+            return Err(AppError::Validation(HashMap::from([(
+                "chapters",
+                "Chapter does not exist",
+            )])));
+        }
+        chapters = chapters
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| indices.contains(index))
+            .map(|(_, chapter)| chapter)
+            .collect();
+    }
+
+    let markers = video_service
+        .create_chapter_markers(&video, &chapters)
+        .await?;
+    let stash_api = state.stash_api().await?;
+    let converter = MarkerDtoConverter::new(stash_api);
+    Ok(Json(
+        markers
+            .into_iter()
+            .map(|marker| converter.from_db(marker, &video))
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct AnalyzeMotionQuery {
+    pub force: Option<bool>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/library/video/{id}/motion",
+    params(
+        ("id" = String, Path, description = "The ID of the video to analyze"),
+        ("force" = Option<bool>, Query, description = "Analyze the video again even if a cached analysis exists")
+    ),
+    responses(
+        (status = 200, description = "The motion scores of the video", body = VideoMotion),
+    )
+)]
+#[axum::debug_handler]
+/// Analyzes how much motion there is throughout a video, so clips can prefer the active parts.
+/// The result is cached.
+pub async fn analyze_video_motion(
+    Path(id): Path<String>,
+    Query(AnalyzeMotionQuery { force }): Query<AnalyzeMotionQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<VideoMotion>, AppError> {
+    let Some(video) = state.database.videos.get_video(&id).await? else {
+        return Err(AppError::StatusCode(StatusCode::NOT_FOUND));
+    };
+    let analyzer = MotionAnalyzer::new(state.database.clone(), state.ffmpeg_location.clone());
+    let motion = analyzer
+        .analyze_video(&video, force.unwrap_or(false))
+        .await?;
+    Ok(Json(motion))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct VerifyVideoQuery {
+    pub mode: Option<VerificationMode>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/library/video/{id}/verify",
+    params(
+        ("id" = String, Path, description = "The ID of the video to verify"),
+        VerifyVideoQuery,
+    ),
+    responses(
+        (status = 200, description = "The result of a sampled check, or nothing if a full check was started in the background", body = Option<VideoIntegrity>),
+    )
+)]
+#[axum::debug_handler]
+/// Decodes the video with ffmpeg to check whether the file is corrupt. Decoding the whole
+/// file takes a while, so full checks run in the background.
+pub async fn verify_video(
+    Path(id): Path<String>,
+    Query(VerifyVideoQuery { mode }): Query<VerifyVideoQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Option<VideoIntegrity>>, AppError> {
+    let Some(video) = state.database.videos.get_video(&id).await? else {
+        return Err(AppError::StatusCode(StatusCode::NOT_FOUND));
+    };
+    if video.source == VideoSource::Stash {
+        return Err(AppError::Validation(HashMap::from([(
+            "id",
+            "Only local videos can be verified",
+        )])));
+    }
+    let verifier = IntegrityVerifier::new(state.database.clone(), state.ffmpeg_location.clone());
+    let mode = mode.unwrap_or_default();
+    if mode == VerificationMode::Full {
+        tokio::spawn(async move {
+            if let Err(e) = verifier
+                .verify_video(&video.id, &video.file_path, video.duration, mode)
+                .await
+            {
+                error!("failed to verify video {}: {e:?}", video.id);
+            }
+        });
+        return Ok(Json(None));
+    }
+
+    let integrity = verifier
+        .verify_video(&video.id, &video.file_path, video.duration, mode)
+        .await?;
+    Ok(Json(Some(integrity)))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyVideosBody {
+    /// The videos to check. All local videos are checked if not set.
+    pub video_ids: Option<Vec<String>>,
+    pub mode: Option<VerificationMode>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/library/integrity/verify",
+    request_body = VerifyVideosBody,
+    responses(
+        (status = 200, description = "The check was started (returns immediately)", body = ()),
+    )
+)]
+#[axum::debug_handler]
+/// Checks the videos for corrupt files in the background
+pub async fn verify_videos(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<VerifyVideosBody>,
+) -> Result<Json<&'static str>, AppError> {
+    let verifier = IntegrityVerifier::new(state.database.clone(), state.ffmpeg_location.clone());
+    tokio::spawn(async move {
+        let mode = body.mode.unwrap_or_default();
+        if let Err(e) = verifier.verify_local_videos(body.video_ids, mode).await {
+            error!("failed to verify videos: {e:?}");
+        }
+    });
+    Ok(Json("OK"))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/library/integrity",
+    responses(
+        (status = 200, description = "All videos that failed the integrity check", body = Vec<VideoIntegrity>),
+    )
+)]
+#[axum::debug_handler]
+/// Lists the videos that are flagged as broken
+pub async fn list_broken_videos(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<VideoIntegrity>>, AppError> {
+    let videos = state.database.integrity.list_broken_videos().await?;
+    Ok(Json(videos))
+}
+
 #[axum::debug_handler]
 /// Serves the video file for a given video ID
 pub async fn get_video_file(
@@ -719,3 +993,127 @@ pub async fn list_video_tags(
     let tags = state.database.videos.list_tags(100).await?;
     Ok(Json(tags))
 }
+
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/api/library/health",
+    responses(
+        (status = 200, description = "All videos whose file is missing", body = LibraryHealthReport),
+    )
+)]
+/// Checks which videos' files are missing and flags them
+pub async fn check_library_health(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<LibraryHealthReport>, AppError> {
+    let service = LibraryHealthService::new(state.database.clone());
+    let report = service.check().await?;
+    Ok(Json(report))
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/api/library/health/remap",
+    request_body = RemapPathsBody,
+    responses(
+        (status = 200, description = "The videos whose path starts with the prefix", body = Vec<RelocatedVideo>),
+    )
+)]
+/// Replaces the start of the path of all videos, e.g. after a drive was moved
+pub async fn remap_video_paths(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<RemapPathsBody>,
+) -> Result<Json<Vec<RelocatedVideo>>, AppError> {
+    if body.from.trim_end_matches(['/', '\\']).is_empty() {
+        return Err(AppError::Validation(HashMap::from([(
+            "from",
+            "Prefix must not be empty",
+        )])));
+    }
+    let service = LibraryHealthService::new(state.database.clone());
+    let videos = service.remap_paths(&body).await?;
+    Ok(Json(videos))
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/api/library/health/relink",
+    request_body = RelinkVideosBody,
+    responses(
+        (status = 200, description = "The videos that were found in the folder", body = RelinkVideosResult),
+    )
+)]
+/// Searches a folder for the files of missing videos by their content and relinks them,
+/// keeping their markers
+pub async fn relink_missing_videos(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<RelinkVideosBody>,
+) -> Result<Json<RelinkVideosResult>, AppError> {
+    if !Utf8Path::new(&body.path).is_dir() {
+        return Err(AppError::Validation(HashMap::from([(
+            "path",
+            "Path must be an existing folder",
+        )])));
+    }
+    let service = LibraryHealthService::new(state.database.clone());
+    let result = service.relink_by_hash(&body).await?;
+    Ok(Json(result))
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/api/library/duplicates",
+    responses(
+        (status = 200, description = "Groups of videos that are the same", body = Vec<DuplicateGroup>),
+    )
+)]
+/// Finds videos that are in the library more than once, either as identical files or as
+/// videos that look the same
+pub async fn list_duplicate_videos(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<DuplicateGroup>>, AppError> {
+    let service = DuplicatesService::new(state.database.clone());
+    let groups = service.find_duplicates().await?;
+    Ok(Json(groups))
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/api/library/duplicates/merge",
+    request_body = MergeDuplicatesBody,
+    responses(
+        (status = 200, description = "How many markers were moved", body = MergeDuplicatesResult),
+    )
+)]
+/// Merges duplicate videos into one: their markers are moved to the kept video and the
+/// duplicates are removed from the library
+pub async fn merge_duplicate_videos(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<MergeDuplicatesBody>,
+) -> Result<Json<MergeDuplicatesResult>, AppError> {
+    if body.remove_ids.is_empty() {
+        return Err(AppError::Validation(HashMap::from([(
+            "removeIds",
+            "At least one video must be removed",
+        )])));
+    }
+    if body.remove_ids.contains(&body.keep_id) {
+        return Err(AppError::Validation(HashMap::from([(
+            "removeIds",
+            "The kept video must not be removed",
+        )])));
+    }
+    for id in body.remove_ids.iter().chain([&body.keep_id]) {
+        if state.database.videos.get_video(id).await?.is_none() {
+            return Err(AppError::StatusCode(StatusCode::NOT_FOUND));
+        }
+    }
+
+    let service = DuplicatesService::new(state.database.clone());
+    let result = service.merge(&body).await?;
+    Ok(Json(result))
+}
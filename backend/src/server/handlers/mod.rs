@@ -12,8 +12,10 @@ pub mod library;
 pub mod music;
 pub mod progress;
 pub mod project;
+pub mod schedule;
 pub mod stash;
 pub mod system;
+pub mod watch_folder;
 
 pub struct AppState {
     pub database: Database,
@@ -1,11 +1,14 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use axum::body::Body;
 use axum::extract::{Multipart, Path, Query, State};
 use axum::response::IntoResponse;
 use axum::Json;
+use camino::Utf8PathBuf;
 use color_eyre::eyre::eyre;
 use color_eyre::Report;
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use tracing::info;
 use url::Url;
@@ -15,7 +18,9 @@ use super::AppState;
 use crate::data::database::music::DbSong;
 use crate::server::error::AppError;
 use crate::server::types::*;
-use crate::service::music::{self, MusicDownloadService};
+use crate::service::music::{
+    self, MusicDownloadService, MusicImportService, MusicLibraryService, VideoSoundtrackService,
+};
 use crate::util::expect_file_name;
 
 #[derive(Deserialize, IntoParams)]
@@ -31,6 +36,10 @@ pub struct SongDto {
     pub file_name: String,
     pub url: String,
     pub beats: Vec<f32>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub bpm: Option<f32>,
 }
 
 impl From<DbSong> for SongDto {
@@ -42,7 +51,11 @@ impl From<DbSong> for SongDto {
             duration: value.duration,
             file_name: expect_file_name(&value.file_path),
             url: value.url,
+            bpm: beats.as_ref().and_then(|b| b.bpm()),
             beats: beats.map(|b| b.offsets).unwrap_or_default(),
+            title: value.title,
+            artist: value.artist,
+            album: value.album,
         }
     }
 }
@@ -68,6 +81,68 @@ pub async fn download_music(
     Ok(Json(song.into()))
 }
 
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportMusicBody {
+    pub path: String,
+    pub recurse: bool,
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/api/song/import",
+    request_body = ImportMusicBody,
+    responses(
+        (status = 200, description = "The newly imported songs", body = Vec<SongDto>)
+    )
+)]
+/// Imports all audio files from a local folder that aren't in the library yet.
+/// Beats are detected in the background.
+pub async fn import_music(
+    State(state): State<Arc<AppState>>,
+    Json(ImportMusicBody { path, recurse }): Json<ImportMusicBody>,
+) -> Result<Json<Vec<SongDto>>, AppError> {
+    let path = Utf8PathBuf::from(path);
+    if !path.is_dir() {
+        return Err(AppError::Validation(HashMap::from([(
+            "path",
+            "Path must be an existing folder",
+        )])));
+    }
+
+    let songs = MusicImportService::from(state)
+        .import_folder(path, recurse)
+        .await?;
+    Ok(Json(songs.into_iter().map(From::from).collect()))
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/api/song/from-video/{id}",
+    params(
+        ("id" = String, Path, description = "The ID of the video to use the audio of")
+    ),
+    responses(
+        (status = 200, description = "The song made from the video's audio", body = SongDto)
+    )
+)]
+/// Uses the soundtrack of a library video as a song, so clips can be cut to its beats and it
+/// can be used as the music of a compilation. The audio is only extracted once per video.
+pub async fn song_from_video(
+    Path(video_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SongDto>, AppError> {
+    let Some(video) = state.database.videos.get_video(&video_id).await? else {
+        return Err(AppError::StatusCode(StatusCode::NOT_FOUND));
+    };
+    let song = VideoSoundtrackService::from(state)
+        .song_from_video(&video)
+        .await?;
+    Ok(Json(song.into()))
+}
+
 #[axum::debug_handler]
 pub async fn stream_song(
     Path(song_id): Path<i64>,
@@ -116,8 +191,32 @@ pub async fn upload_music(
 }
 
 #[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
 pub struct ListSongsQuery {
     shuffle: Option<bool>,
+    /// Only list songs with at least this tempo. Songs without detected beats are skipped.
+    min_bpm: Option<f32>,
+    /// Only list songs with at most this tempo. Songs without detected beats are skipped.
+    max_bpm: Option<f32>,
+    /// Minimum duration in seconds.
+    min_duration: Option<f64>,
+    /// Maximum duration in seconds.
+    max_duration: Option<f64>,
+}
+
+impl ListSongsQuery {
+    fn matches(&self, song: &SongDto) -> bool {
+        let bpm_matches = match (self.min_bpm, self.max_bpm) {
+            (None, None) => true,
+            (min, max) => song.bpm.is_some_and(|bpm| {
+                min.is_none_or(|min| bpm >= min) && max.is_none_or(|max| bpm <= max)
+            }),
+        };
+
+        bpm_matches
+            && self.min_duration.is_none_or(|min| song.duration >= min)
+            && self.max_duration.is_none_or(|max| song.duration <= max)
+    }
 }
 
 #[axum::debug_handler]
@@ -129,9 +228,9 @@ pub struct ListSongsQuery {
         (status = 200, description = "Lists all songs", body = Vec<SongDto>),
     )
 )]
-/// List all songs
+/// List all songs, optionally filtered by tempo and duration
 pub async fn list_songs(
-    Query(ListSongsQuery { shuffle }): Query<ListSongsQuery>,
+    Query(query): Query<ListSongsQuery>,
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<Vec<SongDto>>, AppError> {
     use rand::seq::SliceRandom;
@@ -142,10 +241,11 @@ pub async fn list_songs(
         .list_songs()
         .await?
         .into_iter()
-        .map(From::from)
+        .map(SongDto::from)
+        .filter(|song| query.matches(song))
         .collect();
 
-    if let Some(true) = shuffle {
+    if let Some(true) = query.shuffle {
         songs.shuffle(&mut rand::rng());
     }
 
@@ -168,18 +268,293 @@ pub async fn get_beats(
     Path(song_id): Path<i64>,
     state: State<Arc<AppState>>,
 ) -> Result<Json<Beats>, AppError> {
-    let beats = match state.database.music.fetch_beats(song_id).await? {
-        Some(beats) => beats,
-        None => {
-            let song = state.database.music.get_song(song_id).await?;
-            let beats = music::detect_beats(&song.file_path, &state.ffmpeg_location)?;
-            state
-                .database
-                .music
-                .persist_beats(song.rowid.unwrap(), &beats)
-                .await?;
-            beats
-        }
-    };
+    let beats = load_beats(&state, song_id).await?;
     Ok(Json(beats))
 }
+
+async fn load_beats(state: &AppState, song_id: i64) -> Result<Beats, AppError> {
+    match state.database.music.fetch_beats(song_id).await? {
+        Some(beats) => Ok(beats),
+        None => detect_and_persist_beats(state, song_id).await,
+    }
+}
+
+async fn detect_and_persist_beats(state: &AppState, song_id: i64) -> Result<Beats, AppError> {
+    let song = state.database.music.get_song(song_id).await?;
+    let beats = music::detect_beats(&song.file_path, &state.directories, &state.ffmpeg_location)?;
+    state
+        .database
+        .music
+        .persist_beats(song.rowid.unwrap(), &beats)
+        .await?;
+    Ok(beats)
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    put,
+    path = "/api/song/{id}/beats",
+    params(
+        ("id" = i64, Path, description = "The ID of the song to correct the beats of")
+    ),
+    request_body = BeatGridEdit,
+    responses(
+        (status = 200, description = "The corrected beats of the song", body = Beats),
+    )
+)]
+/// Applies a manual correction to the beat grid of a song and stores the result, so it's
+/// used for all further clip generation and beat funscripts.
+pub async fn edit_beats(
+    Path(song_id): Path<i64>,
+    state: State<Arc<AppState>>,
+    Json(edit): Json<BeatGridEdit>,
+) -> Result<Json<Beats>, AppError> {
+    let beats = load_beats(&state, song_id).await?;
+    info!("applying beat grid edit {edit:?} to song {song_id}");
+    let beats = music::apply_beat_grid_edit(&beats, &edit).map_err(AppError::Validation)?;
+    state.database.music.persist_beats(song_id, &beats).await?;
+    Ok(Json(beats))
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/api/song/{id}/beats/reset",
+    params(
+        ("id" = i64, Path, description = "The ID of the song to reset the beats of")
+    ),
+    responses(
+        (status = 200, description = "The newly detected beats of the song", body = Beats),
+    )
+)]
+/// Discards all manual corrections and detects the beats of a song again.
+pub async fn reset_beats(
+    Path(song_id): Path<i64>,
+    state: State<Arc<AppState>>,
+) -> Result<Json<Beats>, AppError> {
+    let beats = detect_and_persist_beats(&state, song_id).await?;
+    Ok(Json(beats))
+}
+
+const DEFAULT_WAVEFORM_RESOLUTION: usize = 2000;
+
+#[derive(Deserialize, IntoParams)]
+pub struct WaveformQuery {
+    /// Number of buckets the waveform is split into, defaults to 2000. Clamped to between
+    /// 100 and 100000.
+    resolution: Option<usize>,
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/api/song/{id}/waveform",
+    params(
+        ("id" = i64, Path, description = "The ID of the song to get the waveform of"),
+        WaveformQuery,
+    ),
+    responses(
+        (status = 200, description = "The downsampled waveform of the song", body = SongWaveform),
+    )
+)]
+/// Get the peaks of a song's waveform, to display it while editing beats or song ranges.
+pub async fn get_waveform(
+    Path(song_id): Path<i64>,
+    Query(WaveformQuery { resolution }): Query<WaveformQuery>,
+    state: State<Arc<AppState>>,
+) -> Result<Json<SongWaveform>, AppError> {
+    let resolution = resolution.unwrap_or(DEFAULT_WAVEFORM_RESOLUTION);
+    let song = state.database.music.get_song(song_id).await?;
+    let waveform = music::song_waveform(
+        &song.file_path,
+        song_id,
+        resolution,
+        &state.directories,
+        &state.ffmpeg_location,
+    )?;
+    Ok(Json(waveform))
+}
+
+async fn find_song(state: &AppState, song_id: i64) -> Result<DbSong, AppError> {
+    match state.database.music.find_song(song_id).await? {
+        Some(song) => Ok(song),
+        None => Err(AppError::StatusCode(StatusCode::NOT_FOUND)),
+    }
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    put,
+    path = "/api/song/{id}",
+    params(
+        ("id" = i64, Path, description = "The ID of the song to update")
+    ),
+    request_body = UpdateSongBody,
+    responses(
+        (status = 200, description = "The updated song", body = SongDto),
+    )
+)]
+/// Renames a song by setting its title, artist and album
+pub async fn update_song(
+    Path(song_id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<UpdateSongBody>,
+) -> Result<Json<SongDto>, AppError> {
+    find_song(&state, song_id).await?;
+    let title = non_empty(body.title);
+    let artist = non_empty(body.artist);
+    let album = non_empty(body.album);
+    state
+        .database
+        .music
+        .update_song_metadata(
+            song_id,
+            title.as_deref(),
+            artist.as_deref(),
+            album.as_deref(),
+        )
+        .await?;
+
+    Ok(Json(find_song(&state, song_id).await?.into()))
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    delete,
+    path = "/api/song/{id}",
+    params(
+        ("id" = i64, Path, description = "The ID of the song to delete")
+    ),
+    responses(
+        (status = 200, description = "Delete a song and its files", body = ()),
+    )
+)]
+/// Deletes a song, removes it from all playlists and cleans up its files. Files of songs that
+/// were imported from a local folder are kept.
+pub async fn delete_song(
+    Path(song_id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<(), AppError> {
+    let song = find_song(&state, song_id).await?;
+    MusicLibraryService::from(state).delete_song(song).await?;
+    Ok(())
+}
+
+async fn validate_playlist(state: &AppState, body: &CreatePlaylistBody) -> Result<(), AppError> {
+    let mut errors = HashMap::new();
+    if body.name.trim().is_empty() {
+        errors.insert("name", "Name must not be empty");
+    }
+    for song_id in &body.song_ids {
+        if state.database.music.find_song(*song_id).await?.is_none() {
+            errors.insert("songIds", "Playlist contains songs that don't exist");
+            break;
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Validation(errors))
+    }
+}
+
+async fn get_playlist_dto(state: &AppState, id: i64) -> Result<PlaylistDto, AppError> {
+    match state.database.playlists.get_playlist(id).await? {
+        Some(playlist) => Ok(playlist.into()),
+        None => Err(AppError::StatusCode(StatusCode::NOT_FOUND)),
+    }
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/api/song/playlist",
+    responses(
+        (status = 200, description = "List all playlists", body = Vec<PlaylistDto>),
+    )
+)]
+/// Lists all playlists
+pub async fn list_playlists(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<PlaylistDto>>, AppError> {
+    let playlists = state.database.playlists.list_playlists().await?;
+    Ok(Json(playlists.into_iter().map(From::from).collect()))
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/api/song/playlist",
+    request_body = CreatePlaylistBody,
+    responses(
+        (status = 200, description = "The newly created playlist", body = PlaylistDto),
+    )
+)]
+/// Creates a new playlist
+pub async fn create_playlist(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<CreatePlaylistBody>,
+) -> Result<Json<PlaylistDto>, AppError> {
+    validate_playlist(&state, &body).await?;
+    let id = state
+        .database
+        .playlists
+        .create_playlist(body.name.trim(), &body.song_ids)
+        .await?;
+    Ok(Json(get_playlist_dto(&state, id).await?))
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    put,
+    path = "/api/song/playlist/{id}",
+    params(
+        ("id" = i64, Path, description = "The ID of the playlist to update")
+    ),
+    request_body = CreatePlaylistBody,
+    responses(
+        (status = 200, description = "The updated playlist", body = PlaylistDto),
+    )
+)]
+/// Renames a playlist and replaces its songs
+pub async fn update_playlist(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<CreatePlaylistBody>,
+) -> Result<Json<PlaylistDto>, AppError> {
+    get_playlist_dto(&state, id).await?;
+    validate_playlist(&state, &body).await?;
+    state
+        .database
+        .playlists
+        .update_playlist(id, body.name.trim(), &body.song_ids)
+        .await?;
+    Ok(Json(get_playlist_dto(&state, id).await?))
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    delete,
+    path = "/api/song/playlist/{id}",
+    params(
+        ("id" = i64, Path, description = "The ID of the playlist to delete")
+    ),
+    responses(
+        (status = 200, description = "Delete a playlist", body = ()),
+    )
+)]
+/// Deletes a playlist, the songs in it are kept
+pub async fn delete_playlist(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<(), AppError> {
+    state.database.playlists.delete_playlist(id).await?;
+    Ok(())
+}
@@ -1,22 +1,29 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use axum::body::Body;
 use axum::extract::{Path, Query, State};
 use axum::response::IntoResponse;
 use axum::Json;
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::eyre::eyre;
+use itertools::Itertools;
+use reqwest::StatusCode;
+use sanitise_file_name::sanitise;
 use serde::{Deserialize, Serialize};
 use tokio_util::io::ReaderStream;
 use tracing::{debug, error, info};
 use utoipa::{IntoParams, ToSchema};
 
 use super::AppState;
-use crate::data::database::markers::ListMarkersFilter;
+use crate::data::database::clip_lists::ClipList;
 use crate::helpers::random::{generate_id, get_random_word};
 use crate::server::error::AppError;
 use crate::server::types::*;
-use crate::service::clip::{ClipService, ClipsResult};
+use crate::service::clip::ClipsResult;
+use crate::service::clip_editor::{self, ClipEditorService};
+use crate::service::clip_similarity;
+use crate::service::clip_statistics;
 use crate::service::description_generator::DescriptionType;
 use crate::service::funscript::{self, FunScript, ScriptBuilder};
 use crate::service::generator::CompilationGenerator;
@@ -36,15 +43,26 @@ pub async fn fetch_clips(
     State(state): State<Arc<AppState>>,
     Json(body): Json<CreateClipsBody>,
 ) -> Result<Json<ClipsResponse>, AppError> {
+    if let Some(pinned_clips) = &body.pinned_clips {
+        let clips: Vec<_> = pinned_clips.iter().map(|p| p.clip.clone()).collect();
+        let validation = ClipEditorService::new(state.database.clone())
+            .validate_clips(&clips)
+            .await?;
+        if !validation.is_empty() {
+            return Err(AppError::Validation(validation));
+        }
+    }
+
+    let render_settings = body.render_settings.clone();
     let service = OptionsConverterService::new(state.database.clone());
     let options = service.convert_clip_options(body).await?;
     debug!("clip options: {options:?}");
 
-    let clip_service = ClipService::new();
     let ClipsResult {
         beat_offsets,
         clips,
-    } = clip_service.arrange_clips(options);
+        similarity_order_pending,
+    } = clip_similarity::arrange_clips(&state.database, &state.ffmpeg_location, options).await?;
 
     let mut video_ids: Vec<_> = clips.iter().map(|c| c.video_id.as_str()).collect();
     video_ids.sort();
@@ -61,12 +79,21 @@ pub async fn fetch_clips(
 
     let stream_service = StreamUrlService::new(state.database.clone()).await;
     let streams = stream_service.get_clip_streams(&clips, &videos, LocalVideoSource::Url);
+    let performers = state
+        .database
+        .performers
+        .find_names_for_videos(&video_ids)
+        .await?;
+    let statistics =
+        clip_statistics::compute_statistics(&clips, &videos, &performers, render_settings.as_ref());
 
     let response = ClipsResponse {
         clips,
         streams,
         videos,
         beat_offsets,
+        statistics,
+        similarity_order_pending,
     };
     Ok(Json(response))
 }
@@ -89,6 +116,276 @@ async fn create_video_inner(
     Ok(())
 }
 
+macro_rules! max_variants {
+    () => {
+        20
+    };
+}
+
+/// Maximum number of variants that can be created in one batch.
+const MAX_VARIANTS: usize = max_variants!();
+
+const SEEDS_ERROR: &str = concat!(
+    "Between 1 and ",
+    max_variants!(),
+    " distinct seeds are required"
+);
+
+fn variant_file_name(file_name: &str, seed: &str) -> String {
+    let path = Utf8Path::new(file_name);
+    let name = match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => format!("{stem}-{seed}.{extension}"),
+        _ => format!("{file_name}-{seed}"),
+    };
+    sanitise(&name)
+}
+
+/// Splits the query into one query per value, leaving out duplicate and blank values.
+fn per_tag_queries(query: InteractiveClipsQuery) -> Vec<(String, InteractiveClipsQuery)> {
+    let (mut values, make_query): (_, fn(Vec<String>) -> InteractiveClipsQuery) = match query {
+        InteractiveClipsQuery::MarkerTitles { data } => {
+            (data, |data| InteractiveClipsQuery::MarkerTitles { data })
+        }
+        InteractiveClipsQuery::Performers { data } => {
+            (data, |data| InteractiveClipsQuery::Performers { data })
+        }
+        InteractiveClipsQuery::VideoTags { data } => {
+            (data, |data| InteractiveClipsQuery::VideoTags { data })
+        }
+    };
+    let mut seen = HashSet::new();
+    values.retain(|v| !v.trim().is_empty() && seen.insert(v.clone()));
+
+    values
+        .into_iter()
+        .map(|value| (value.clone(), make_query(vec![value])))
+        .collect()
+}
+
+/// Renders the compilations one after the other and returns the video IDs of the ones that
+/// failed to render.
+async fn render_compilations(
+    state: Arc<AppState>,
+    compilations: Vec<CreateVideoBody>,
+) -> Result<Vec<String>, AppError> {
+    let service = OptionsConverterService::new(state.database.clone());
+    let generator = CompilationGenerator::new(
+        state.directories.clone(),
+        &state.ffmpeg_location,
+        state.database.clone(),
+    )
+    .await?;
+
+    // compilations are rendered one after the other, so clips that appear in several
+    // of them are only encoded once and picked up from the clip cache afterwards.
+    let total = compilations.len();
+    let mut failed = vec![];
+    for (index, body) in compilations.into_iter().enumerate() {
+        info!(
+            "rendering compilation {} / {total} with file name '{}'",
+            index + 1,
+            body.file_name
+        );
+        let video_id = body.video_id.clone();
+        let options = match service.convert_compilation_options(body).await {
+            Ok(options) => options,
+            Err(e) => {
+                error!("failed to prepare compilation {video_id}: {e:?}");
+                failed.push(video_id);
+                continue;
+            }
+        };
+        let result = match generator.gather_clips(&options).await {
+            Ok(clips) => generator.compile_clips(&options, clips).await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            error!("failed to render compilation {}: {e:?}", options.video_id);
+            failed.push(options.video_id);
+        }
+    }
+
+    Ok(failed)
+}
+
+fn spawn_render_compilations(state: Arc<AppState>, compilations: Vec<CreateVideoBody>) {
+    tokio::spawn(async move {
+        match render_compilations(state, compilations).await {
+            Ok(failed) if !failed.is_empty() => {
+                error!("failed to render {} compilations: {failed:?}", failed.len())
+            }
+            Ok(_) => {}
+            Err(e) => error!("error: {e:?}"),
+        }
+    });
+}
+
+/// Validates the requested seeds and returns the distinct seeds to create variants with.
+fn variant_seeds(seeds: VariantSeeds) -> Result<Vec<String>, AppError> {
+    let seeds = match seeds {
+        VariantSeeds::Fixed { seeds } => seeds.into_iter().unique().collect_vec(),
+        VariantSeeds::Random { count } if count <= MAX_VARIANTS => {
+            let mut seeds = vec![];
+            while seeds.len() < count {
+                let seed = get_random_word();
+                if !seeds.contains(&seed) {
+                    seeds.push(seed);
+                }
+            }
+            seeds
+        }
+        VariantSeeds::Random { .. } => vec![],
+    };
+    if seeds.is_empty() || seeds.len() > MAX_VARIANTS {
+        return Err(AppError::Validation(HashMap::from([(
+            "seeds",
+            SEEDS_ERROR,
+        )])));
+    }
+
+    Ok(seeds)
+}
+
+/// Arranges the clips once per seed and returns the variants together with the bodies to
+/// render them with.
+async fn arrange_variants(
+    state: &AppState,
+    clips: CreateClipsBody,
+    file_name: &str,
+    render: &BatchRenderOptions,
+    seeds: Vec<String>,
+) -> Result<(Vec<VariantDto>, Vec<CreateVideoBody>), AppError> {
+    let selected_markers = clips.markers.clone();
+    let service = OptionsConverterService::new(state.database.clone());
+    let options = service.convert_clip_options(clips).await?;
+
+    let mut variants = vec![];
+    let mut render_jobs = vec![];
+    for seed in seeds {
+        let mut options = options.clone();
+        options.seed = Some(seed.clone());
+        let ClipsResult { clips, .. } =
+            clip_similarity::arrange_clips(&state.database, &state.ffmpeg_location, options)
+                .await?;
+        let video_id = generate_id();
+        let file_name = variant_file_name(file_name, &seed);
+
+        render_jobs.push(render.to_video_body(
+            video_id.clone(),
+            file_name.clone(),
+            clips.clone(),
+            selected_markers.clone(),
+        ));
+        variants.push(VariantDto {
+            video_id,
+            seed,
+            file_name,
+            clips,
+        });
+    }
+
+    Ok((variants, render_jobs))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/project/variants",
+    request_body = CreateVariantsBody,
+    responses(
+        (status = 200, description = "The variants that will be rendered (returns immediately)", body = Vec<VariantDto>),
+    )
+)]
+#[axum::debug_handler]
+/// Arranges the clips once per seed and renders all variants in a single background job.
+/// The progress of each variant can be queried with its video ID.
+pub async fn create_variants(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<CreateVariantsBody>,
+) -> Result<Json<Vec<VariantDto>>, AppError> {
+    let seeds = variant_seeds(body.seeds)?;
+    let (variants, render_jobs) =
+        arrange_variants(&state, body.clips, &body.file_name, &body.render, seeds).await?;
+    spawn_render_compilations(state, render_jobs);
+
+    Ok(Json(variants))
+}
+
+/// Arranges the clips for every value of the query and returns the compilations together
+/// with the bodies to render them with. Values without any markers are skipped.
+async fn arrange_per_tag_compilations(
+    state: &AppState,
+    body: CreatePerTagCompilationsBody,
+) -> Result<(Vec<TagCompilationDto>, Vec<CreateVideoBody>), AppError> {
+    let queries = per_tag_queries(body.query);
+
+    let service = OptionsConverterService::new(state.database.clone());
+    let seed = body.seed.unwrap_or_else(get_random_word);
+    let prefix = body.file_name_prefix.unwrap_or_default();
+
+    let mut compilations = vec![];
+    let mut render_jobs = vec![];
+    for (tag, query) in queries {
+        let markers = service.query_markers(query).await?;
+        if markers.is_empty() {
+            info!("no markers found for '{tag}', skipping");
+            continue;
+        }
+
+        let options = service
+            .convert_clip_options(CreateClipsBody {
+                markers: markers.clone(),
+                seed: Some(seed.clone()),
+                clips: body.clips.clone(),
+                pinned_clips: None,
+                freshness: None,
+                motion: None,
+                render_settings: None,
+            })
+            .await?;
+        let ClipsResult { clips, .. } =
+            clip_similarity::arrange_clips(&state.database, &state.ffmpeg_location, options)
+                .await?;
+        let video_id = generate_id();
+        let file_name = sanitise(&format!("{prefix}{tag}.mp4"));
+
+        render_jobs.push(body.render.to_video_body(
+            video_id.clone(),
+            file_name.clone(),
+            clips.clone(),
+            markers,
+        ));
+        compilations.push(TagCompilationDto {
+            video_id,
+            tag,
+            file_name,
+            clips,
+        });
+    }
+
+    Ok((compilations, render_jobs))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/project/per-tag",
+    request_body = CreatePerTagCompilationsBody,
+    responses(
+        (status = 200, description = "The compilations that will be rendered (returns immediately)", body = Vec<TagCompilationDto>),
+    )
+)]
+#[axum::debug_handler]
+/// Creates one compilation per video tag, marker title or performer and renders them all
+/// in a single background job. Values without any markers are skipped.
+pub async fn create_per_tag_compilations(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<CreatePerTagCompilationsBody>,
+) -> Result<Json<Vec<TagCompilationDto>>, AppError> {
+    let (compilations, render_jobs) = arrange_per_tag_compilations(&state, body).await?;
+    spawn_render_compilations(state, render_jobs);
+
+    Ok(Json(compilations))
+}
+
 #[utoipa::path(
     post,
     path = "/api/project/clips/interactive",
@@ -102,29 +399,8 @@ pub async fn fetch_clips_interactive(
     state: State<Arc<AppState>>,
     Json(body): Json<CreateInteractiveClipsBody>,
 ) -> Result<Json<ClipsResponse>, AppError> {
-    let filter = match body.query {
-        InteractiveClipsQuery::MarkerTitles { data } => ListMarkersFilter::MarkerTitles(data),
-        InteractiveClipsQuery::Performers { data } => ListMarkersFilter::VideoPerformers(data),
-        InteractiveClipsQuery::VideoTags { data } => ListMarkersFilter::VideoTags(data),
-    };
-
-    let all_markers: Vec<_> = state
-        .database
-        .markers
-        .list_markers(Some(filter), None)
-        .await?
-        .into_iter()
-        .map(|m| SelectedMarker {
-            id: m.rowid.unwrap(),
-            video_id: m.video_id,
-            selected_range: (m.start_time, m.end_time),
-            index_within_video: m.index_within_video as usize,
-            selected: Some(true),
-            title: m.title,
-            loops: 1,
-            source: m.source,
-        })
-        .collect();
+    let service = OptionsConverterService::new(state.database.clone());
+    let all_markers = service.query_markers(body.query).await?;
 
     let options = CreateClipsBody {
         markers: all_markers,
@@ -138,6 +414,10 @@ pub async fn fetch_clips_interactive(
             }),
             order: body.order,
         },
+        pinned_clips: None,
+        freshness: None,
+        motion: None,
+        render_settings: None,
     };
 
     fetch_clips(state, Json(options)).await
@@ -372,3 +652,431 @@ pub async fn generate_random_seed() -> Json<String> {
 
     Json(random::get_random_word())
 }
+
+impl From<ClipList> for ClipListDto {
+    fn from(list: ClipList) -> Self {
+        ClipListDto {
+            id: list.id,
+            clips: list.clips,
+            undo_steps: list.history.len(),
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/project/clip-list",
+    request_body = CreateClipListBody,
+    responses(
+        (status = 200, description = "The newly stored clip list", body = ClipListDto),
+    )
+)]
+#[axum::debug_handler]
+/// Stores a clip list on the server so it can be edited
+pub async fn create_clip_list(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<CreateClipListBody>,
+) -> Result<Json<ClipListDto>, AppError> {
+    let service = ClipEditorService::new(state.database.clone());
+    let validation = service.validate_clips(&body.clips).await?;
+    if !validation.is_empty() {
+        return Err(AppError::Validation(validation));
+    }
+
+    let list = service.create_clip_list(body.clips).await?;
+    info!(
+        "created clip list {} with {} clips",
+        list.id,
+        list.clips.len()
+    );
+    Ok(Json(list.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/project/clip-list/{id}",
+    params(
+        ("id" = String, Path, description = "The ID of the clip list")
+    ),
+    responses(
+        (status = 200, description = "Get a stored clip list", body = ClipListDto),
+    )
+)]
+#[axum::debug_handler]
+/// Gets a stored clip list
+pub async fn get_clip_list(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ClipListDto>, AppError> {
+    let service = ClipEditorService::new(state.database.clone());
+    match service.get_clip_list(&id).await? {
+        Some(list) => Ok(Json(list.into())),
+        None => Err(AppError::StatusCode(StatusCode::NOT_FOUND)),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/project/clip-list/{id}/edit",
+    params(
+        ("id" = String, Path, description = "The ID of the clip list")
+    ),
+    request_body = ClipListEdit,
+    responses(
+        (status = 200, description = "The clip list after applying the edit", body = ClipListDto),
+    )
+)]
+#[axum::debug_handler]
+/// Reorders, trims, splits, duplicates or deletes a clip in a stored clip list
+pub async fn edit_clip_list(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(edit): Json<ClipListEdit>,
+) -> Result<Json<ClipListDto>, AppError> {
+    let service = ClipEditorService::new(state.database.clone());
+    let Some(list) = service.get_clip_list(&id).await? else {
+        return Err(AppError::StatusCode(StatusCode::NOT_FOUND));
+    };
+
+    debug!("applying edit {edit:?} to clip list {id}");
+    let clips = clip_editor::apply_edit(&list.clips, &edit).map_err(AppError::Validation)?;
+    let validation = service.validate_clips(&clips).await?;
+    if !validation.is_empty() {
+        return Err(AppError::Validation(validation));
+    }
+
+    let list = service.update_clips(list, clips).await?;
+    Ok(Json(list.into()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/project/clip-list/{id}/undo",
+    params(
+        ("id" = String, Path, description = "The ID of the clip list")
+    ),
+    responses(
+        (status = 200, description = "The clip list after undoing the last edit", body = ClipListDto),
+    )
+)]
+#[axum::debug_handler]
+/// Undoes the last edit of a stored clip list
+pub async fn undo_clip_list_edit(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ClipListDto>, AppError> {
+    let service = ClipEditorService::new(state.database.clone());
+    let Some(list) = service.get_clip_list(&id).await? else {
+        return Err(AppError::StatusCode(StatusCode::NOT_FOUND));
+    };
+
+    match service.undo(list).await? {
+        Some(list) => Ok(Json(list.into())),
+        None => Err(AppError::Validation(HashMap::from([(
+            "history",
+            "There is nothing to undo",
+        )]))),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/project/clip-list/{id}",
+    params(
+        ("id" = String, Path, description = "The ID of the clip list")
+    ),
+    responses(
+        (status = 200, description = "Delete a stored clip list", body = ()),
+    )
+)]
+#[axum::debug_handler]
+/// Deletes a stored clip list
+pub async fn delete_clip_list(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<&'static str>, AppError> {
+    let service = ClipEditorService::new(state.database.clone());
+    service.delete_clip_list(&id).await?;
+    Ok(Json("OK"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use sqlx::SqlitePool;
+    use tracing_test::traced_test;
+
+    use super::{
+        arrange_per_tag_compilations, arrange_variants, per_tag_queries, render_compilations,
+        variant_seeds, MAX_VARIANTS, SEEDS_ERROR,
+    };
+    use crate::data::database::integrity::VerificationMode;
+    use crate::data::database::markers::DbMarker;
+    use crate::data::database::videos::VideoSource;
+    use crate::data::database::Database;
+    use crate::server::error::AppError;
+    use crate::server::handlers::AppState;
+    use crate::server::types::*;
+    use crate::service::commands::ffmpeg::FfmpegLocation;
+    use crate::service::directories::Directories;
+    use crate::service::fixtures::{persist_marker, persist_video};
+    use crate::service::generator::BrokenVideoHandling;
+    use crate::service::new_version_checker::NewVersionChecker;
+
+    fn app_state(database: Database) -> AppState {
+        AppState {
+            database,
+            directories: Directories::new().unwrap(),
+            ffmpeg_location: FfmpegLocation::System,
+            new_version_checker: NewVersionChecker::new(),
+        }
+    }
+
+    fn render_options() -> BatchRenderOptions {
+        BatchRenderOptions {
+            output_resolution: (1280, 720),
+            output_fps: 30,
+            song_ids: vec![],
+            music_volume: None,
+            video_codec: VideoCodec::H264,
+            video_quality: VideoQuality::Medium,
+            encoding_effort: EncodingEffort::Medium,
+            padding: None,
+            force_re_encode: false,
+            include_original_file_name: false,
+            broken_videos: Some(BrokenVideoHandling::Skip),
+        }
+    }
+
+    fn selected_marker(marker: &DbMarker) -> SelectedMarker {
+        SelectedMarker {
+            id: marker.rowid.unwrap(),
+            video_id: marker.video_id.clone(),
+            selected_range: (marker.start_time, marker.end_time),
+            index_within_video: marker.index_within_video as usize,
+            selected: Some(true),
+            title: marker.title.clone(),
+            loops: 1,
+            source: VideoSource::Folder,
+        }
+    }
+
+    fn clip_options() -> ClipOptions {
+        ClipOptions {
+            clip_picker: ClipPickerOptions::EqualLength(EqualLengthClipOptions {
+                clip_duration: 2.0,
+                spread: 0.0,
+                length: None,
+                min_clip_duration: None,
+            }),
+            order: ClipOrder::Random,
+        }
+    }
+
+    fn clips_body(markers: Vec<SelectedMarker>) -> CreateClipsBody {
+        CreateClipsBody {
+            markers,
+            seed: None,
+            clips: clip_options(),
+            pinned_clips: None,
+            freshness: None,
+            motion: None,
+            render_settings: None,
+        }
+    }
+
+    fn seeds_error(result: Result<Vec<String>, AppError>) -> &'static str {
+        match result {
+            Err(AppError::Validation(errors)) => errors["seeds"],
+            other => panic!("expected a validation error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_variant_seeds() {
+        let seeds = variant_seeds(VariantSeeds::Fixed {
+            seeds: vec!["a".into(), "b".into(), "a".into()],
+        })
+        .unwrap();
+        assert_eq!(seeds, vec!["a", "b"]);
+
+        let seeds = variant_seeds(VariantSeeds::Random { count: 5 }).unwrap();
+        assert_eq!(seeds.len(), 5);
+        assert!(seeds
+            .iter()
+            .all(|s| seeds.iter().filter(|o| *o == s).count() == 1));
+    }
+
+    #[test]
+    fn test_variant_seeds_limits() {
+        assert_eq!(
+            SEEDS_ERROR,
+            format!("Between 1 and {MAX_VARIANTS} distinct seeds are required")
+        );
+        let error = seeds_error(variant_seeds(VariantSeeds::Fixed { seeds: vec![] }));
+        assert_eq!(error, SEEDS_ERROR);
+        let error = seeds_error(variant_seeds(VariantSeeds::Random { count: 0 }));
+        assert_eq!(error, SEEDS_ERROR);
+        let error = seeds_error(variant_seeds(VariantSeeds::Random {
+            count: MAX_VARIANTS + 1,
+        }));
+        assert_eq!(error, SEEDS_ERROR);
+        let too_many = (0..=MAX_VARIANTS).map(|i| i.to_string()).collect();
+        let error = seeds_error(variant_seeds(VariantSeeds::Fixed { seeds: too_many }));
+        assert_eq!(error, SEEDS_ERROR);
+
+        // duplicates only count once towards the limit
+        let duplicates = (0..=MAX_VARIANTS).map(|i| (i % 2).to_string()).collect();
+        let seeds = variant_seeds(VariantSeeds::Fixed { seeds: duplicates }).unwrap();
+        assert_eq!(seeds, vec!["0", "1"]);
+    }
+
+    #[sqlx::test]
+    #[traced_test]
+    async fn test_arrange_variants(pool: SqlitePool) {
+        let database = Database::with_pool(pool);
+        let video = persist_video(&database).await.unwrap();
+        let marker = persist_marker(&database, &video.id, 0, 0.0, 20.0, false)
+            .await
+            .unwrap();
+        let state = app_state(database);
+        let seeds = vec!["first".to_string(), "second".to_string()];
+
+        let (variants, render_jobs) = arrange_variants(
+            &state,
+            clips_body(vec![selected_marker(&marker)]),
+            "compilation.mp4",
+            &render_options(),
+            seeds,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(variants.len(), 2);
+        assert_eq!(render_jobs.len(), 2);
+        assert_eq!(variants[0].file_name, "compilation-first.mp4");
+        assert_eq!(variants[1].file_name, "compilation-second.mp4");
+        assert_ne!(variants[0].video_id, variants[1].video_id);
+        for (variant, job) in variants.iter().zip(&render_jobs) {
+            assert_eq!(variant.video_id, job.video_id);
+            assert_eq!(variant.file_name, job.file_name);
+            assert_eq!(variant.clips, job.clips);
+            assert!(!variant.clips.is_empty());
+        }
+    }
+
+    #[sqlx::test]
+    #[traced_test]
+    async fn test_render_compilations_continues_after_failures(pool: SqlitePool) {
+        let database = Database::with_pool(pool);
+        let video = persist_video(&database).await.unwrap();
+        let marker = persist_marker(&database, &video.id, 0, 0.0, 20.0, false)
+            .await
+            .unwrap();
+        database
+            .integrity
+            .set_integrity(&video.id, VerificationMode::Sample, Some("broken"))
+            .await
+            .unwrap();
+        let state = app_state(database);
+        let (_, render_jobs) = arrange_variants(
+            &state,
+            clips_body(vec![selected_marker(&marker)]),
+            "compilation.mp4",
+            &render_options(),
+            vec!["a".into(), "b".into(), "c".into()],
+        )
+        .await
+        .unwrap();
+        let video_ids: Vec<_> = render_jobs.iter().map(|j| j.video_id.clone()).collect();
+
+        // all clips are from a broken video that is skipped, so every compilation fails,
+        // but the ones after the first failure are still attempted in order
+        let failed = render_compilations(Arc::new(state), render_jobs)
+            .await
+            .unwrap();
+        assert_eq!(failed, video_ids);
+    }
+
+    fn query_values(queries: &[(String, InteractiveClipsQuery)]) -> Vec<&str> {
+        queries
+            .iter()
+            .map(|(value, query)| {
+                let data = match query {
+                    InteractiveClipsQuery::MarkerTitles { data }
+                    | InteractiveClipsQuery::Performers { data }
+                    | InteractiveClipsQuery::VideoTags { data } => data,
+                };
+                assert_eq!(data, &vec![value.clone()]);
+                value.as_str()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_per_tag_queries_empty() {
+        let queries = per_tag_queries(InteractiveClipsQuery::VideoTags { data: vec![] });
+        assert!(queries.is_empty());
+
+        let queries = per_tag_queries(InteractiveClipsQuery::VideoTags {
+            data: vec!["".into(), "  ".into()],
+        });
+        assert!(queries.is_empty());
+    }
+
+    #[test]
+    fn test_per_tag_queries_duplicates() {
+        let queries = per_tag_queries(InteractiveClipsQuery::Performers {
+            data: vec!["b".into(), "a".into(), "b".into(), "c".into(), "a".into()],
+        });
+        assert_eq!(query_values(&queries), vec!["b", "a", "c"]);
+        assert!(queries
+            .iter()
+            .all(|(_, q)| matches!(q, InteractiveClipsQuery::Performers { .. })));
+    }
+
+    #[test]
+    fn test_per_tag_queries_keep_query_type() {
+        let queries = per_tag_queries(InteractiveClipsQuery::MarkerTitles {
+            data: vec!["Kiss".into(), "Dance".into()],
+        });
+        assert_eq!(query_values(&queries), vec!["Kiss", "Dance"]);
+        assert!(queries
+            .iter()
+            .all(|(_, q)| matches!(q, InteractiveClipsQuery::MarkerTitles { .. })));
+    }
+
+    #[sqlx::test]
+    #[traced_test]
+    async fn test_per_tag_compilations_skip_tags_without_markers(pool: SqlitePool) {
+        let database = Database::with_pool(pool);
+        let video = persist_video(&database).await.unwrap();
+        let marker = persist_marker(&database, &video.id, 0, 0.0, 20.0, false)
+            .await
+            .unwrap();
+        let state = app_state(database);
+        let body = CreatePerTagCompilationsBody {
+            query: InteractiveClipsQuery::MarkerTitles {
+                data: vec![
+                    "does not exist".into(),
+                    marker.title.clone(),
+                    marker.title.clone(),
+                ],
+            },
+            clips: clip_options(),
+            seed: Some("seed".into()),
+            file_name_prefix: Some("tag-".into()),
+            render: render_options(),
+        };
+
+        let (compilations, render_jobs) = arrange_per_tag_compilations(&state, body).await.unwrap();
+
+        assert_eq!(compilations.len(), 1);
+        assert_eq!(render_jobs.len(), 1);
+        assert_eq!(compilations[0].tag, marker.title);
+        assert_eq!(compilations[0].video_id, render_jobs[0].video_id);
+        assert!(compilations[0].file_name.starts_with("tag-"));
+        assert!(!compilations[0].clips.is_empty());
+    }
+}
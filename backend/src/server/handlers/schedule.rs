@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::Json;
+use reqwest::StatusCode;
+use tracing::{error, info};
+
+use super::AppState;
+use crate::data::database::schedules::CreateSchedule;
+use crate::data::database::unix_timestamp_now;
+use crate::server::error::AppError;
+use crate::server::types::{CreateScheduleBody, ScheduleDto, ScheduleRunDto};
+use crate::service::scheduler::{self, Scheduler};
+
+fn validate_schedule(
+    body: &CreateScheduleBody,
+) -> Result<CreateSchedule, HashMap<&'static str, &'static str>> {
+    let mut errors = HashMap::new();
+    if body.name.trim().is_empty() {
+        errors.insert("name", "Name must not be empty");
+    }
+    let next_run = match scheduler::next_run(&body.cron, unix_timestamp_now()) {
+        Ok(Some(next_run)) => Some(next_run),
+        Ok(None) => {
+            errors.insert("cron", "Cron expression never matches");
+            None
+        }
+        Err(_) => {
+            errors.insert("cron", "Invalid cron expression");
+            None
+        }
+    };
+    let options_json = match serde_json::to_string(&body.options) {
+        Ok(json) => json,
+        Err(_) => {
+            errors.insert("options", "Invalid schedule options");
+            String::new()
+        }
+    };
+
+    if errors.is_empty() {
+        Ok(CreateSchedule {
+            name: body.name.trim().to_string(),
+            cron: body.cron.trim().to_string(),
+            enabled: body.enabled,
+            options_json,
+            next_run,
+        })
+    } else {
+        Err(errors)
+    }
+}
+
+async fn get_schedule_dto(state: &AppState, id: i64) -> Result<ScheduleDto, AppError> {
+    match state.database.schedules.get_schedule(id).await? {
+        Some(schedule) => Ok(schedule.try_into()?),
+        None => Err(AppError::StatusCode(StatusCode::NOT_FOUND)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/schedule",
+    responses(
+        (status = 200, description = "List all scheduled compilations", body = Vec<ScheduleDto>),
+    )
+)]
+#[axum::debug_handler]
+/// Lists all scheduled compilations
+pub async fn list_schedules(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ScheduleDto>>, AppError> {
+    let schedules = state
+        .database
+        .schedules
+        .list_schedules()
+        .await?
+        .into_iter()
+        .map(TryFrom::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Json(schedules))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/schedule",
+    request_body = CreateScheduleBody,
+    responses(
+        (status = 200, description = "The newly created schedule", body = ScheduleDto),
+    )
+)]
+#[axum::debug_handler]
+/// Creates a new scheduled compilation
+pub async fn create_schedule(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<CreateScheduleBody>,
+) -> Result<Json<ScheduleDto>, AppError> {
+    let schedule = validate_schedule(&body).map_err(AppError::Validation)?;
+    let id = state.database.schedules.create_schedule(&schedule).await?;
+    Ok(Json(get_schedule_dto(&state, id).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/schedule/{id}",
+    params(
+        ("id" = i64, Path, description = "The ID of the schedule to update")
+    ),
+    request_body = CreateScheduleBody,
+    responses(
+        (status = 200, description = "The updated schedule", body = ScheduleDto),
+    )
+)]
+#[axum::debug_handler]
+/// Updates a scheduled compilation
+pub async fn update_schedule(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<CreateScheduleBody>,
+) -> Result<Json<ScheduleDto>, AppError> {
+    let schedule = validate_schedule(&body).map_err(AppError::Validation)?;
+    get_schedule_dto(&state, id).await?;
+    state
+        .database
+        .schedules
+        .update_schedule(id, &schedule)
+        .await?;
+    Ok(Json(get_schedule_dto(&state, id).await?))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/schedule/{id}",
+    params(
+        ("id" = i64, Path, description = "The ID of the schedule to delete")
+    ),
+    responses(
+        (status = 200, description = "Delete a schedule and its runs", body = ()),
+    )
+)]
+#[axum::debug_handler]
+/// Deletes a scheduled compilation
+pub async fn delete_schedule(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<&'static str>, AppError> {
+    info!("deleting schedule {id}");
+    state.database.schedules.delete_schedule(id).await?;
+    Ok(Json("OK"))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/schedule/{id}/runs",
+    params(
+        ("id" = i64, Path, description = "The ID of the schedule")
+    ),
+    responses(
+        (status = 200, description = "List the runs of a schedule, newest first", body = Vec<ScheduleRunDto>),
+    )
+)]
+#[axum::debug_handler]
+/// Lists the compilations created by a schedule
+pub async fn list_schedule_runs(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ScheduleRunDto>>, AppError> {
+    let runs = state.database.schedules.list_runs(id).await?;
+    Ok(Json(runs.into_iter().map(From::from).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/schedule/{id}/run",
+    params(
+        ("id" = i64, Path, description = "The ID of the schedule to run")
+    ),
+    responses(
+        (status = 200, description = "Runs the schedule in the background (returns immediately)", body = ()),
+    )
+)]
+#[axum::debug_handler]
+/// Runs a scheduled compilation right away
+pub async fn run_schedule(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<&'static str>, AppError> {
+    let Some(schedule) = state.database.schedules.get_schedule(id).await? else {
+        return Err(AppError::StatusCode(StatusCode::NOT_FOUND));
+    };
+
+    let scheduler = Scheduler::new(
+        state.database.clone(),
+        state.directories.clone(),
+        state.ffmpeg_location.clone(),
+    );
+    tokio::spawn(async move {
+        if let Err(e) = scheduler.run_schedule(&schedule).await {
+            error!("failed to run schedule {id}: {e:?}");
+        }
+    });
+
+    Ok(Json("OK"))
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::env;
 use std::sync::Arc;
 
@@ -11,6 +12,7 @@ use tracing::{error, info, warn};
 use crate::data::database::Settings;
 use crate::server::error::AppError;
 use crate::server::handlers::AppState;
+use crate::service::local_metadata::FileNameTemplate;
 use crate::service::new_version_checker::AppVersion;
 
 #[axum::debug_handler]
@@ -63,6 +65,14 @@ pub async fn set_config(
     Json(config): Json<Settings>,
 ) -> Result<Json<&'static str>, AppError> {
     info!("setting config {:#?}", config);
+    for template in &config.file_name_templates {
+        if let Err(error) = FileNameTemplate::parse(template) {
+            return Err(AppError::Validation(HashMap::from([(
+                "fileNameTemplates",
+                error,
+            )])));
+        }
+    }
     state.database.settings.set(config).await?;
 
     Ok(Json("OK"))
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::Json;
+use camino::Utf8Path;
+use reqwest::StatusCode;
+use tracing::info;
+
+use super::AppState;
+use crate::data::database::watch_folders::{CreateWatchFolder, DbWatchFolder};
+use crate::server::error::AppError;
+use crate::server::types::{CreateWatchFolderBody, WatchFolderDto};
+use crate::service::watch_folders::{self, WatchFolderScanResult};
+
+fn validate_watch_folder(
+    body: &CreateWatchFolderBody,
+) -> Result<CreateWatchFolder, HashMap<&'static str, &'static str>> {
+    let path = body.path.trim();
+    if !Utf8Path::new(path).is_dir() {
+        return Err(HashMap::from([("path", "Path must be an existing folder")]));
+    }
+
+    let tags = body.tags.clone().unwrap_or_default();
+    Ok(CreateWatchFolder {
+        path: path.to_string(),
+        recurse: body.recurse,
+        tags: serde_json::to_string(&tags).expect("tags must be serializable"),
+        enabled: body.enabled,
+        folder_tags: body.folder_tags.is_some(),
+        folder_tag_depth: body
+            .folder_tags
+            .and_then(|options| options.depth)
+            .map(|depth| depth as i64),
+    })
+}
+
+async fn get_watch_folder(state: &AppState, id: i64) -> Result<DbWatchFolder, AppError> {
+    match state.database.watch_folders.get_watch_folder(id).await? {
+        Some(folder) => Ok(folder),
+        None => Err(AppError::StatusCode(StatusCode::NOT_FOUND)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/library/watch-folder",
+    responses(
+        (status = 200, description = "List all watch folders", body = Vec<WatchFolderDto>),
+    )
+)]
+#[axum::debug_handler]
+/// Lists all folders that are watched for new videos
+pub async fn list_watch_folders(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<WatchFolderDto>>, AppError> {
+    let folders = state.database.watch_folders.list_watch_folders().await?;
+    Ok(Json(folders.into_iter().map(From::from).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/library/watch-folder",
+    request_body = CreateWatchFolderBody,
+    responses(
+        (status = 200, description = "The newly created watch folder", body = WatchFolderDto),
+    )
+)]
+#[axum::debug_handler]
+/// Adds a folder that is watched for new videos. New files in it are imported automatically,
+/// and videos whose file was removed are flagged as missing.
+pub async fn create_watch_folder(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<CreateWatchFolderBody>,
+) -> Result<Json<WatchFolderDto>, AppError> {
+    let folder = validate_watch_folder(&body).map_err(AppError::Validation)?;
+    let id = state
+        .database
+        .watch_folders
+        .create_watch_folder(&folder)
+        .await?;
+    Ok(Json(get_watch_folder(&state, id).await?.into()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/library/watch-folder/{id}",
+    params(
+        ("id" = i64, Path, description = "The ID of the watch folder to update")
+    ),
+    request_body = CreateWatchFolderBody,
+    responses(
+        (status = 200, description = "The updated watch folder", body = WatchFolderDto),
+    )
+)]
+#[axum::debug_handler]
+/// Updates a watch folder
+pub async fn update_watch_folder(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<CreateWatchFolderBody>,
+) -> Result<Json<WatchFolderDto>, AppError> {
+    let folder = validate_watch_folder(&body).map_err(AppError::Validation)?;
+    get_watch_folder(&state, id).await?;
+    state
+        .database
+        .watch_folders
+        .update_watch_folder(id, &folder)
+        .await?;
+    Ok(Json(get_watch_folder(&state, id).await?.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/library/watch-folder/{id}",
+    params(
+        ("id" = i64, Path, description = "The ID of the watch folder to delete")
+    ),
+    responses(
+        (status = 200, description = "Stop watching the folder, its videos are kept", body = ()),
+    )
+)]
+#[axum::debug_handler]
+/// Stops watching a folder, videos imported from it are kept
+pub async fn delete_watch_folder(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<&'static str>, AppError> {
+    info!("deleting watch folder {id}");
+    state.database.watch_folders.delete_watch_folder(id).await?;
+    Ok(Json("OK"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/library/watch-folder/{id}/scan",
+    params(
+        ("id" = i64, Path, description = "The ID of the watch folder to scan")
+    ),
+    responses(
+        (status = 200, description = "What changed in the folder", body = WatchFolderScanResult),
+    )
+)]
+#[axum::debug_handler]
+/// Scans a watch folder right away and imports all new files in it
+pub async fn scan_watch_folder(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<WatchFolderScanResult>, AppError> {
+    let folder = get_watch_folder(&state, id).await?;
+    let result = watch_folders::scan_folder(state, &folder, false).await?;
+    Ok(Json(result))
+}
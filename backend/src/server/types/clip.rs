@@ -6,7 +6,7 @@ use utoipa::ToSchema;
 
 use super::{Beats, MarkerGroup, SelectedMarker, VideoDto};
 use crate::data::database::videos::VideoSource;
-use crate::service::generator::PaddingType;
+use crate::service::generator::{BrokenVideoHandling, PaddingType};
 
 #[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
 #[serde(rename_all = "kebab-case", tag = "type")]
@@ -18,6 +18,8 @@ pub enum ClipOrder {
     Fixed {
         marker_title_groups: Vec<MarkerGroup>,
     },
+    /// Puts clips next to each other whose boundary frames look alike.
+    Similarity,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
@@ -48,6 +50,12 @@ impl Clip {
     pub fn duration_millis(&self) -> u32 {
         (self.duration() * 1000.0) as u32
     }
+
+    pub fn overlaps(&self, other: &Clip) -> bool {
+        self.video_id == other.video_id
+            && self.range.0 < other.range.1
+            && other.range.0 < self.range.1
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Copy, ToSchema)]
@@ -68,7 +76,7 @@ impl fmt::Display for VideoCodec {
     }
 }
 
-#[derive(Deserialize, Debug, Clone, Copy, ToSchema)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum VideoQuality {
     Low,
@@ -77,7 +85,7 @@ pub enum VideoQuality {
     Lossless,
 }
 
-#[derive(Deserialize, Debug, Clone, Copy, ToSchema)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum EncodingEffort {
     Low,
@@ -102,9 +110,10 @@ pub struct CreateVideoBody {
     pub padding: Option<PaddingType>,
     pub force_re_encode: bool,
     pub include_original_file_name: bool,
+    pub broken_videos: Option<BrokenVideoHandling>,
 }
 
-#[derive(Deserialize, Debug, Serialize, ToSchema)]
+#[derive(Deserialize, Debug, Clone, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct RandomizedClipOptions {
     pub base_duration: f64,
@@ -118,34 +127,63 @@ pub enum MeasureCount {
     Random { min: usize, max: usize },
 }
 
-#[derive(Deserialize, Debug, Serialize, ToSchema)]
+#[derive(Deserialize, Debug, Clone, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct SongClipOptions {
-    pub beats_per_measure: usize,
+    /// Uses the detected number of beats per measure of each song if not set.
+    #[serde(default)]
+    pub beats_per_measure: Option<usize>,
     pub cut_after_measures: MeasureCount,
     pub songs: Vec<Beats>,
 }
 
-#[derive(Deserialize, Debug, Serialize, ToSchema)]
+/// Cuts on the beat like [`SongClipOptions`], but uses short clips in the energetic parts
+/// of the songs and long ones in the calm parts.
+#[derive(Deserialize, Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SongEnergyClipOptions {
+    /// Uses the detected number of beats per measure of each song if not set.
+    #[serde(default)]
+    pub beats_per_measure: Option<usize>,
+    /// Measures per clip in the most energetic parts.
+    pub min_measures: usize,
+    /// Measures per clip in the calmest parts.
+    pub max_measures: usize,
+    pub songs: Vec<Beats>,
+}
+
+#[derive(Deserialize, Debug, Clone, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum ClipLengthOptions {
     Randomized(RandomizedClipOptions),
     Songs(SongClipOptions),
+    SongEnergy(SongEnergyClipOptions),
 }
 
-#[derive(Deserialize, Serialize, Debug, ToSchema)]
+impl ClipLengthOptions {
+    pub fn songs(&self) -> Option<&[Beats]> {
+        match self {
+            ClipLengthOptions::Randomized(_) => None,
+            ClipLengthOptions::Songs(options) => Some(&options.songs),
+            ClipLengthOptions::SongEnergy(options) => Some(&options.songs),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ClipOptions {
     pub clip_picker: ClipPickerOptions,
     pub order: ClipOrder,
 }
 
-#[derive(Deserialize, Debug, Serialize, ToSchema)]
+#[derive(Deserialize, Debug, Clone, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum ClipPickerOptions {
     RoundRobin(RoundRobinClipOptions),
     WeightedRandom(WeightedRandomClipOptions),
     EqualLength(EqualLengthClipOptions),
+    Template(TemplateClipOptions),
     NoSplit,
 }
 
@@ -155,24 +193,21 @@ impl ClipPickerOptions {
             ClipPickerOptions::RoundRobin(opts) => Some(&opts.clip_lengths),
             ClipPickerOptions::WeightedRandom(opts) => Some(&opts.clip_lengths),
             ClipPickerOptions::EqualLength(_) => None,
+            ClipPickerOptions::Template(_) => None,
             ClipPickerOptions::NoSplit => None,
         }
     }
 
     pub fn has_music(&self) -> bool {
-        matches!(self.clip_lengths(), Some(ClipLengthOptions::Songs(_)))
+        self.songs().is_some()
     }
 
     pub fn songs(&self) -> Option<&[Beats]> {
-        if let Some(ClipLengthOptions::Songs(songs)) = self.clip_lengths() {
-            Some(&songs.songs)
-        } else {
-            None
-        }
+        self.clip_lengths().and_then(|lengths| lengths.songs())
     }
 }
 
-#[derive(Deserialize, Debug, Serialize, ToSchema)]
+#[derive(Deserialize, Debug, Clone, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct RoundRobinClipOptions {
     pub length: f64,
@@ -181,7 +216,7 @@ pub struct RoundRobinClipOptions {
     pub min_clip_duration: Option<f64>,
 }
 
-#[derive(Deserialize, Debug, Serialize, ToSchema)]
+#[derive(Deserialize, Debug, Clone, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct WeightedRandomClipOptions {
     pub weights: Vec<(String, f64)>,
@@ -190,7 +225,7 @@ pub struct WeightedRandomClipOptions {
     pub min_clip_duration: Option<f64>,
 }
 
-#[derive(Deserialize, Serialize, Debug, ToSchema)]
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct EqualLengthClipOptions {
     pub clip_duration: f64,
@@ -199,22 +234,199 @@ pub struct EqualLengthClipOptions {
     pub min_clip_duration: Option<f64>,
 }
 
-#[derive(Deserialize, Debug, ToSchema)]
+/// A section of a [`TemplateClipOptions`] template.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateSection {
+    pub name: String,
+    /// Only markers with one of these titles are used in this section (all markers if empty).
+    pub marker_titles: Vec<String>,
+    /// How the clips of this section are picked, the picker's length is the section's length.
+    #[schema(no_recursion)]
+    pub clip_picker: ClipPickerOptions,
+    pub order: ClipOrder,
+}
+
+/// Composes the clip list out of sections that each pick clips with their own settings.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateClipOptions {
+    pub sections: Vec<TemplateSection>,
+    /// Names of the sections in the order they are played, sections can appear several
+    /// times. Defaults to every section once, in the order they are listed.
+    pub sequence: Option<Vec<String>>,
+}
+
+/// A clip that must stay at the given position in the generated clip list.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PinnedClip {
+    /// Index of the clip in the final clip list.
+    pub position: usize,
+    pub clip: Clip,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, ToSchema)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum FreshnessMode {
+    /// Never use ranges from recent compilations, unless nothing else is left.
+    Exclude,
+    /// Keep ranges from recent compilations with the given probability (0 to 1).
+    Downweight { weight: f64 },
+}
+
+/// Avoids source ranges that were already used in recent compilations.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FreshnessOptions {
+    /// How many of the most recent compilations to take into account.
+    pub recent_compilations: usize,
+    pub mode: FreshnessMode,
+}
+
+/// Prefers the parts of markers with a lot of motion. Only videos whose motion was
+/// analyzed before are affected.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MotionOptions {
+    /// Share (0 to 1) of each video's least active moments that are treated as static
+    /// and skipped.
+    pub static_share: f64,
+}
+
+#[derive(Deserialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderSettings {
+    pub output_resolution: (u32, u32),
+    pub output_fps: u32,
+    pub video_codec: VideoCodec,
+    pub video_quality: VideoQuality,
+    pub encoding_effort: EncodingEffort,
+}
+
+#[derive(Deserialize, Debug, Clone, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateClipsBody {
     pub markers: Vec<SelectedMarker>,
     pub seed: Option<String>,
     pub clips: ClipOptions,
+    /// Clips that are kept in place, the remaining slots are filled from the seed.
+    pub pinned_clips: Option<Vec<PinnedClip>>,
+    pub freshness: Option<FreshnessOptions>,
+    pub motion: Option<MotionOptions>,
+    /// Used to estimate the output size and render time in the clip statistics.
+    pub render_settings: Option<RenderSettings>,
+}
+
+/// Encoding options shared by all compilations rendered in one batch.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchRenderOptions {
+    pub output_resolution: (u32, u32),
+    pub output_fps: u32,
+    pub song_ids: Vec<i64>,
+    pub music_volume: Option<f64>,
+    pub video_codec: VideoCodec,
+    pub video_quality: VideoQuality,
+    pub encoding_effort: EncodingEffort,
+    pub padding: Option<PaddingType>,
+    pub force_re_encode: bool,
+    pub include_original_file_name: bool,
+    pub broken_videos: Option<BrokenVideoHandling>,
+}
+
+impl BatchRenderOptions {
+    pub fn to_video_body(
+        &self,
+        video_id: String,
+        file_name: String,
+        clips: Vec<Clip>,
+        selected_markers: Vec<SelectedMarker>,
+    ) -> CreateVideoBody {
+        CreateVideoBody {
+            video_id,
+            file_name,
+            clips,
+            selected_markers,
+            output_resolution: self.output_resolution,
+            output_fps: self.output_fps,
+            song_ids: self.song_ids.clone(),
+            music_volume: self.music_volume,
+            video_codec: self.video_codec,
+            video_quality: self.video_quality,
+            encoding_effort: self.encoding_effort,
+            padding: self.padding,
+            force_re_encode: self.force_re_encode,
+            include_original_file_name: self.include_original_file_name,
+            broken_videos: self.broken_videos,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, ToSchema)]
 #[serde(rename_all = "camelCase", tag = "type")]
+pub enum VariantSeeds {
+    /// Creates one variant per given seed.
+    Fixed { seeds: Vec<String> },
+    /// Creates `count` variants with random seeds.
+    Random { count: usize },
+}
+
+/// Creates several variants of the same compilation (one per seed) and renders them
+/// one after the other.
+#[derive(Deserialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateVariantsBody {
+    pub clips: CreateClipsBody,
+    pub seeds: VariantSeeds,
+    /// The seed is appended to the file name of each variant.
+    pub file_name: String,
+    #[serde(flatten)]
+    pub render: BatchRenderOptions,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VariantDto {
+    /// ID to query the progress of this variant with.
+    pub video_id: String,
+    pub seed: String,
+    pub file_name: String,
+    pub clips: Vec<Clip>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase", tag = "type")]
 pub enum InteractiveClipsQuery {
     MarkerTitles { data: Vec<String> },
     Performers { data: Vec<String> },
     VideoTags { data: Vec<String> },
 }
 
+/// Creates one compilation for each of the values in the query (each tag, marker title
+/// or performer), with the same clip and encoding options.
+#[derive(Deserialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePerTagCompilationsBody {
+    pub query: InteractiveClipsQuery,
+    pub clips: ClipOptions,
+    pub seed: Option<String>,
+    /// Prepended to the file names, which are otherwise just the tag.
+    pub file_name_prefix: Option<String>,
+    #[serde(flatten)]
+    pub render: BatchRenderOptions,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCompilationDto {
+    /// ID to query the progress of this compilation with.
+    pub video_id: String,
+    pub tag: String,
+    pub file_name: String,
+    pub clips: Vec<Clip>,
+}
+
 #[derive(Deserialize, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateInteractiveClipsBody {
@@ -231,4 +443,99 @@ pub struct ClipsResponse {
     pub streams: HashMap<String, String>,
     pub videos: Vec<VideoDto>,
     pub beat_offsets: Option<Vec<f32>>,
+    pub statistics: ClipStatistics,
+    /// Set if the clips should be ordered by visual similarity, but aren't because the
+    /// frames of the videos are still being analyzed. Generating the clips again later
+    /// sorts them.
+    pub similarity_order_pending: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreenTimeShare {
+    pub name: String,
+    /// Screen time in seconds.
+    pub duration: f64,
+    /// Share of the total duration, from 0 to 1.
+    pub share: f64,
+}
+
+/// Screen time of a single video. Videos are kept apart even if they have the same title.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoScreenTimeShare {
+    pub video_id: String,
+    pub title: String,
+    /// Screen time in seconds.
+    pub duration: f64,
+    /// Share of the total duration, from 0 to 1.
+    pub share: f64,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderEstimate {
+    pub file_size_bytes: u64,
+    pub render_time_seconds: f64,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipStatistics {
+    /// Total duration of all clips in seconds.
+    pub total_duration: f64,
+    pub clip_count: usize,
+    pub cut_count: usize,
+    pub shortest_clip: Option<Clip>,
+    pub longest_clip: Option<Clip>,
+    pub videos: Vec<VideoScreenTimeShare>,
+    pub performers: Vec<ScreenTimeShare>,
+    pub marker_titles: Vec<ScreenTimeShare>,
+    pub tags: Vec<ScreenTimeShare>,
+    /// Only present if render settings were given.
+    pub estimate: Option<RenderEstimate>,
+}
+
+/// A single editing operation on a stored clip list.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum ClipListEdit {
+    /// Moves the clip at `from` so that it ends up at index `to`.
+    Move {
+        from: usize,
+        to: usize,
+    },
+    /// Sets a new start and end point (in seconds) for the clip.
+    Trim {
+        index: usize,
+        start: f64,
+        end: f64,
+    },
+    /// Splits the clip into two clips at the given time (in seconds).
+    Split {
+        index: usize,
+        time: f64,
+    },
+    /// Inserts a copy of the clip right after it.
+    Duplicate {
+        index: usize,
+    },
+    Delete {
+        index: usize,
+    },
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateClipListBody {
+    pub clips: Vec<Clip>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipListDto {
+    pub id: String,
+    pub clips: Vec<Clip>,
+    /// How many edits can be undone.
+    pub undo_steps: usize,
 }
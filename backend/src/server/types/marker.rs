@@ -108,7 +108,7 @@ impl MarkerDtoConverter {
     }
 }
 
-#[derive(Deserialize, Debug, ToSchema)]
+#[derive(Deserialize, Debug, Clone, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct SelectedMarker {
     pub id: i64,
@@ -1,12 +1,18 @@
 pub use clip::*;
 pub use marker::*;
+pub use playlist::*;
+pub use schedule::*;
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 pub use video::*;
+pub use watch_folder::*;
 
 mod clip;
 mod marker;
+mod playlist;
+mod schedule;
 mod video;
+mod watch_folder;
 
 #[derive(Serialize, Debug, Clone, ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -52,9 +58,90 @@ impl<T: Serialize + ToSchema> Page<T> {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct Beats {
     pub offsets: Vec<f32>,
     pub length: f32,
+    /// Offsets of the first beat of every measure, if they could be detected.
+    #[serde(default)]
+    pub downbeats: Option<Vec<f32>>,
+    /// The detected number of beats per measure.
+    #[serde(default)]
+    pub beats_per_measure: Option<usize>,
+    #[serde(default)]
+    pub energy: Option<SongEnergy>,
+}
+
+impl Beats {
+    /// The tempo of the song, from the median time between two beats.
+    pub fn bpm(&self) -> Option<f32> {
+        let mut intervals: Vec<f32> = self.offsets.windows(2).map(|w| w[1] - w[0]).collect();
+        if intervals.is_empty() {
+            return None;
+        }
+        intervals.sort_by(|a, b| a.total_cmp(b));
+        let median = intervals[intervals.len() / 2];
+        (median > 0.0).then(|| 60.0 / median)
+    }
+}
+
+/// Downsampled waveform of a song. Every bucket covers the same number of samples and
+/// stores the lowest and the highest sample in it, scaled to the range -1 to 1.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SongWaveform {
+    /// Length of the song in seconds.
+    pub length: f32,
+    /// Length of a bucket in seconds.
+    pub bucket_duration: f32,
+    pub min: Vec<f32>,
+    pub max: Vec<f32>,
+}
+
+/// How energetic a song is over time, from 0 (calmest part) to 1 (most intense part).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SongEnergy {
+    /// Time between two values in seconds.
+    pub interval: f32,
+    pub values: Vec<f32>,
+}
+
+impl SongEnergy {
+    pub fn at(&self, time: f32) -> Option<f32> {
+        let index = (time / self.interval).max(0.0) as usize;
+        self.values
+            .get(index.min(self.values.len().saturating_sub(1)))
+            .copied()
+    }
+}
+
+/// A manual correction of a song's beat grid. All times are in seconds.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum BeatGridEdit {
+    /// Replaces the beats with a regular grid that has a beat at `phase`. If the beats per
+    /// measure are known, `phase` is also the start of a measure.
+    SetTempo {
+        bpm: f32,
+        phase: f32,
+        beats_per_measure: Option<usize>,
+    },
+    /// Moves all beats by the given offset (can be negative).
+    Nudge {
+        offset: f32,
+    },
+    InsertBeat {
+        offset: f32,
+    },
+    DeleteBeat {
+        index: usize,
+    },
+    /// Calculates tempo and phase from taps along the song, the first tap starts a measure.
+    TapTempo {
+        taps: Vec<f32>,
+        beats_per_measure: Option<usize>,
+    },
 }
 
 #[derive(Serialize, ToSchema)]
@@ -65,6 +152,19 @@ pub struct SongDto {
     pub file_name: String,
     pub url: String,
     pub beats: Vec<f32>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Tempo of the song in beats per minute, if its beats are detected already.
+    pub bpm: Option<f32>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateSongBody {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::data::database::playlists::DbPlaylist;
+
+#[derive(Deserialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePlaylistBody {
+    pub name: String,
+    /// The songs of the playlist, in order.
+    pub song_ids: Vec<i64>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistDto {
+    pub id: i64,
+    pub name: String,
+    /// The songs of the playlist, in order. Can be used as `songIds` when creating clips.
+    pub song_ids: Vec<i64>,
+    pub created_on: i64,
+}
+
+impl From<DbPlaylist> for PlaylistDto {
+    fn from(value: DbPlaylist) -> Self {
+        PlaylistDto {
+            id: value.id,
+            name: value.name,
+            song_ids: value.song_ids,
+            created_on: value.created_on,
+        }
+    }
+}
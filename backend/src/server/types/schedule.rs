@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{BatchRenderOptions, ClipOptions, InteractiveClipsQuery};
+use crate::data::database::schedules::{DbSchedule, DbScheduleRun};
+use crate::Result;
+
+/// What a scheduled compilation is made of.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleOptions {
+    pub query: InteractiveClipsQuery,
+    /// Only use markers from videos that were imported into the library in the last N days.
+    pub added_within_days: Option<u32>,
+    pub clips: ClipOptions,
+    pub render: BatchRenderOptions,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateScheduleBody {
+    pub name: String,
+    /// Cron expression with five fields (minute, hour, day of month, month, day of week), in UTC.
+    pub cron: String,
+    pub enabled: bool,
+    pub options: ScheduleOptions,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleDto {
+    pub id: i64,
+    pub name: String,
+    pub cron: String,
+    pub enabled: bool,
+    pub options: ScheduleOptions,
+    pub last_run: Option<i64>,
+    pub next_run: Option<i64>,
+    pub created_on: i64,
+}
+
+impl TryFrom<DbSchedule> for ScheduleDto {
+    type Error = color_eyre::Report;
+
+    fn try_from(value: DbSchedule) -> Result<Self> {
+        Ok(ScheduleDto {
+            options: serde_json::from_str(&value.options_json)?,
+            id: value.id,
+            name: value.name,
+            cron: value.cron,
+            enabled: value.enabled,
+            last_run: value.last_run,
+            next_run: value.next_run,
+            created_on: value.created_on,
+        })
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleRunDto {
+    pub id: i64,
+    pub schedule_id: i64,
+    pub video_id: Option<String>,
+    pub file_name: Option<String>,
+    pub seed: String,
+    pub error: Option<String>,
+    pub created_on: i64,
+}
+
+impl From<DbScheduleRun> for ScheduleRunDto {
+    fn from(value: DbScheduleRun) -> Self {
+        ScheduleRunDto {
+            id: value.id,
+            schedule_id: value.schedule_id,
+            video_id: value.video_id,
+            file_name: value.file_name,
+            seed: value.seed,
+            error: value.error,
+            created_on: value.created_on,
+        }
+    }
+}
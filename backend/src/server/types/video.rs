@@ -69,6 +69,8 @@ pub struct VideoDto {
     pub stash_scene_id: Option<i64>,
     pub tags: Vec<String>,
     pub created_on: i64,
+    /// When the file of the video was found to be missing, if it is.
+    pub missing_since: Option<i64>,
 }
 
 impl VideoLike for VideoDto {
@@ -105,6 +107,7 @@ impl From<FindScenesQueryFindScenesScenes> for VideoDto {
             duration: file.duration,
             tags: value.tags.into_iter().map(|t| t.name).collect(),
             created_on,
+            missing_since: None,
         }
     }
 }
@@ -130,6 +133,7 @@ impl From<DbVideo> for VideoDto {
             tags,
             file_path: Some(value.file_path),
             created_on: value.video_created_on,
+            missing_since: value.missing_since,
         }
     }
 }
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::data::database::videos::tags_from_string;
+use crate::data::database::watch_folders::DbWatchFolder;
+use crate::service::video::FolderTagOptions;
+use crate::service::watch_folders::folder_tag_options;
+
+#[derive(Deserialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateWatchFolderBody {
+    pub path: String,
+    pub recurse: bool,
+    /// Tags that all videos imported from the folder get.
+    pub tags: Option<Vec<String>>,
+    pub enabled: bool,
+    /// Tags imported videos with the folders they're in, if set.
+    pub folder_tags: Option<FolderTagOptions>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchFolderDto {
+    pub id: i64,
+    pub path: String,
+    pub recurse: bool,
+    pub tags: Vec<String>,
+    pub enabled: bool,
+    pub folder_tags: Option<FolderTagOptions>,
+    pub last_scan: Option<i64>,
+    pub created_on: i64,
+}
+
+impl From<DbWatchFolder> for WatchFolderDto {
+    fn from(value: DbWatchFolder) -> Self {
+        WatchFolderDto {
+            tags: tags_from_string(Some(&value.tags)),
+            folder_tags: folder_tag_options(&value),
+            id: value.id,
+            path: value.path,
+            recurse: value.recurse,
+            enabled: value.enabled,
+            last_scan: value.last_scan,
+            created_on: value.created_on,
+        }
+    }
+}
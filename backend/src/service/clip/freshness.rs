@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::data::database::compilations::UsedRange;
+use crate::server::types::FreshnessMode;
+use crate::service::Marker;
+
+/// Parts of a marker shorter than this (in seconds) are dropped when splitting markers.
+const MIN_SEGMENT_DURATION: f64 = 1.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Freshness {
+    pub mode: FreshnessMode,
+    pub used_ranges: Vec<UsedRange>,
+}
+
+/// Splits the range `(start, end)` into segments, marking the ones that overlap any of the
+/// given (sorted) ranges.
+pub(super) fn split_range(start: f64, end: f64, used: &[(f64, f64)]) -> Vec<(f64, f64, bool)> {
+    let mut segments = vec![];
+    let mut offset = start;
+    for &(used_start, used_end) in used {
+        if used_end <= offset || used_start >= end {
+            continue;
+        }
+        if used_start > offset {
+            segments.push((offset, used_start, false));
+        }
+        let stale_end = used_end.min(end);
+        if stale_end > offset {
+            segments.push((offset.max(used_start), stale_end, true));
+            offset = stale_end;
+        }
+    }
+    if offset < end {
+        segments.push((offset, end, false));
+    }
+
+    segments
+}
+
+/// Removes (or with [`FreshnessMode::Downweight`], randomly thins out) the parts of the markers
+/// that were already used in recent compilations. Falls back to the original markers if
+/// nothing would be left otherwise.
+pub fn apply_freshness(
+    markers: Vec<Marker>,
+    freshness: &Freshness,
+    rng: &mut StdRng,
+) -> Vec<Marker> {
+    let mut used_by_video: HashMap<&str, Vec<(f64, f64)>> = HashMap::new();
+    for range in &freshness.used_ranges {
+        used_by_video
+            .entry(range.video_id.as_str())
+            .or_default()
+            .push((range.start_time, range.end_time));
+    }
+    for ranges in used_by_video.values_mut() {
+        ranges.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+
+    let mut result = vec![];
+    for marker in &markers {
+        let Some(used) = used_by_video.get(marker.video_id.as_str()) else {
+            result.push(marker.clone());
+            continue;
+        };
+
+        for (start, end, stale) in split_range(marker.start_time, marker.end_time, used) {
+            if end - start < MIN_SEGMENT_DURATION {
+                continue;
+            }
+            let keep = !stale
+                || match freshness.mode {
+                    FreshnessMode::Exclude => false,
+                    FreshnessMode::Downweight { weight } => rng.random_bool(weight.clamp(0.0, 1.0)),
+                };
+            if keep {
+                result.push(Marker {
+                    start_time: start,
+                    end_time: end,
+                    ..marker.clone()
+                });
+            }
+        }
+    }
+
+    if result.is_empty() && !markers.is_empty() {
+        warn!("all markers were used in recent compilations, ignoring freshness options");
+        markers
+    } else {
+        info!(
+            "kept {} marker segments after applying freshness options {:?}",
+            result.len(),
+            freshness.mode
+        );
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_test::traced_test;
+
+    use super::*;
+    use crate::data::database::videos::VideoSource;
+    use crate::helpers::random::create_seeded_rng;
+
+    fn marker(video_id: &str, start_time: f64, end_time: f64) -> Marker {
+        Marker {
+            id: 1,
+            start_time,
+            end_time,
+            index_within_video: 0,
+            video_id: video_id.to_string(),
+            title: "marker".to_string(),
+            loops: 1,
+            source: VideoSource::Folder,
+        }
+    }
+
+    fn used(video_id: &str, start_time: f64, end_time: f64) -> UsedRange {
+        UsedRange {
+            video_id: video_id.to_string(),
+            start_time,
+            end_time,
+        }
+    }
+
+    #[test]
+    fn test_split_range() {
+        let segments = split_range(0.0, 30.0, &[(5.0, 10.0), (20.0, 40.0)]);
+        assert_eq!(
+            segments,
+            vec![
+                (0.0, 5.0, false),
+                (5.0, 10.0, true),
+                (10.0, 20.0, false),
+                (20.0, 30.0, true)
+            ]
+        );
+    }
+
+    #[traced_test]
+    #[test]
+    fn test_exclude_used_ranges() {
+        let mut rng = create_seeded_rng(None);
+        let markers = vec![marker("a", 0.0, 30.0), marker("b", 0.0, 10.0)];
+        let freshness = Freshness {
+            mode: FreshnessMode::Exclude,
+            used_ranges: vec![used("a", 10.0, 20.0), used("b", 0.0, 10.0)],
+        };
+
+        let result = apply_freshness(markers, &freshness, &mut rng);
+        assert_eq!(result.len(), 2);
+        assert_eq!((result[0].start_time, result[0].end_time), (0.0, 10.0));
+        assert_eq!((result[1].start_time, result[1].end_time), (20.0, 30.0));
+        assert!(result.iter().all(|m| m.video_id == "a"));
+    }
+
+    #[traced_test]
+    #[test]
+    fn test_downweight_used_ranges() {
+        let mut rng = create_seeded_rng(None);
+        let markers = vec![marker("a", 0.0, 30.0)];
+        let freshness = Freshness {
+            mode: FreshnessMode::Downweight { weight: 1.0 },
+            used_ranges: vec![used("a", 10.0, 20.0)],
+        };
+        let result = apply_freshness(markers.clone(), &freshness, &mut rng);
+        assert_eq!(result.len(), 3);
+
+        let freshness = Freshness {
+            mode: FreshnessMode::Downweight { weight: 0.0 },
+            ..freshness
+        };
+        let result = apply_freshness(markers, &freshness, &mut rng);
+        assert_eq!(result.len(), 2);
+    }
+
+    #[traced_test]
+    #[test]
+    fn test_fall_back_when_everything_was_used() {
+        let mut rng = create_seeded_rng(None);
+        let markers = vec![marker("a", 0.0, 30.0)];
+        let freshness = Freshness {
+            mode: FreshnessMode::Exclude,
+            used_ranges: vec![used("a", 0.0, 30.0)],
+        };
+
+        let result = apply_freshness(markers.clone(), &freshness, &mut rng);
+        assert_eq!(result, markers);
+    }
+}
@@ -8,6 +8,9 @@ use tracing::{debug, info};
 use super::get_divisors;
 use crate::server::types::{Beats, ClipLengthOptions, MeasureCount};
 
+/// Used for songs without a detected meter if the options don't set one.
+const DEFAULT_BEATS_PER_MEASURE: usize = 4;
+
 #[derive(Debug)]
 pub struct RandomizedClipLengthPicker<'a> {
     rng: &'a mut StdRng,
@@ -59,12 +62,22 @@ impl<'a> Iterator for RandomizedClipLengthPicker<'a> {
     }
 }
 
+/// How many measures long the clips are.
+#[derive(Debug, Clone, Copy)]
+enum ClipMeasures {
+    Count(MeasureCount),
+    /// Between `max` in the calmest and `min` in the most energetic parts of a song.
+    Energy {
+        min: usize,
+        max: usize,
+    },
+}
+
 #[derive(Debug)]
 pub struct SongClipLengthPicker<'a> {
     rng: &'a mut StdRng,
     songs: Vec<Beats>,
-    beats_per_measure: usize,
-    cut_after_measure_count: MeasureCount,
+    measures: ClipMeasures,
     song_index: usize,
     beat_index: usize,
 }
@@ -72,13 +85,53 @@ pub struct SongClipLengthPicker<'a> {
 impl<'a> SongClipLengthPicker<'a> {
     pub fn new(
         rng: &'a mut StdRng,
-        mut songs: Vec<Beats>,
-        beats_per_measure: usize,
+        songs: Vec<Beats>,
+        beats_per_measure: Option<usize>,
         cut_after_measure_count: MeasureCount,
+    ) -> Self {
+        Self::create(
+            rng,
+            songs,
+            beats_per_measure,
+            ClipMeasures::Count(cut_after_measure_count),
+        )
+    }
+
+    pub fn with_energy(
+        rng: &'a mut StdRng,
+        songs: Vec<Beats>,
+        beats_per_measure: Option<usize>,
+        min_measures: usize,
+        max_measures: usize,
+    ) -> Self {
+        Self::create(
+            rng,
+            songs,
+            beats_per_measure,
+            ClipMeasures::Energy {
+                min: min_measures,
+                max: max_measures,
+            },
+        )
+    }
+
+    fn create(
+        rng: &'a mut StdRng,
+        mut songs: Vec<Beats>,
+        beats_per_measure: Option<usize>,
+        measures: ClipMeasures,
     ) -> Self {
         assert!(!songs.is_empty(), "songs must not be empty");
 
         for beats in &mut songs {
+            // the picker counts in measures, so the beats are replaced with the bar lines
+            let beats_per_measure = beats_per_measure
+                .or(beats.beats_per_measure)
+                .unwrap_or(DEFAULT_BEATS_PER_MEASURE)
+                .max(1);
+            beats.offsets = measure_starts(beats, beats_per_measure);
+            beats.downbeats = None;
+
             if beats.offsets.first() != Some(&0.0) {
                 beats.offsets.insert(0, 0.0);
             }
@@ -91,14 +144,37 @@ impl<'a> SongClipLengthPicker<'a> {
         Self {
             rng,
             songs,
-            beats_per_measure,
-            cut_after_measure_count,
+            measures,
             song_index: 0,
             beat_index: 0,
         }
     }
 }
 
+/// Returns the start of every measure. Measures begin at the first detected downbeat, so
+/// the beats before it (the pickup) end up in a partial measure of their own.
+fn measure_starts(beats: &Beats, beats_per_measure: usize) -> Vec<f32> {
+    let phase = beats
+        .downbeats
+        .as_ref()
+        .and_then(|downbeats| downbeats.first())
+        .and_then(|first| {
+            beats
+                .offsets
+                .iter()
+                .position(|offset| (offset - first).abs() < f32::EPSILON)
+        })
+        .unwrap_or(0);
+
+    beats
+        .offsets
+        .iter()
+        .skip(phase)
+        .step_by(beats_per_measure)
+        .copied()
+        .collect()
+}
+
 impl<'a> Iterator for SongClipLengthPicker<'a> {
     type Item = f64;
 
@@ -116,18 +192,29 @@ impl<'a> Iterator for SongClipLengthPicker<'a> {
             return None;
         }
 
-        let beats = &self.songs[self.song_index].offsets;
-        let num_measures = match self.cut_after_measure_count {
-            MeasureCount::Fixed { count } => count,
-            MeasureCount::Random { min, max } => self.rng.random_range(min..max),
+        let song = &self.songs[self.song_index];
+        let beats = &song.offsets;
+        let num_measures = match self.measures {
+            ClipMeasures::Count(MeasureCount::Fixed { count }) => count,
+            ClipMeasures::Count(MeasureCount::Random { min, max }) => {
+                self.rng.random_range(min..max)
+            }
+            ClipMeasures::Energy { min, max } => {
+                let energy = song
+                    .energy
+                    .as_ref()
+                    .and_then(|energy| energy.at(beats[self.beat_index]))
+                    .unwrap_or(0.5);
+                let measures = max as f32 - (max as f32 - min as f32) * energy;
+                (measures.round() as usize).max(1)
+            }
         };
-        let num_beats_to_advance = self.beats_per_measure * num_measures;
-        let next_beat_index = (self.beat_index + num_beats_to_advance).min(beats.len() - 1);
+        let next_beat_index = (self.beat_index + num_measures).min(beats.len() - 1);
         let start = beats[self.beat_index];
         let end = beats[next_beat_index];
         let duration = (end - start) as f64;
 
-        debug!("advancing by {num_beats_to_advance} beats, next clip from {start} - {end} seconds ({duration} seconds long)");
+        debug!("advancing by {num_measures} measures, next clip from {start} - {end} seconds ({duration} seconds long)");
         debug!(
             "next beat index: {}, number of beats: {}",
             next_beat_index,
@@ -175,6 +262,15 @@ impl<'a> ClipLengthPicker<'a> {
                     options.cut_after_measures,
                 ))
             }
+            ClipLengthOptions::SongEnergy(options) => {
+                ClipLengthPicker::Songs(SongClipLengthPicker::with_energy(
+                    rng,
+                    options.songs,
+                    options.beats_per_measure,
+                    options.min_measures,
+                    options.max_measures,
+                ))
+            }
         }
     }
 
@@ -194,7 +290,7 @@ mod test {
     use tracing_test::traced_test;
 
     use crate::helpers::random::create_seeded_rng;
-    use crate::server::types::{Beats, MeasureCount};
+    use crate::server::types::{Beats, MeasureCount, SongEnergy};
     use crate::service::clip::length_picker::{RandomizedClipLengthPicker, SongClipLengthPicker};
     use crate::service::fixtures;
 
@@ -206,13 +302,20 @@ mod test {
             Beats {
                 length: 250.0,
                 offsets: (0..250).map(|n| n as f32).collect(),
+                downbeats: None,
+                beats_per_measure: None,
+                energy: None,
             },
             Beats {
                 length: 250.0,
                 offsets: (0..250).map(|n| n as f32).collect(),
+                downbeats: None,
+                beats_per_measure: None,
+                energy: None,
             },
         ];
-        let songs = SongClipLengthPicker::new(&mut rng, beats, 4, MeasureCount::Fixed { count: 1 });
+        let songs =
+            SongClipLengthPicker::new(&mut rng, beats, Some(4), MeasureCount::Fixed { count: 1 });
         let durations: Vec<_> = songs.collect();
         assert_eq!(126, durations.len());
     }
@@ -225,14 +328,24 @@ mod test {
             Beats {
                 length: 10.0,
                 offsets: (0..10).map(|n| n as f32).collect(),
+                downbeats: None,
+                beats_per_measure: None,
+                energy: None,
             },
             Beats {
                 length: 10.0,
                 offsets: (0..10).map(|n| n as f32).collect(),
+                downbeats: None,
+                beats_per_measure: None,
+                energy: None,
             },
         ];
-        let songs =
-            SongClipLengthPicker::new(&mut rng, beats, 4, MeasureCount::Random { min: 1, max: 3 });
+        let songs = SongClipLengthPicker::new(
+            &mut rng,
+            beats,
+            Some(4),
+            MeasureCount::Random { min: 1, max: 3 },
+        );
         let durations: Vec<_> = songs.collect();
         assert_eq!(vec![4.0, 6.0, 4.0, 4.0, 2.0], durations);
         assert_eq!(5, durations.len());
@@ -244,13 +357,73 @@ mod test {
         );
     }
 
+    #[traced_test]
+    #[test]
+    fn clip_lengths_downbeats() {
+        let mut rng = create_seeded_rng(None);
+        let beats = vec![
+            // pickup beat at 0, measures of three beats start at 1, 4 and 7
+            Beats {
+                length: 10.0,
+                offsets: (0..10).map(|n| n as f32).collect(),
+                downbeats: Some(vec![1.0, 4.0, 7.0]),
+                beats_per_measure: Some(3),
+                energy: None,
+            },
+            Beats {
+                length: 8.0,
+                offsets: (0..8).map(|n| n as f32).collect(),
+                downbeats: None,
+                beats_per_measure: None,
+                energy: None,
+            },
+        ];
+        // the detected meter is used when the options don't set one, the pickup beat is
+        // a measure of its own
+        let songs = SongClipLengthPicker::new(
+            &mut rng,
+            beats.clone(),
+            None,
+            MeasureCount::Fixed { count: 1 },
+        );
+        let durations: Vec<_> = songs.collect();
+        assert_eq!(vec![1.0, 3.0, 3.0, 3.0, 4.0, 4.0], durations);
+
+        // the user's value wins over the detected one
+        let songs =
+            SongClipLengthPicker::new(&mut rng, beats, Some(4), MeasureCount::Fixed { count: 1 });
+        let durations: Vec<_> = songs.collect();
+        assert_eq!(vec![1.0, 4.0, 4.0, 1.0, 4.0, 4.0], durations);
+    }
+
+    #[traced_test]
+    #[test]
+    fn clip_lengths_energy() {
+        let mut rng = create_seeded_rng(None);
+        let beats = vec![Beats {
+            length: 32.0,
+            offsets: (0..32).map(|n| n as f32).collect(),
+            downbeats: None,
+            beats_per_measure: None,
+            // calm first half, energetic second half
+            energy: Some(SongEnergy {
+                interval: 16.0,
+                values: vec![0.0, 1.0],
+            }),
+        }];
+        let songs = SongClipLengthPicker::with_energy(&mut rng, beats, Some(4), 1, 2);
+        let durations: Vec<_> = songs.collect();
+        assert_eq!(vec![8.0, 8.0, 4.0, 4.0, 4.0, 4.0], durations);
+    }
+
     #[traced_test]
     #[test]
     fn clip_lengths_songs() {
         let mut rng = create_seeded_rng(None);
         let songs = fixtures::songs();
         let expected_duration: f64 = songs.iter().map(|s| s.length as f64).sum();
-        let state = SongClipLengthPicker::new(&mut rng, songs, 1, MeasureCount::Fixed { count: 1 });
+        let state =
+            SongClipLengthPicker::new(&mut rng, songs, Some(1), MeasureCount::Fixed { count: 1 });
         let total: f64 = state.sum();
         assert!(
             total >= expected_duration,
@@ -10,17 +10,22 @@ use tracing::info;
 use super::Marker;
 use crate::helpers::math;
 use crate::helpers::random::create_seeded_rng;
-use crate::server::types::{Beats, Clip, ClipOptions, ClipOrder, ClipPickerOptions};
+use crate::server::types::{Beats, Clip, ClipOptions, ClipOrder, ClipPickerOptions, PinnedClip};
 use crate::service::clip::equal_len::EqualLengthClipPicker;
+use crate::service::clip::freshness::Freshness;
+use crate::service::clip::motion::Motion;
 use crate::service::clip::round_robin::RoundRobinClipPicker;
 use crate::service::clip::sort::{ClipSorter, RandomClipSorter, SceneOrderClipSorter};
 use crate::service::clip::weighted::WeightedRandomClipPicker;
 
 mod equal_len;
+pub mod freshness;
 mod length_picker;
+pub mod motion;
 mod round_robin;
 mod sort;
 mod state;
+mod template;
 mod weighted;
 
 pub trait ClipPicker {
@@ -34,11 +39,17 @@ pub trait ClipPicker {
     ) -> Vec<Clip>;
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateClipsOptions {
     pub markers: Vec<Marker>,
     pub seed: Option<String>,
     pub clip_options: ClipOptions,
+    #[serde(default)]
+    pub pinned_clips: Vec<PinnedClip>,
+    #[serde(default)]
+    pub freshness: Option<Freshness>,
+    #[serde(default)]
+    pub motion: Option<Motion>,
 }
 
 impl CreateClipsOptions {
@@ -94,9 +105,155 @@ fn normalize_beat_offsets(songs: &[Beats]) -> Vec<f32> {
     offsets
 }
 
+/// How the pinned clips make room for themselves in the generated clip list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PinnedPlacement {
+    /// The picker produces a shorter clip list and the pinned clips are inserted into it.
+    Insert,
+    /// Every pinned clip takes over the slot of the generated clip at its position, so the
+    /// timing of the clip list (like its alignment to the beats of songs) doesn't change.
+    ReplaceSlots,
+}
+
+/// Shortens the target length of the clip picker by the duration of the pinned clips, so
+/// inserting them later keeps the requested length. Pickers that follow songs or templates
+/// can't be shortened without changing their timing, their slots are replaced instead.
+fn reserve_pinned_clips(
+    clip_picker: &mut ClipPickerOptions,
+    pinned_clips: &[PinnedClip],
+) -> PinnedPlacement {
+    if clip_picker.has_music() {
+        return PinnedPlacement::ReplaceSlots;
+    }
+
+    let pinned_duration: f64 = pinned_clips.iter().map(|p| p.clip.duration()).sum();
+    let length = match clip_picker {
+        ClipPickerOptions::RoundRobin(options) => Some(&mut options.length),
+        ClipPickerOptions::WeightedRandom(options) => Some(&mut options.length),
+        ClipPickerOptions::EqualLength(options) => options.length.as_mut(),
+        ClipPickerOptions::Template(_) => return PinnedPlacement::ReplaceSlots,
+        ClipPickerOptions::NoSplit => None,
+    };
+    if let Some(length) = length {
+        *length = (*length - pinned_duration).max(0.0);
+    }
+
+    PinnedPlacement::Insert
+}
+
+/// Inserts the pinned clips at their positions between the generated clips. Only generated
+/// clips that overlap a pinned clip are dropped, so the same part of a video isn't shown
+/// twice and the compilation keeps its length otherwise.
+fn apply_pinned_clips(clips: Vec<Clip>, mut pinned_clips: Vec<PinnedClip>) -> Vec<Clip> {
+    if pinned_clips.is_empty() {
+        return clips;
+    }
+
+    pinned_clips.sort_by_key(|p| p.position);
+    let generated = clips
+        .into_iter()
+        .filter(|clip| !pinned_clips.iter().any(|p| p.clip.overlaps(clip)))
+        .collect_vec();
+    let mut output = Vec::with_capacity(generated.len() + pinned_clips.len());
+    let mut generated = generated.into_iter();
+    let mut pinned = pinned_clips.into_iter().peekable();
+
+    loop {
+        if let Some(pinned_clip) = pinned.next_if(|p| p.position <= output.len()) {
+            output.push(pinned_clip.clip);
+        } else if let Some(clip) = generated.next() {
+            output.push(clip);
+        } else {
+            break;
+        }
+    }
+    output.extend(pinned.map(|p| p.clip));
+
+    output
+}
+
+/// Puts the pinned clips into the slots of the generated clips at their positions. A pinned
+/// clip that is longer than its slot is cut to the slot's duration, if it's shorter, the
+/// rest of the slot is filled with the end of the generated clip.
+fn replace_pinned_slots(mut clips: Vec<Clip>, mut pinned_clips: Vec<PinnedClip>) -> Vec<Clip> {
+    pinned_clips.sort_by_key(|p| p.position);
+    for PinnedClip { position, mut clip } in pinned_clips {
+        if clips.is_empty() {
+            clips.push(clip);
+            continue;
+        }
+
+        let position = position.min(clips.len() - 1);
+        let slot = clips[position].clone();
+        if clip.duration() >= slot.duration() {
+            clip.range.1 = clip.range.0 + slot.duration();
+            clips[position] = clip;
+        } else {
+            let rest = Clip {
+                range: (slot.range.0 + clip.duration(), slot.range.1),
+                ..slot
+            };
+            clips[position] = clip;
+            clips.insert(position + 1, rest);
+        }
+    }
+
+    clips
+}
+
+fn pick_clips(markers: Vec<Marker>, clip_picker: ClipPickerOptions, rng: &mut StdRng) -> Vec<Clip> {
+    match clip_picker {
+        ClipPickerOptions::RoundRobin(picker_options) => {
+            let mut picker = RoundRobinClipPicker;
+            picker.pick_clips(markers, picker_options, rng)
+        }
+        ClipPickerOptions::WeightedRandom(picker_options) => {
+            let mut picker = WeightedRandomClipPicker;
+            picker.pick_clips(markers, picker_options, rng)
+        }
+        ClipPickerOptions::EqualLength(picker_options) => {
+            let mut picker = EqualLengthClipPicker;
+            picker.pick_clips(markers, picker_options, rng)
+        }
+        ClipPickerOptions::Template(template_options) => {
+            template::pick_template_clips(markers, template_options, rng)
+        }
+        ClipPickerOptions::NoSplit => markers_to_clips(markers),
+    }
+}
+
+fn sort_clips(clips: Vec<Clip>, order: ClipOrder, rng: &mut StdRng) -> Vec<Clip> {
+    match order {
+        ClipOrder::Random => {
+            let sorter = RandomClipSorter;
+            sorter.sort_clips(clips, rng)
+        }
+        ClipOrder::Scene => {
+            let sorter = SceneOrderClipSorter;
+            sorter.sort_clips(clips, rng)
+        }
+        ClipOrder::Fixed {
+            marker_title_groups,
+        } => {
+            let sorter = sort::FixedOrderClipSorter {
+                marker_title_groups: marker_title_groups
+                    .into_iter()
+                    .map(|m| m.markers.into_iter().map(|s| s.title).collect())
+                    .collect(),
+            };
+            sorter.sort_clips(clips, rng)
+        }
+        // needs the video files, see `clip_similarity::arrange_clips`
+        ClipOrder::NoOp | ClipOrder::Similarity => clips,
+    }
+}
+
 pub struct ClipsResult {
     pub clips: Vec<Clip>,
     pub beat_offsets: Option<Vec<f32>>,
+    /// The clips should be ordered by similarity, but aren't yet because their frames are
+    /// still being analyzed.
+    pub similarity_order_pending: bool,
 }
 
 pub struct ClipService {}
@@ -141,56 +298,37 @@ impl ClipService {
             info!("options have music, not sorting clips");
             options.clip_options.order = ClipOrder::NoOp;
         }
+        if let ClipPickerOptions::Template(_) = options.clip_options.clip_picker {
+            info!("options have a template, sections are sorted individually");
+            options.clip_options.order = ClipOrder::NoOp;
+        }
 
         let mut rng = create_seeded_rng(options.seed.as_deref());
+        if let Some(freshness) = &options.freshness {
+            options.markers = freshness::apply_freshness(options.markers, freshness, &mut rng);
+        }
+        if let Some(motion) = &options.motion {
+            options.markers = motion::apply_motion(options.markers, motion);
+        }
         options.markers.shuffle(&mut rng);
-        let clips = match options.clip_options.clip_picker {
-            ClipPickerOptions::RoundRobin(picker_options) => {
-                let mut picker = RoundRobinClipPicker;
-                picker.pick_clips(options.markers, picker_options, &mut rng)
-            }
-            ClipPickerOptions::WeightedRandom(picker_options) => {
-                let mut picker = WeightedRandomClipPicker;
-                picker.pick_clips(options.markers, picker_options, &mut rng)
-            }
-            ClipPickerOptions::EqualLength(picker_options) => {
-                let mut picker = EqualLengthClipPicker;
-                picker.pick_clips(options.markers, picker_options, &mut rng)
-            }
-            ClipPickerOptions::NoSplit => markers_to_clips(options.markers),
-        };
-
-        let clips = match options.clip_options.order {
-            ClipOrder::Random => {
-                let sorter = RandomClipSorter;
-                sorter.sort_clips(clips, &mut rng)
-            }
-            ClipOrder::Scene => {
-                let sorter = SceneOrderClipSorter;
-                sorter.sort_clips(clips, &mut rng)
-            }
-            ClipOrder::Fixed {
-                marker_title_groups,
-            } => {
-                let sorter = sort::FixedOrderClipSorter {
-                    marker_title_groups: marker_title_groups
-                        .into_iter()
-                        .map(|m| m.markers.into_iter().map(|s| s.title).collect())
-                        .collect(),
-                };
-                sorter.sort_clips(clips, &mut rng)
-            }
-            ClipOrder::NoOp => clips,
-        };
+        let placement =
+            reserve_pinned_clips(&mut options.clip_options.clip_picker, &options.pinned_clips);
+        let clips = pick_clips(options.markers, options.clip_options.clip_picker, &mut rng);
+        let clips = sort_clips(clips, options.clip_options.order, &mut rng);
 
         let elapsed = start.elapsed();
         info!("generated {} clips in {:?}", clips.len(), elapsed);
 
         let clips = self.concatenate_clips(clips);
+        let clips = match placement {
+            PinnedPlacement::Insert => apply_pinned_clips(clips, options.pinned_clips),
+            PinnedPlacement::ReplaceSlots => replace_pinned_slots(clips, options.pinned_clips),
+        };
 
         ClipsResult {
             clips,
             beat_offsets,
+            similarity_order_pending: false,
         }
     }
 }
@@ -218,12 +356,12 @@ mod tests {
     use float_cmp::assert_approx_eq;
     use tracing_test::traced_test;
 
-    use super::{ClipOrder, CreateClipsOptions};
+    use super::{apply_pinned_clips, replace_pinned_slots, ClipOrder, CreateClipsOptions};
     use crate::data::database::videos::VideoSource;
     use crate::helpers::random::create_seeded_rng;
     use crate::server::types::{
         Clip, ClipLengthOptions, ClipOptions, ClipPickerOptions, EqualLengthClipOptions,
-        RandomizedClipOptions, RoundRobinClipOptions,
+        PinnedClip, RandomizedClipOptions, RoundRobinClipOptions,
     };
     use crate::service::clip::sort::ClipSorter;
     use crate::service::clip::{ClipService, ClipsResult, SceneOrderClipSorter};
@@ -248,6 +386,9 @@ mod tests {
                 }),
                 order: ClipOrder::Scene,
             },
+            pinned_clips: vec![],
+            freshness: None,
+            motion: None,
         };
         let service = ClipService::new();
         let ClipsResult { clips: results, .. } = service.arrange_clips(options);
@@ -271,6 +412,9 @@ mod tests {
                 clip_picker: ClipPickerOptions::NoSplit,
                 order: ClipOrder::Scene,
             },
+            pinned_clips: vec![],
+            freshness: None,
+            motion: None,
         };
         let service = ClipService::new();
         let ClipsResult { clips: results, .. } = service.arrange_clips(options);
@@ -300,6 +444,9 @@ mod tests {
                 }),
                 order: ClipOrder::Scene,
             },
+            pinned_clips: vec![],
+            freshness: None,
+            motion: None,
         };
 
         options.normalize_video_indices();
@@ -372,6 +519,9 @@ mod tests {
                 }),
                 order: ClipOrder::Scene,
             },
+            pinned_clips: vec![],
+            freshness: None,
+            motion: None,
         };
         let service = ClipService::new();
         let ClipsResult { clips: results, .. } = service.arrange_clips(options);
@@ -400,6 +550,9 @@ mod tests {
                 }),
                 order: ClipOrder::Scene,
             },
+            pinned_clips: vec![],
+            freshness: None,
+            motion: None,
         };
         let options = options.apply_marker_loops();
         assert_eq!(options.markers.len(), 5);
@@ -469,4 +622,239 @@ mod tests {
         assert_eq!(concatenated[0].range, (0.0, 15.0));
         assert_eq!(concatenated[1].range, (15.0, 18.0));
     }
+
+    #[test]
+    #[traced_test]
+    fn test_pinned_clips_stay_in_place() {
+        let pinned = Clip {
+            index_within_marker: 0,
+            index_within_video: 0,
+            marker_id: 1,
+            range: (20.0, 25.0),
+            source: VideoSource::Folder,
+            video_id: "v1".into(),
+            marker_title: "Pinned".into(),
+        };
+        let markers = vec![
+            create_marker_video_id(1, 0.0, 30.0, 0, "v1"),
+            create_marker_video_id(2, 0.0, 30.0, 0, "v2"),
+            create_marker_video_id(3, 0.0, 30.0, 0, "v3"),
+        ];
+        let create_options = |seed: &str| CreateClipsOptions {
+            markers: markers.clone(),
+            seed: Some(seed.into()),
+            clip_options: ClipOptions {
+                clip_picker: ClipPickerOptions::RoundRobin(RoundRobinClipOptions {
+                    clip_lengths: ClipLengthOptions::Randomized(RandomizedClipOptions {
+                        base_duration: 5.0,
+                        spread: 0.5,
+                    }),
+                    length: 60.0,
+                    lenient_duration: false,
+                    min_clip_duration: None,
+                }),
+                order: ClipOrder::Random,
+            },
+            pinned_clips: vec![PinnedClip {
+                position: 2,
+                clip: pinned.clone(),
+            }],
+            freshness: None,
+            motion: None,
+        };
+        let service = ClipService::new();
+
+        let first = service.arrange_clips(create_options("first")).clips;
+        let second = service.arrange_clips(create_options("second")).clips;
+        assert_eq!(first[2], pinned);
+        assert_eq!(second[2], pinned);
+        assert_ne!(first, second);
+        for clips in [&first, &second] {
+            let overlapping = clips.iter().filter(|c| c.overlaps(&pinned)).count();
+            assert_eq!(overlapping, 1);
+        }
+
+        let again = service.arrange_clips(create_options("first")).clips;
+        assert_eq!(first, again);
+    }
+
+    #[test]
+    fn test_pinned_clips_keep_generated_clips() {
+        let clip = |video_id: &str, start: f64| Clip {
+            index_within_marker: 0,
+            index_within_video: 0,
+            marker_id: 1,
+            range: (start, start + 5.0),
+            source: VideoSource::Folder,
+            video_id: video_id.into(),
+            marker_title: "Marker".into(),
+        };
+        let generated = vec![
+            clip("v1", 0.0),
+            clip("v2", 0.0),
+            clip("v3", 0.0),
+            clip("v4", 0.0),
+        ];
+        let pinned = vec![
+            PinnedClip {
+                position: 10,
+                clip: clip("v5", 0.0),
+            },
+            PinnedClip {
+                position: 1,
+                clip: clip("v1", 20.0),
+            },
+            PinnedClip {
+                position: 3,
+                clip: clip("v3", 2.0),
+            },
+        ];
+
+        let clips = apply_pinned_clips(generated, pinned);
+        let video_ids: Vec<_> = clips.iter().map(|c| c.video_id.as_str()).collect();
+        assert_eq!(video_ids, vec!["v1", "v1", "v2", "v3", "v4", "v5"]);
+        assert_eq!(clips[1].range, (20.0, 25.0));
+        // only the generated clip of v3 overlaps a pinned clip, all others are kept
+        let total_duration: f64 = clips.iter().map(|c| c.duration()).sum();
+        assert_eq!(total_duration, 30.0);
+    }
+
+    #[test]
+    fn test_pinned_clips_keep_length() {
+        let markers = vec![
+            create_marker_video_id(1, 0.0, 30.0, 0, "v1"),
+            create_marker_video_id(2, 0.0, 30.0, 0, "v2"),
+            create_marker_video_id(3, 0.0, 30.0, 0, "v3"),
+        ];
+        let pinned = Clip {
+            index_within_marker: 0,
+            index_within_video: 0,
+            marker_id: 1,
+            range: (20.0, 28.0),
+            source: VideoSource::Folder,
+            video_id: "v1".into(),
+            marker_title: "Pinned".into(),
+        };
+        let options = CreateClipsOptions {
+            markers,
+            seed: Some("seed".into()),
+            clip_options: ClipOptions {
+                clip_picker: ClipPickerOptions::RoundRobin(RoundRobinClipOptions {
+                    clip_lengths: ClipLengthOptions::Randomized(RandomizedClipOptions {
+                        base_duration: 5.0,
+                        spread: 0.5,
+                    }),
+                    length: 60.0,
+                    lenient_duration: false,
+                    min_clip_duration: None,
+                }),
+                order: ClipOrder::Random,
+            },
+            pinned_clips: vec![PinnedClip {
+                position: 1,
+                clip: pinned.clone(),
+            }],
+            freshness: None,
+            motion: None,
+        };
+
+        let clips = ClipService::new().arrange_clips(options).clips;
+        assert_eq!(clips[1], pinned);
+        let total_duration: f64 = clips.iter().map(|c| c.duration()).sum();
+        assert!(total_duration <= 60.0 + 0.01, "{total_duration}");
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_pinned_clips_keep_beat_alignment() {
+        let string = std::fs::read_to_string("testfiles/infinite-loop.json").unwrap();
+        let options: CreateClipsOptions = serde_json::from_str(&string).unwrap();
+        let marker = &options.markers[0];
+        let pinned = Clip {
+            index_within_marker: 0,
+            index_within_video: marker.index_within_video,
+            marker_id: marker.id,
+            range: (marker.start_time, marker.start_time + 0.1),
+            source: marker.source,
+            video_id: marker.video_id.clone(),
+            marker_title: marker.title.clone(),
+        };
+        let service = ClipService::new();
+        let ends = |clips: &[Clip]| -> Vec<i64> {
+            let mut time = 0.0;
+            clips
+                .iter()
+                .map(|clip| {
+                    time += clip.duration();
+                    (time * 100.0).round() as i64
+                })
+                .collect()
+        };
+
+        let unpinned = service.arrange_clips(options.clone()).clips;
+        let pinned_options = CreateClipsOptions {
+            pinned_clips: vec![PinnedClip {
+                position: 3,
+                clip: pinned.clone(),
+            }],
+            ..options
+        };
+        let clips = service.arrange_clips(pinned_options).clips;
+
+        assert_eq!(clips[3], pinned);
+        let pinned_ends = ends(&clips);
+        // the pinned clip only adds a cut inside the slot it took over
+        assert_eq!(pinned_ends.len(), unpinned.len() + 1);
+        for end in ends(&unpinned) {
+            assert!(pinned_ends.contains(&end), "missing cut at {end}");
+        }
+    }
+
+    #[test]
+    fn test_replace_pinned_slots() {
+        let clip = |video_id: &str, start: f64, duration: f64| Clip {
+            index_within_marker: 0,
+            index_within_video: 0,
+            marker_id: 1,
+            range: (start, start + duration),
+            source: VideoSource::Folder,
+            video_id: video_id.into(),
+            marker_title: "Marker".into(),
+        };
+        let generated = vec![
+            clip("v1", 0.0, 2.0),
+            clip("v2", 0.0, 4.0),
+            clip("v3", 0.0, 4.0),
+        ];
+        let pinned = vec![
+            PinnedClip {
+                position: 1,
+                clip: clip("v4", 10.0, 6.0),
+            },
+            PinnedClip {
+                position: 2,
+                clip: clip("v5", 10.0, 1.0),
+            },
+            PinnedClip {
+                position: 10,
+                clip: clip("v6", 10.0, 1.0),
+            },
+        ];
+
+        let clips = replace_pinned_slots(generated, pinned);
+        let ranges: Vec<_> = clips
+            .iter()
+            .map(|c| (c.video_id.as_str(), c.range))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                ("v1", (0.0, 2.0)),
+                ("v4", (10.0, 14.0)),
+                ("v5", (10.0, 11.0)),
+                ("v6", (10.0, 11.0)),
+                ("v3", (2.0, 4.0)),
+            ]
+        );
+    }
 }
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::freshness::split_range;
+use crate::data::database::motion::VideoMotion;
+use crate::service::Marker;
+
+/// Static stretches shorter than this (in seconds) are not worth cutting out.
+const MIN_STATIC_DURATION: f64 = 2.0;
+
+/// Active parts of a marker shorter than this (in seconds) are dropped.
+const MIN_SEGMENT_DURATION: f64 = 1.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Motion {
+    pub static_share: f64,
+    pub videos: Vec<VideoMotion>,
+}
+
+/// Finds the near-static stretches of a video: runs of samples that are among the
+/// `static_share` least active samples of that video.
+fn static_ranges(motion: &VideoMotion, static_share: f64) -> Vec<(f64, f64)> {
+    let mut sorted = motion.scores.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let static_count = (sorted.len() as f64 * static_share.clamp(0.0, 1.0)).floor() as usize;
+    if static_count == 0 {
+        return vec![];
+    }
+    let threshold = sorted[static_count - 1];
+
+    let interval = motion.sample_interval;
+    let mut ranges: Vec<(f64, f64)> = vec![];
+    for (index, score) in motion.scores.iter().enumerate() {
+        if *score > threshold {
+            continue;
+        }
+        // the score describes the change from the previous sample to this one
+        let start = (index as f64 - 1.0).max(0.0) * interval;
+        let end = index as f64 * interval;
+        match ranges.last_mut() {
+            Some(last) if last.1 >= start => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    ranges.retain(|(start, end)| end - start >= MIN_STATIC_DURATION);
+
+    ranges
+}
+
+/// Cuts the near-static stretches out of the markers, so the clip pickers only choose from
+/// the parts with a lot of motion. Markers of videos without a motion analysis are kept as
+/// they are. Falls back to the original markers if nothing would be left otherwise.
+pub fn apply_motion(markers: Vec<Marker>, motion: &Motion) -> Vec<Marker> {
+    let static_by_video: HashMap<&str, Vec<(f64, f64)>> = motion
+        .videos
+        .iter()
+        .map(|video| {
+            (
+                video.video_id.as_str(),
+                static_ranges(video, motion.static_share),
+            )
+        })
+        .collect();
+
+    let mut result = vec![];
+    for marker in &markers {
+        let Some(static_ranges) = static_by_video.get(marker.video_id.as_str()) else {
+            result.push(marker.clone());
+            continue;
+        };
+
+        for (start, end, is_static) in
+            split_range(marker.start_time, marker.end_time, static_ranges)
+        {
+            if !is_static && end - start >= MIN_SEGMENT_DURATION {
+                result.push(Marker {
+                    start_time: start,
+                    end_time: end,
+                    ..marker.clone()
+                });
+            }
+        }
+    }
+
+    if result.is_empty() && !markers.is_empty() {
+        warn!("all markers are near-static, ignoring motion options");
+        markers
+    } else {
+        info!(
+            "kept {} marker segments after removing near-static stretches",
+            result.len()
+        );
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_test::traced_test;
+
+    use super::*;
+    use crate::data::database::videos::VideoSource;
+
+    fn marker(video_id: &str, start_time: f64, end_time: f64) -> Marker {
+        Marker {
+            id: 1,
+            start_time,
+            end_time,
+            index_within_video: 0,
+            video_id: video_id.to_string(),
+            title: "marker".to_string(),
+            loops: 1,
+            source: VideoSource::Folder,
+        }
+    }
+
+    fn motion(video_id: &str, scores: Vec<f64>) -> VideoMotion {
+        VideoMotion {
+            video_id: video_id.to_string(),
+            sample_interval: 1.0,
+            scores,
+        }
+    }
+
+    #[test]
+    fn test_static_ranges() {
+        // seconds 2 to 6 are static, seconds 7 to 8 as well but that's too short to matter
+        let video = motion("a", vec![0.3, 0.3, 0.3, 0.0, 0.0, 0.01, 0.0, 0.3, 0.0, 0.3]);
+        assert_eq!(static_ranges(&video, 0.5), vec![(2.0, 6.0)]);
+        assert_eq!(static_ranges(&video, 0.0), vec![]);
+    }
+
+    #[traced_test]
+    #[test]
+    fn test_apply_motion() {
+        let markers = vec![marker("a", 0.0, 10.0), marker("b", 0.0, 10.0)];
+        let motion = Motion {
+            static_share: 0.4,
+            videos: vec![motion(
+                "a",
+                vec![0.3, 0.3, 0.3, 0.0, 0.0, 0.01, 0.0, 0.3, 0.3, 0.3],
+            )],
+        };
+
+        let result = apply_motion(markers, &motion);
+        let ranges: Vec<_> = result
+            .iter()
+            .map(|m| (m.video_id.as_str(), m.start_time, m.end_time))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![("a", 0.0, 2.0), ("a", 6.0, 10.0), ("b", 0.0, 10.0)]
+        );
+    }
+
+    #[traced_test]
+    #[test]
+    fn test_fall_back_when_everything_is_static() {
+        let markers = vec![marker("a", 2.0, 6.0)];
+        let motion = Motion {
+            static_share: 0.4,
+            videos: vec![motion(
+                "a",
+                vec![0.3, 0.3, 0.3, 0.0, 0.0, 0.01, 0.0, 0.3, 0.3, 0.3],
+            )],
+        };
+
+        let result = apply_motion(markers.clone(), &motion);
+        assert_eq!(result, markers);
+    }
+}
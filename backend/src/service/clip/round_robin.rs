@@ -3,7 +3,7 @@ use tracing::{debug, info};
 
 use super::length_picker::ClipLengthPicker;
 use super::ClipPicker;
-use crate::server::types::{Clip, ClipLengthOptions, RoundRobinClipOptions};
+use crate::server::types::{Clip, RoundRobinClipOptions};
 use crate::service::clip::state::{MarkerState, MarkerStateInfo};
 use crate::service::clip::trim_clips;
 use crate::service::Marker;
@@ -20,12 +20,10 @@ impl ClipPicker for RoundRobinClipPicker {
         rng: &mut StdRng,
     ) -> Vec<Clip> {
         info!("using RoundRobinClipPicker to make clips");
-        let song_duration = match &options.clip_lengths {
-            ClipLengthOptions::Songs(options) => {
-                Some(options.songs.iter().map(|s| s.length as f64).sum())
-            }
-            _ => None,
-        };
+        let song_duration = options
+            .clip_lengths
+            .songs()
+            .map(|songs| songs.iter().map(|s| s.length as f64).sum());
         if !options.lenient_duration {
             let marker_duration = markers.iter().map(|m| m.duration()).sum::<f64>();
             assert!(
@@ -39,7 +37,7 @@ impl ClipPicker for RoundRobinClipPicker {
         let max_duration = options.length;
         let mut clips = vec![];
         let mut marker_idx = 0;
-        let has_music = matches!(options.clip_lengths, ClipLengthOptions::Songs(_));
+        let has_music = options.clip_lengths.songs().is_some();
         let min_duration = options.min_clip_duration.unwrap_or(1.5);
         let clip_lengths =
             ClipLengthPicker::new(options.clip_lengths, max_duration, min_duration, rng);
@@ -127,7 +125,7 @@ mod test {
         let options = RoundRobinClipOptions {
             length: song_duration,
             clip_lengths: ClipLengthOptions::Songs(SongClipOptions {
-                beats_per_measure: 4,
+                beats_per_measure: Some(4),
                 cut_after_measures: MeasureCount::Fixed { count: 4 },
                 songs,
             }),
@@ -147,10 +145,16 @@ mod test {
             Beats {
                 length: 10.0,
                 offsets: (0..10).map(|n| n as f32).collect(),
+                downbeats: None,
+                beats_per_measure: None,
+                energy: None,
             },
             Beats {
                 length: 10.0,
                 offsets: (0..10).map(|n| n as f32).collect(),
+                downbeats: None,
+                beats_per_measure: None,
+                energy: None,
             },
         ];
         let song_duration = songs.iter().map(|s| s.length as f64).sum();
@@ -158,7 +162,7 @@ mod test {
         let options = RoundRobinClipOptions {
             length: song_duration,
             clip_lengths: ClipLengthOptions::Songs(SongClipOptions {
-                beats_per_measure: 4,
+                beats_per_measure: Some(4),
                 cut_after_measures: MeasureCount::Fixed { count: 4 },
                 songs,
             }),
@@ -0,0 +1,172 @@
+use rand::rngs::StdRng;
+use tracing::{info, warn};
+
+use super::freshness::{self, Freshness};
+use super::{pick_clips, sort_clips};
+use crate::data::database::compilations::UsedRange;
+use crate::server::types::{Clip, FreshnessMode, TemplateClipOptions, TemplateSection};
+use crate::service::Marker;
+
+fn section_markers(markers: &[Marker], section: &TemplateSection) -> Vec<Marker> {
+    markers
+        .iter()
+        .filter(|marker| {
+            section.marker_titles.is_empty()
+                || section
+                    .marker_titles
+                    .iter()
+                    .any(|title| title.eq_ignore_ascii_case(&marker.title))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Picks and sorts the clips of every section with its own settings and puts them one
+/// after another. Parts of the markers that were already used by an earlier section are
+/// avoided, so repeated sections don't show the same clips again.
+pub fn pick_template_clips(
+    markers: Vec<Marker>,
+    options: TemplateClipOptions,
+    rng: &mut StdRng,
+) -> Vec<Clip> {
+    let sections: Vec<&TemplateSection> = match &options.sequence {
+        Some(names) => names
+            .iter()
+            .filter_map(|name| {
+                let section = options.sections.iter().find(|s| &s.name == name);
+                if section.is_none() {
+                    warn!("no section named '{name}' found in template, skipping");
+                }
+                section
+            })
+            .collect(),
+        None => options.sections.iter().collect(),
+    };
+
+    let mut clips = vec![];
+    let mut used_ranges = vec![];
+    for section in sections {
+        let mut markers = section_markers(&markers, section);
+        if markers.is_empty() {
+            warn!("no markers found for section '{}', skipping", section.name);
+            continue;
+        }
+        if !used_ranges.is_empty() {
+            let freshness = Freshness {
+                mode: FreshnessMode::Exclude,
+                used_ranges: used_ranges.clone(),
+            };
+            markers = freshness::apply_freshness(markers, &freshness, rng);
+        }
+
+        let section_clips = pick_clips(markers, section.clip_picker.clone(), rng);
+        let section_clips = sort_clips(section_clips, section.order.clone(), rng);
+        info!(
+            "picked {} clips for section '{}'",
+            section_clips.len(),
+            section.name
+        );
+        used_ranges.extend(section_clips.iter().map(|clip| UsedRange {
+            video_id: clip.video_id.clone(),
+            start_time: clip.range.0,
+            end_time: clip.range.1,
+        }));
+        clips.extend(section_clips);
+    }
+
+    clips
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::assert_approx_eq;
+    use tracing_test::traced_test;
+
+    use super::*;
+    use crate::helpers::random::create_seeded_rng;
+    use crate::server::types::{
+        ClipLengthOptions, ClipOrder, ClipPickerOptions, RandomizedClipOptions,
+        RoundRobinClipOptions,
+    };
+    use crate::service::fixtures::create_marker_video_id;
+
+    fn marker(id: i64, title: &str, video_id: &str) -> Marker {
+        Marker {
+            title: title.to_string(),
+            ..create_marker_video_id(id, 0.0, 60.0, 0, video_id)
+        }
+    }
+
+    fn section(name: &str, marker_titles: &[&str], length: f64) -> TemplateSection {
+        TemplateSection {
+            name: name.to_string(),
+            marker_titles: marker_titles.iter().map(|t| t.to_string()).collect(),
+            clip_picker: ClipPickerOptions::RoundRobin(RoundRobinClipOptions {
+                length,
+                clip_lengths: ClipLengthOptions::Randomized(RandomizedClipOptions {
+                    base_duration: 5.0,
+                    spread: 0.5,
+                }),
+                lenient_duration: false,
+                min_clip_duration: None,
+            }),
+            order: ClipOrder::Random,
+        }
+    }
+
+    #[traced_test]
+    #[test]
+    fn test_sections_in_order() {
+        let mut rng = create_seeded_rng(None);
+        let markers = vec![
+            marker(1, "Intro", "v1"),
+            marker(2, "Main", "v2"),
+            marker(3, "main", "v3"),
+        ];
+        let options = TemplateClipOptions {
+            sections: vec![
+                section("intro", &["intro"], 10.0),
+                section("main", &["Main"], 30.0),
+            ],
+            sequence: None,
+        };
+
+        let clips = pick_template_clips(markers, options, &mut rng);
+        let intro_duration: f64 = clips
+            .iter()
+            .take_while(|c| c.marker_id == 1)
+            .map(|c| c.duration())
+            .sum();
+        let total_duration: f64 = clips.iter().map(|c| c.duration()).sum();
+        assert_approx_eq!(f64, intro_duration, 10.0, epsilon = 0.01);
+        assert_approx_eq!(f64, total_duration, 40.0, epsilon = 0.01);
+        assert!(clips
+            .iter()
+            .skip_while(|c| c.marker_id == 1)
+            .all(|c| c.marker_id != 1));
+    }
+
+    #[traced_test]
+    #[test]
+    fn test_repeated_sections_use_new_ranges() {
+        let mut rng = create_seeded_rng(None);
+        let markers = vec![marker(1, "Main", "v1"), marker(2, "Break", "v2")];
+        let options = TemplateClipOptions {
+            sections: vec![section("main", &["Main"], 10.0), section("break", &[], 5.0)],
+            sequence: Some(vec![
+                "main".to_string(),
+                "break".to_string(),
+                "main".to_string(),
+                "missing".to_string(),
+            ]),
+        };
+
+        let clips = pick_template_clips(markers, options, &mut rng);
+        let total_duration: f64 = clips.iter().map(|c| c.duration()).sum();
+        assert_approx_eq!(f64, total_duration, 25.0, epsilon = 0.01);
+        for (index, clip) in clips.iter().enumerate() {
+            let overlapping = clips[index + 1..].iter().filter(|c| c.overlaps(clip));
+            assert_eq!(overlapping.count(), 0, "clip {clip:?} is used twice");
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+
+use crate::data::database::clip_lists::ClipList;
+use crate::data::database::markers::ListMarkersFilter;
+use crate::data::database::Database;
+use crate::server::error::ValidationErrors;
+use crate::server::types::{Clip, ClipListEdit};
+use crate::Result;
+
+/// Maximum number of previous versions kept per clip list.
+const MAX_HISTORY: usize = 50;
+
+/// Tolerance when comparing clip ranges against marker and video bounds.
+const EPSILON: f64 = 0.001;
+
+/// Applies a single edit to a list of clips, returning the edited list.
+pub fn apply_edit(
+    clips: &[Clip],
+    edit: &ClipListEdit,
+) -> std::result::Result<Vec<Clip>, ValidationErrors> {
+    let mut clips = clips.to_vec();
+    let check_index = |index: usize| {
+        if index < clips.len() {
+            Ok(index)
+        } else {
+            Err(HashMap::from([("index", "Clip index is out of bounds")]))
+        }
+    };
+
+    match *edit {
+        ClipListEdit::Move { from, to } => {
+            let from = check_index(from)?;
+            let to = check_index(to)?;
+            let clip = clips.remove(from);
+            clips.insert(to, clip);
+        }
+        ClipListEdit::Trim { index, start, end } => {
+            let index = check_index(index)?;
+            clips[index].range = (start, end);
+        }
+        ClipListEdit::Split { index, time } => {
+            let index = check_index(index)?;
+            let (start, end) = clips[index].range;
+            if time <= start || time >= end {
+                return Err(HashMap::from([(
+                    "time",
+                    "Split time must be inside the clip",
+                )]));
+            }
+            let mut second = clips[index].clone();
+            clips[index].range = (start, time);
+            second.range = (time, end);
+            second.index_within_marker += 1;
+            // make room for the new clip among the later clips of the same marker
+            for clip in &mut clips {
+                if clip.marker_id == second.marker_id
+                    && clip.index_within_marker >= second.index_within_marker
+                {
+                    clip.index_within_marker += 1;
+                }
+            }
+            clips.insert(index + 1, second);
+        }
+        ClipListEdit::Duplicate { index } => {
+            let index = check_index(index)?;
+            let clip = clips[index].clone();
+            clips.insert(index + 1, clip);
+        }
+        ClipListEdit::Delete { index } => {
+            let index = check_index(index)?;
+            clips.remove(index);
+        }
+    }
+
+    Ok(clips)
+}
+
+pub struct ClipEditorService {
+    db: Database,
+}
+
+impl ClipEditorService {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    /// Checks that every clip lies within its marker and within the duration of its video.
+    pub async fn validate_clips(&self, clips: &[Clip]) -> Result<ValidationErrors> {
+        let mut errors = HashMap::new();
+        let mut video_ids: Vec<_> = clips.iter().map(|c| c.video_id.as_str()).collect();
+        video_ids.sort();
+        video_ids.dedup();
+        if video_ids.is_empty() {
+            return Ok(errors);
+        }
+
+        let markers: HashMap<_, _> = self
+            .db
+            .markers
+            .list_markers(
+                Some(ListMarkersFilter::VideoIds(
+                    video_ids.iter().map(|id| id.to_string()).collect(),
+                )),
+                None,
+            )
+            .await?
+            .into_iter()
+            .filter_map(|m| m.rowid.map(|id| (id, m)))
+            .collect();
+        let video_durations: HashMap<_, _> = self
+            .db
+            .videos
+            .get_videos_by_ids(&video_ids)
+            .await?
+            .into_iter()
+            .map(|v| (v.id, v.duration))
+            .collect();
+
+        for clip in clips {
+            let (start, end) = clip.range;
+            if end <= start {
+                errors.insert("range", "Clip end must be after start");
+            }
+            match markers.get(&clip.marker_id) {
+                Some(marker) if marker.video_id == clip.video_id => {
+                    if start < marker.start_time - EPSILON || end > marker.end_time + EPSILON {
+                        errors.insert("range", "Clip must be within its marker");
+                    }
+                }
+                _ => {
+                    errors.insert("markerId", "Clip must belong to a marker of its video");
+                }
+            }
+            match video_durations.get(&clip.video_id) {
+                Some(duration) if *duration > 0.0 && end > duration + EPSILON => {
+                    errors.insert("range", "Clip must not exceed the video duration");
+                }
+                Some(_) => {}
+                None => {
+                    errors.insert("videoId", "Clip must belong to an existing video");
+                }
+            }
+        }
+
+        Ok(errors)
+    }
+
+    pub async fn get_clip_list(&self, id: &str) -> Result<Option<ClipList>> {
+        self.db.clip_lists.get_clip_list(id).await
+    }
+
+    pub async fn create_clip_list(&self, clips: Vec<Clip>) -> Result<ClipList> {
+        self.db.clip_lists.create_clip_list(clips).await
+    }
+
+    /// Replaces the clips of the list and pushes the previous version onto the undo history.
+    pub async fn update_clips(&self, mut list: ClipList, clips: Vec<Clip>) -> Result<ClipList> {
+        let previous = std::mem::replace(&mut list.clips, clips);
+        list.history.push(previous);
+        if list.history.len() > MAX_HISTORY {
+            let excess = list.history.len() - MAX_HISTORY;
+            list.history.drain(..excess);
+        }
+        self.db
+            .clip_lists
+            .update_clip_list(&list.id, &list.clips, &list.history)
+            .await?;
+        Ok(list)
+    }
+
+    /// Restores the previous version of the clip list. Returns `None` if there is nothing to undo.
+    pub async fn undo(&self, mut list: ClipList) -> Result<Option<ClipList>> {
+        match list.history.pop() {
+            Some(previous) => {
+                list.clips = previous;
+                self.db
+                    .clip_lists
+                    .update_clip_list(&list.id, &list.clips, &list.history)
+                    .await?;
+                Ok(Some(list))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub async fn delete_clip_list(&self, id: &str) -> Result<()> {
+        self.db.clip_lists.delete_clip_list(id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+    use tracing_test::traced_test;
+
+    use super::*;
+    use crate::data::database::videos::VideoSource;
+    use crate::service::fixtures::{persist_marker, persist_video};
+
+    fn clip(video_id: &str, marker_id: i64, range: (f64, f64)) -> Clip {
+        Clip {
+            source: VideoSource::Folder,
+            video_id: video_id.to_string(),
+            marker_id,
+            range,
+            index_within_video: 0,
+            index_within_marker: 0,
+            marker_title: "marker".to_string(),
+        }
+    }
+
+    #[traced_test]
+    #[test]
+    fn test_apply_edits() {
+        let clips = vec![clip("a", 1, (0.0, 10.0)), clip("b", 2, (5.0, 15.0))];
+
+        let moved = apply_edit(&clips, &ClipListEdit::Move { from: 0, to: 1 }).unwrap();
+        assert_eq!(moved[0].video_id, "b");
+        assert_eq!(moved[1].video_id, "a");
+
+        let split = apply_edit(
+            &clips,
+            &ClipListEdit::Split {
+                index: 1,
+                time: 8.0,
+            },
+        )
+        .unwrap();
+        assert_eq!(split.len(), 3);
+        assert_eq!(split[1].range, (5.0, 8.0));
+        assert_eq!(split[2].range, (8.0, 15.0));
+
+        // only the later clips of the split clip's marker are renumbered
+        let mut marker_clips = vec![
+            clip("a", 1, (0.0, 10.0)),
+            clip("b", 2, (0.0, 5.0)),
+            clip("a", 1, (20.0, 30.0)),
+            clip("b", 2, (5.0, 10.0)),
+        ];
+        marker_clips[2].index_within_marker = 1;
+        marker_clips[3].index_within_marker = 1;
+        let split = apply_edit(
+            &marker_clips,
+            &ClipListEdit::Split {
+                index: 0,
+                time: 5.0,
+            },
+        )
+        .unwrap();
+        let indices: Vec<_> = split
+            .iter()
+            .map(|c| (c.marker_id, c.index_within_marker))
+            .collect();
+        assert_eq!(indices, vec![(1, 0), (1, 1), (2, 0), (1, 2), (2, 1)]);
+
+        let duplicated = apply_edit(&clips, &ClipListEdit::Duplicate { index: 0 }).unwrap();
+        assert_eq!(duplicated.len(), 3);
+        assert_eq!(duplicated[0], duplicated[1]);
+
+        let deleted = apply_edit(&clips, &ClipListEdit::Delete { index: 0 }).unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].video_id, "b");
+
+        let errors = apply_edit(&clips, &ClipListEdit::Delete { index: 2 }).unwrap_err();
+        assert!(errors.contains_key("index"));
+        let errors = apply_edit(
+            &clips,
+            &ClipListEdit::Split {
+                index: 0,
+                time: 10.0,
+            },
+        )
+        .unwrap_err();
+        assert!(errors.contains_key("time"));
+    }
+
+    #[sqlx::test]
+    #[traced_test]
+    async fn test_edit_and_undo(pool: SqlitePool) {
+        let database = Database::with_pool(pool);
+        let video = persist_video(&database).await.unwrap();
+        let marker = persist_marker(&database, &video.id, 0, 10.0, 20.0, false)
+            .await
+            .unwrap();
+        let service = ClipEditorService::new(database);
+
+        let clips = vec![clip(&video.id, marker.rowid.unwrap(), (10.0, 20.0))];
+        assert!(service.validate_clips(&clips).await.unwrap().is_empty());
+        let list = service.create_clip_list(clips.clone()).await.unwrap();
+
+        let trimmed = apply_edit(
+            &list.clips,
+            &ClipListEdit::Trim {
+                index: 0,
+                start: 12.0,
+                end: 18.0,
+            },
+        )
+        .unwrap();
+        assert!(service.validate_clips(&trimmed).await.unwrap().is_empty());
+        let list = service.update_clips(list, trimmed).await.unwrap();
+
+        let stored = service.get_clip_list(&list.id).await.unwrap().unwrap();
+        assert_eq!(stored.clips[0].range, (12.0, 18.0));
+        assert_eq!(stored.history.len(), 1);
+
+        let out_of_marker = apply_edit(
+            &stored.clips,
+            &ClipListEdit::Trim {
+                index: 0,
+                start: 5.0,
+                end: 18.0,
+            },
+        )
+        .unwrap();
+        let errors = service.validate_clips(&out_of_marker).await.unwrap();
+        assert!(errors.contains_key("range"));
+
+        let undone = service.undo(stored).await.unwrap().unwrap();
+        assert_eq!(undone.clips, clips);
+        assert!(undone.history.is_empty());
+        assert!(service.undo(undone).await.unwrap().is_none());
+    }
+}
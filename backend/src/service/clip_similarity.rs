@@ -0,0 +1,348 @@
+use std::collections::{HashMap, HashSet};
+
+use color_eyre::eyre::bail;
+use tracing::{info, warn};
+
+use super::commands::ffmpeg::{Ffmpeg, FfmpegLocation};
+use crate::data::database::Database;
+use crate::helpers::parallelize;
+use crate::server::types::{Clip, ClipOrder};
+use crate::service::clip::{ClipService, ClipsResult, CreateClipsOptions};
+use crate::service::streams::{LocalVideoSource, StreamUrlService};
+use crate::Result;
+
+/// Width and height of the downscaled frames that are hashed.
+const HASH_SIZE: usize = 8;
+
+/// Distance used when the signature of a clip couldn't be computed.
+const UNKNOWN_DISTANCE: u32 = (HASH_SIZE * HASH_SIZE / 2) as u32;
+
+/// Frames are hashed on a fixed grid (in milliseconds), so all clips of a video share
+/// their hashes, whatever their ranges are.
+const FRAME_INTERVAL_MILLIS: u32 = 500;
+
+/// Maximum number of missing frame hashes that are computed while sorting. If more are
+/// missing, they're computed in the background and the clips aren't sorted this time.
+const MAX_INLINE_FRAMES: usize = 100;
+
+/// Average hashes of the first and the last frame of a clip.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipSignature {
+    pub first_frame: u64,
+    pub last_frame: u64,
+}
+
+/// Grid times of the first and the last frame of the clip, both inside the clip's range
+/// (unless the clip is shorter than the grid interval).
+fn clip_frames(clip: &Clip) -> (u32, u32) {
+    let (start, end) = clip.range;
+    let start = (start.max(0.0) * 1000.0).round() as u32;
+    let end = (end.max(0.0) * 1000.0).round() as u32;
+    let first = start.div_ceil(FRAME_INTERVAL_MILLIS) * FRAME_INTERVAL_MILLIS;
+    let last = end.saturating_sub(100) / FRAME_INTERVAL_MILLIS * FRAME_INTERVAL_MILLIS;
+    (first, last.max(first))
+}
+
+/// Computes the average hash of a grayscale image: one bit per pixel, set if the pixel
+/// is brighter than the mean.
+fn average_hash(pixels: &[u8]) -> u64 {
+    let mean = pixels.iter().map(|p| *p as u32).sum::<u32>() / pixels.len().max(1) as u32;
+    pixels
+        .iter()
+        .enumerate()
+        .filter(|(_, pixel)| **pixel as u32 > mean)
+        .fold(0, |hash, (index, _)| hash | (1 << index))
+}
+
+/// Extracts the frame at the given time and returns its average hash. Frames that look alike
+/// have hashes with a small hamming distance.
+pub async fn frame_hash(ffmpeg_location: &FfmpegLocation, url: &str, time: f64) -> Result<u64> {
+    let pixels = Ffmpeg::new(ffmpeg_location, "-")
+        .start(time)
+        .input(url)
+        .format("rawvideo")
+        .video_filter(format!("scale={HASH_SIZE}:{HASH_SIZE},format=gray"))
+        .extra_arg("-frames:v")
+        .extra_arg("1")
+        .stdout()
+        .await?;
+    if pixels.len() < HASH_SIZE * HASH_SIZE {
+        bail!("could not extract frame at {time} from {url}");
+    }
+
+    Ok(average_hash(&pixels[..HASH_SIZE * HASH_SIZE]))
+}
+
+fn distance(from: Option<&ClipSignature>, to: Option<&ClipSignature>) -> u32 {
+    match (from, to) {
+        (Some(from), Some(to)) => (from.last_frame ^ to.first_frame).count_ones(),
+        _ => UNKNOWN_DISTANCE,
+    }
+}
+
+/// Greedily orders the clips so that every clip starts with a frame that looks as similar as
+/// possible to the last frame of the clip before it. Returns the new order as indices.
+/// Clips at the `pinned` positions stay where they are.
+fn order_by_similarity(
+    signatures: &[Option<ClipSignature>],
+    pinned: &HashSet<usize>,
+) -> Vec<usize> {
+    let mut free: Vec<usize> = (0..signatures.len())
+        .filter(|index| !pinned.contains(index))
+        .collect();
+    let mut order: Vec<usize> = Vec::with_capacity(signatures.len());
+
+    for position in 0..signatures.len() {
+        if pinned.contains(&position) {
+            order.push(position);
+            continue;
+        }
+
+        let next = match order.last() {
+            None => 0,
+            Some(&previous) => free
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, candidate)| {
+                    distance(
+                        signatures[previous].as_ref(),
+                        signatures[**candidate].as_ref(),
+                    )
+                })
+                .map(|(index, _)| index)
+                .expect("free clips must not be empty"),
+        };
+        order.push(free.remove(next));
+    }
+
+    order
+}
+
+/// Arranges the clips with [`ClipService::arrange_clips`]. If the clips should be ordered by
+/// visual similarity, they're sorted afterwards, since that needs to look at the video files.
+pub async fn arrange_clips(
+    database: &Database,
+    ffmpeg_location: &FfmpegLocation,
+    options: CreateClipsOptions,
+) -> Result<ClipsResult> {
+    let sort_by_similarity = matches!(options.clip_options.order, ClipOrder::Similarity)
+        && !options.clip_options.clip_picker.has_music();
+    let pinned_clips: Vec<_> = options
+        .pinned_clips
+        .iter()
+        .map(|p| p.clip.clone())
+        .collect();
+    let mut result = ClipService::new().arrange_clips(options);
+
+    if sort_by_similarity {
+        let sorter = SimilaritySorter::new(database.clone(), ffmpeg_location.clone());
+        match sorter.sort_clips(&result.clips, &pinned_clips).await? {
+            Some(clips) => result.clips = clips,
+            None => result.similarity_order_pending = true,
+        }
+    }
+
+    Ok(result)
+}
+
+#[derive(Clone)]
+pub struct SimilaritySorter {
+    database: Database,
+    ffmpeg_location: FfmpegLocation,
+}
+
+impl SimilaritySorter {
+    pub fn new(database: Database, ffmpeg_location: FfmpegLocation) -> Self {
+        Self {
+            database,
+            ffmpeg_location,
+        }
+    }
+
+    /// Computes the hash of the frame and caches it.
+    async fn compute_hash(&self, video_id: &str, time_millis: u32, url: &str) -> Result<u64> {
+        let hash = frame_hash(&self.ffmpeg_location, url, time_millis as f64 / 1000.0).await?;
+        self.database
+            .frame_hashes
+            .set_hash(video_id, time_millis, hash)
+            .await?;
+
+        Ok(hash)
+    }
+
+    /// Computes the hashes of the frames in parallel. The results are in the same order
+    /// as the frames, failed ones are `None`.
+    async fn compute_hashes(&self, frames: Vec<(String, u32, String)>) -> Vec<Option<u64>> {
+        let futures = frames.into_iter().enumerate().map(|(index, frame)| {
+            let sorter = self.clone();
+            async move {
+                let (video_id, time_millis, url) = frame;
+                let result = sorter.compute_hash(&video_id, time_millis, &url).await;
+                if let Err(e) = &result {
+                    warn!("failed to hash frame at {time_millis}ms of video {video_id}: {e}");
+                }
+                (index, result.ok())
+            }
+        });
+        let mut results = parallelize(futures).await;
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, hash)| hash).collect()
+    }
+
+    /// Sorts the clips by visual similarity. If too many frames haven't been hashed yet,
+    /// they're hashed in the background and `None` is returned, so the caller can tell the
+    /// user that the clips aren't sorted yet.
+    pub async fn sort_clips(
+        &self,
+        clips: &[Clip],
+        pinned_clips: &[Clip],
+    ) -> Result<Option<Vec<Clip>>> {
+        info!("sorting {} clips by visual similarity", clips.len());
+        let mut video_ids: Vec<_> = clips.iter().map(|c| c.video_id.as_str()).collect();
+        video_ids.sort();
+        video_ids.dedup();
+        let streams: HashMap<_, _> = StreamUrlService::new(self.database.clone())
+            .await
+            .get_video_streams(&video_ids, LocalVideoSource::File)
+            .await?;
+
+        let mut hashes: HashMap<(&str, u32), Option<u64>> = HashMap::new();
+        let mut missing = vec![];
+        for clip in clips {
+            let (first, last) = clip_frames(clip);
+            for time_millis in [first, last] {
+                let key = (clip.video_id.as_str(), time_millis);
+                if hashes.contains_key(&key) {
+                    continue;
+                }
+                let hash = self
+                    .database
+                    .frame_hashes
+                    .get_hash(&clip.video_id, time_millis)
+                    .await?;
+                if hash.is_none() {
+                    if let Some(url) = streams.get(&clip.video_id) {
+                        missing.push((clip.video_id.clone(), time_millis, url.clone()));
+                    }
+                }
+                hashes.insert(key, hash);
+            }
+        }
+
+        if missing.len() > MAX_INLINE_FRAMES {
+            info!(
+                "{} frames have no hash yet, computing them in the background",
+                missing.len()
+            );
+            let sorter = self.clone();
+            tokio::spawn(async move {
+                let count = missing.len();
+                sorter.compute_hashes(missing).await;
+                info!("computed {count} frame hashes");
+            });
+            return Ok(None);
+        }
+        let keys: Vec<_> = missing
+            .iter()
+            .map(|(video_id, time_millis, _)| (video_id.clone(), *time_millis))
+            .collect();
+        let computed = self.compute_hashes(missing).await;
+        for ((video_id, time_millis), hash) in keys.iter().zip(computed) {
+            hashes.insert((video_id.as_str(), *time_millis), hash);
+        }
+
+        let signatures: Vec<_> = clips
+            .iter()
+            .map(|clip| {
+                let (first, last) = clip_frames(clip);
+                let video_id = clip.video_id.as_str();
+                Some(ClipSignature {
+                    first_frame: hashes[&(video_id, first)]?,
+                    last_frame: hashes[&(video_id, last)]?,
+                })
+            })
+            .collect();
+        let pinned: HashSet<_> = clips
+            .iter()
+            .enumerate()
+            .filter(|(_, clip)| pinned_clips.contains(clip))
+            .map(|(index, _)| index)
+            .collect();
+        let order = order_by_similarity(&signatures, &pinned);
+
+        Ok(Some(
+            order
+                .into_iter()
+                .map(|index| clips[index].clone())
+                .collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signature(first_frame: u64, last_frame: u64) -> Option<ClipSignature> {
+        Some(ClipSignature {
+            first_frame,
+            last_frame,
+        })
+    }
+
+    #[test]
+    fn test_average_hash() {
+        let mut pixels = vec![10; 64];
+        pixels[0] = 200;
+        pixels[63] = 200;
+        assert_eq!(average_hash(&pixels), 1 | (1 << 63));
+        assert_eq!(average_hash(&[50; 64]), 0);
+    }
+
+    #[test]
+    fn test_clip_frames() {
+        let mut clip = Clip {
+            source: crate::data::database::videos::VideoSource::Folder,
+            video_id: "a".to_string(),
+            marker_id: 1,
+            range: (1.2, 4.0),
+            index_within_video: 0,
+            index_within_marker: 0,
+            marker_title: "marker".to_string(),
+        };
+        assert_eq!(clip_frames(&clip), (1500, 3500));
+
+        // clips with slightly different ranges share their frames
+        clip.range = (1.4, 4.3);
+        assert_eq!(clip_frames(&clip), (1500, 4000));
+
+        clip.range = (2.1, 2.3);
+        assert_eq!(clip_frames(&clip), (2500, 2500));
+    }
+
+    #[test]
+    fn test_order_by_similarity() {
+        let signatures = vec![
+            signature(0, 0b1111),
+            signature(0b1000_0000, 0b0011),
+            signature(0b1110, 0b0001),
+            signature(0b0011, 0b1111_0000),
+        ];
+        // 0 ends with 1111 -> 2 starts with 1110, 2 ends with 0001 -> 3 starts with 0011
+        let order = order_by_similarity(&signatures, &HashSet::new());
+        assert_eq!(order, vec![0, 2, 3, 1]);
+    }
+
+    #[test]
+    fn test_order_keeps_pinned_positions() {
+        let signatures = vec![
+            signature(0, 0b1111),
+            signature(0b1000_0000, 0b0011),
+            signature(0b1110, 0b0001),
+            signature(0b0011, 0b1111_0000),
+        ];
+        let order = order_by_similarity(&signatures, &HashSet::from([1]));
+        assert_eq!(order[1], 1);
+        assert_eq!(order, vec![0, 1, 3, 2]);
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+
+use crate::server::types::{
+    Clip, ClipStatistics, EncodingEffort, RenderEstimate, RenderSettings, ScreenTimeShare,
+    VideoCodec, VideoDto, VideoQuality, VideoScreenTimeShare,
+};
+
+/// Pixel rate (1080p at 30 fps) the bitrates and encoding speeds below are based on.
+const REFERENCE_PIXEL_RATE: f64 = 1920.0 * 1080.0 * 30.0;
+
+/// Bitrate of the AAC audio track in bits per second.
+const AUDIO_BITRATE: f64 = 128_000.0;
+
+/// Rough average video bitrate in bits per second at the reference pixel rate.
+fn reference_bitrate(codec: VideoCodec, quality: VideoQuality) -> f64 {
+    let mbit = match codec {
+        VideoCodec::H264 => match quality {
+            VideoQuality::Low => 3.0,
+            VideoQuality::Medium => 5.0,
+            VideoQuality::High => 9.0,
+            VideoQuality::Lossless => 15.0,
+        },
+        VideoCodec::H265 => match quality {
+            VideoQuality::Low => 1.5,
+            VideoQuality::Medium => 3.0,
+            VideoQuality::High => 5.0,
+            VideoQuality::Lossless => 10.0,
+        },
+        VideoCodec::Av1 => match quality {
+            VideoQuality::Low => 1.2,
+            VideoQuality::Medium => 2.5,
+            VideoQuality::High => 4.0,
+            VideoQuality::Lossless => 8.0,
+        },
+    };
+    mbit * 1_000_000.0
+}
+
+/// Rough encoding speed as a multiple of realtime at the reference pixel rate.
+fn reference_speed(codec: VideoCodec, effort: EncodingEffort) -> f64 {
+    match codec {
+        VideoCodec::H264 => match effort {
+            EncodingEffort::Low => 4.0,
+            EncodingEffort::Medium => 2.0,
+            EncodingEffort::High => 1.0,
+        },
+        VideoCodec::H265 => match effort {
+            EncodingEffort::Low => 1.5,
+            EncodingEffort::Medium => 0.6,
+            EncodingEffort::High => 0.25,
+        },
+        // the SVT-AV1 presets used for the effort levels get faster with higher effort
+        VideoCodec::Av1 => match effort {
+            EncodingEffort::Low => 0.3,
+            EncodingEffort::Medium => 1.5,
+            EncodingEffort::High => 4.0,
+        },
+    }
+}
+
+/// Estimates the file size and render time of a compilation with the given duration.
+pub fn estimate_render(duration: f64, settings: &RenderSettings) -> RenderEstimate {
+    let (width, height) = settings.output_resolution;
+    let pixel_rate_factor =
+        (width as f64 * height as f64 * settings.output_fps as f64) / REFERENCE_PIXEL_RATE;
+    let video_bitrate =
+        reference_bitrate(settings.video_codec, settings.video_quality) * pixel_rate_factor;
+    let file_size_bytes = ((video_bitrate + AUDIO_BITRATE) * duration / 8.0).round() as u64;
+    let speed = reference_speed(settings.video_codec, settings.encoding_effort)
+        / pixel_rate_factor.max(0.01);
+
+    RenderEstimate {
+        file_size_bytes,
+        render_time_seconds: duration / speed,
+    }
+}
+
+fn to_shares(durations: HashMap<String, f64>, total: f64) -> Vec<ScreenTimeShare> {
+    let mut shares: Vec<_> = durations
+        .into_iter()
+        .map(|(name, duration)| ScreenTimeShare {
+            name,
+            duration,
+            share: if total > 0.0 { duration / total } else { 0.0 },
+        })
+        .collect();
+    shares.sort_by(|a, b| {
+        b.duration
+            .total_cmp(&a.duration)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    shares
+}
+
+fn to_video_shares(
+    durations: HashMap<&str, f64>,
+    videos: &HashMap<&str, &VideoDto>,
+    total: f64,
+) -> Vec<VideoScreenTimeShare> {
+    let mut shares: Vec<_> = durations
+        .into_iter()
+        .map(|(id, duration)| VideoScreenTimeShare {
+            video_id: id.to_string(),
+            title: videos[id].title.clone(),
+            duration,
+            share: if total > 0.0 { duration / total } else { 0.0 },
+        })
+        .collect();
+    shares.sort_by(|a, b| {
+        b.duration
+            .total_cmp(&a.duration)
+            .then_with(|| a.title.cmp(&b.title))
+            .then_with(|| a.video_id.cmp(&b.video_id))
+    });
+    shares
+}
+
+/// Summarizes a list of clips: durations, screen time per video, performer, marker title
+/// and tag, and (if render settings are given) an estimate of the output size and render time.
+pub fn compute_statistics(
+    clips: &[Clip],
+    videos: &[VideoDto],
+    performers: &HashMap<String, Vec<String>>,
+    render_settings: Option<&RenderSettings>,
+) -> ClipStatistics {
+    let videos: HashMap<_, _> = videos.iter().map(|v| (v.id.as_str(), v)).collect();
+    let total_duration: f64 = clips.iter().map(|c| c.duration()).sum();
+
+    let mut per_video = HashMap::new();
+    let mut per_performer = HashMap::new();
+    let mut per_marker_title = HashMap::new();
+    let mut per_tag = HashMap::new();
+    for clip in clips {
+        let duration = clip.duration();
+        *per_marker_title
+            .entry(clip.marker_title.clone())
+            .or_insert(0.0) += duration;
+        if let Some(video) = videos.get(clip.video_id.as_str()) {
+            *per_video.entry(video.id.as_str()).or_insert(0.0) += duration;
+            for tag in &video.tags {
+                *per_tag.entry(tag.clone()).or_insert(0.0) += duration;
+            }
+        }
+        for performer in performers.get(&clip.video_id).into_iter().flatten() {
+            *per_performer.entry(performer.clone()).or_insert(0.0) += duration;
+        }
+    }
+
+    ClipStatistics {
+        total_duration,
+        clip_count: clips.len(),
+        cut_count: clips.len().saturating_sub(1),
+        shortest_clip: clips
+            .iter()
+            .min_by(|a, b| a.duration().total_cmp(&b.duration()))
+            .cloned(),
+        longest_clip: clips
+            .iter()
+            .max_by(|a, b| a.duration().total_cmp(&b.duration()))
+            .cloned(),
+        videos: to_video_shares(per_video, &videos, total_duration),
+        performers: to_shares(per_performer, total_duration),
+        marker_titles: to_shares(per_marker_title, total_duration),
+        tags: to_shares(per_tag, total_duration),
+        estimate: render_settings.map(|settings| estimate_render(total_duration, settings)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::assert_approx_eq;
+
+    use super::*;
+    use crate::data::database::videos::VideoSource;
+
+    fn clip(video_id: &str, marker_title: &str, range: (f64, f64)) -> Clip {
+        Clip {
+            source: VideoSource::Folder,
+            video_id: video_id.to_string(),
+            marker_id: 1,
+            range,
+            index_within_video: 0,
+            index_within_marker: 0,
+            marker_title: marker_title.to_string(),
+        }
+    }
+
+    fn video(id: &str, tags: &[&str]) -> VideoDto {
+        VideoDto {
+            id: id.to_string(),
+            title: format!("Video {id}"),
+            file_name: format!("{id}.mp4"),
+            file_path: None,
+            interactive: false,
+            source: VideoSource::Folder,
+            duration: 100.0,
+            stash_scene_id: None,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            created_on: 0,
+            missing_since: None,
+        }
+    }
+
+    #[test]
+    fn test_compute_statistics() {
+        let clips = vec![
+            clip("a", "Intro", (0.0, 10.0)),
+            clip("b", "Dance", (0.0, 5.0)),
+            clip("a", "Dance", (20.0, 25.0)),
+        ];
+        let videos = vec![video("a", &["outdoor", "music"]), video("b", &["music"])];
+        let performers = HashMap::from([("b".to_string(), vec!["Performer".to_string()])]);
+        let stats = compute_statistics(&clips, &videos, &performers, None);
+
+        assert_approx_eq!(f64, stats.total_duration, 20.0);
+        assert_eq!(stats.clip_count, 3);
+        assert_eq!(stats.cut_count, 2);
+        assert_eq!(stats.longest_clip.unwrap().range, (0.0, 10.0));
+        assert_eq!(stats.shortest_clip.unwrap().video_id, "b");
+        assert_eq!(stats.videos[0].video_id, "a");
+        assert_eq!(stats.videos[0].title, "Video a");
+        assert_approx_eq!(f64, stats.videos[0].share, 0.75);
+        assert_eq!(stats.marker_titles.len(), 2);
+        assert_approx_eq!(f64, stats.marker_titles[0].share, 0.5);
+        assert_eq!(stats.tags[0].name, "music");
+        assert_approx_eq!(f64, stats.tags[0].share, 1.0);
+        assert_eq!(stats.performers.len(), 1);
+        assert_approx_eq!(f64, stats.performers[0].duration, 5.0);
+        assert!(stats.estimate.is_none());
+    }
+
+    #[test]
+    fn test_estimate_render() {
+        let settings = RenderSettings {
+            output_resolution: (1920, 1080),
+            output_fps: 30,
+            video_codec: VideoCodec::H264,
+            video_quality: VideoQuality::Medium,
+            encoding_effort: EncodingEffort::Medium,
+        };
+        let estimate = estimate_render(60.0, &settings);
+        assert_eq!(estimate.file_size_bytes, 38_460_000);
+        assert_approx_eq!(f64, estimate.render_time_seconds, 30.0);
+
+        let smaller = estimate_render(
+            60.0,
+            &RenderSettings {
+                output_resolution: (1280, 720),
+                ..settings
+            },
+        );
+        assert!(smaller.file_size_bytes < estimate.file_size_bytes);
+        assert!(smaller.render_time_seconds < estimate.render_time_seconds);
+    }
+
+    #[test]
+    fn test_videos_with_same_title_are_kept_apart() {
+        let clips = vec![
+            clip("a", "Intro", (0.0, 10.0)),
+            clip("b", "Intro", (0.0, 5.0)),
+        ];
+        let mut videos = vec![video("a", &[]), video("b", &[])];
+        for video in &mut videos {
+            video.title = "Untitled".to_string();
+        }
+        let stats = compute_statistics(&clips, &videos, &HashMap::new(), None);
+
+        assert_eq!(stats.videos.len(), 2);
+        assert_eq!(stats.videos[0].video_id, "a");
+        assert_approx_eq!(f64, stats.videos[0].share, 2.0 / 3.0);
+        assert_eq!(stats.videos[1].video_id, "b");
+        assert_eq!(stats.videos[1].title, "Untitled");
+    }
+}
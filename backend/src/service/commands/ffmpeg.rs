@@ -298,4 +298,15 @@ impl Ffmpeg {
             commandline_error(self.executable_path.as_str(), output)
         }
     }
+
+    /// Runs ffmpeg and returns the raw bytes it wrote to standard output.
+    pub async fn stdout(&self) -> Result<Vec<u8>> {
+        let mut command = self.command();
+        let output = command.output().await?;
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            commandline_error(self.executable_path.as_str(), output)
+        }
+    }
 }
@@ -1,5 +1,7 @@
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info, Level};
+use utoipa::ToSchema;
 
 use super::ffmpeg::FfmpegLocation;
 use crate::util::commandline_error;
@@ -13,10 +15,28 @@ pub struct VideoParameters {
     pub codec: String,
 }
 
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SongTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+/// A chapter that's embedded in a video file (MKV and MP4 files often have them).
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoChapter {
+    pub title: String,
+    pub start: f64,
+    pub end: f64,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct FfProbe {
     pub streams: Vec<Stream>,
     pub format: Format,
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
 }
 
 impl FfProbe {
@@ -27,6 +47,57 @@ impl FfProbe {
             .and_then(|n| n.parse::<f64>().ok())
     }
 
+    /// Title, artist and album of an audio file. Most formats store them in the container,
+    /// but some (like Ogg Vorbis) store them on the audio stream instead.
+    pub fn song_tags(&self) -> SongTags {
+        let audio_tags = self
+            .streams
+            .iter()
+            .find(|s| s.codec_type.as_deref() == Some("audio"))
+            .and_then(|s| s.tags.as_ref());
+        let format_tags = self.format.tags.as_ref();
+        let tag = |get: fn(&FormatTags) -> &Option<String>| {
+            format_tags
+                .and_then(|t| get(t).clone())
+                .or_else(|| audio_tags.and_then(|t| get(t).clone()))
+                .filter(|value| !value.trim().is_empty())
+        };
+
+        SongTags {
+            title: tag(|t| &t.title),
+            artist: tag(|t| &t.artist),
+            album: tag(|t| &t.album),
+        }
+    }
+
+    /// The chapters of the video, ordered by their start time. Chapters without a title are
+    /// numbered, empty ones are skipped.
+    pub fn chapters(&self) -> Vec<VideoChapter> {
+        self.chapters
+            .iter()
+            .filter_map(|chapter| {
+                let start = chapter.start_time.parse::<f64>().ok()?;
+                let end = chapter.end_time.parse::<f64>().ok()?;
+                Some((chapter, start, end))
+            })
+            .filter(|(_, start, end)| end > start)
+            .sorted_by(|(_, a, _), (_, b, _)| a.total_cmp(b))
+            .enumerate()
+            .map(|(index, (chapter, start, end))| VideoChapter {
+                title: chapter
+                    .tags
+                    .as_ref()
+                    .and_then(|t| t.title.as_deref())
+                    .map(str::trim)
+                    .filter(|title| !title.is_empty())
+                    .map(String::from)
+                    .unwrap_or_else(|| format!("Chapter {}", index + 1)),
+                start,
+                end,
+            })
+            .collect()
+    }
+
     pub fn video_parameters(self) -> VideoParameters {
         let video_stream = self
             .streams
@@ -69,6 +140,7 @@ pub struct Stream {
     pub width: Option<i64>,
     pub height: Option<i64>,
     pub id: Option<String>,
+    pub tags: Option<FormatTags>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -93,6 +165,21 @@ impl Format {
     }
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct Chapter {
+    pub id: i64,
+    pub time_base: String,
+    pub start_time: String,
+    pub end_time: String,
+    pub tags: Option<ChapterTags>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ChapterTags {
+    #[serde(alias = "TITLE", alias = "Title")]
+    pub title: Option<String>,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct FormatTags {
     #[serde(rename = "WMFSDKNeeded")]
@@ -108,6 +195,12 @@ pub struct FormatTags {
     pub compatible_brands: Option<String>,
     pub creation_time: Option<String>,
     pub encoder: Option<String>,
+    #[serde(alias = "TITLE", alias = "Title")]
+    pub title: Option<String>,
+    #[serde(alias = "ARTIST", alias = "Artist")]
+    pub artist: Option<String>,
+    #[serde(alias = "ALBUM", alias = "Album")]
+    pub album: Option<String>,
 }
 
 pub async fn ffprobe(path: impl AsRef<str>, location: &FfmpegLocation) -> Result<FfProbe> {
@@ -122,6 +215,7 @@ pub async fn ffprobe(path: impl AsRef<str>, location: &FfmpegLocation) -> Result
         "json",
         "-show_format",
         "-show_streams",
+        "-show_chapters",
         path.as_ref(),
     ];
     debug!("running ffprobe with args {args:?}");
@@ -154,3 +248,83 @@ pub async fn get_version(location: &FfmpegLocation) -> Result<String> {
         commandline_error("ffmpeg", output)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chapters() {
+        let json = r#"{
+            "streams": [],
+            "format": {
+                "filename": "video.mkv",
+                "nb_streams": 1,
+                "nb_programs": 0,
+                "format_name": "matroska,webm",
+                "format_long_name": "Matroska / WebM",
+                "duration": "300.000000"
+            },
+            "chapters": [
+                {
+                    "id": 2,
+                    "time_base": "1/1000000000",
+                    "start": 120000000000,
+                    "start_time": "120.000000",
+                    "end": 300000000000,
+                    "end_time": "300.000000",
+                    "tags": { "title": "  " }
+                },
+                {
+                    "id": 1,
+                    "time_base": "1/1000000000",
+                    "start": 0,
+                    "start_time": "0.000000",
+                    "end": 120000000000,
+                    "end_time": "120.000000",
+                    "tags": { "TITLE": "Intro" }
+                },
+                {
+                    "id": 3,
+                    "time_base": "1/1000000000",
+                    "start": 300000000000,
+                    "start_time": "300.000000",
+                    "end": 300000000000,
+                    "end_time": "300.000000"
+                }
+            ]
+        }"#;
+        let ffprobe: FfProbe = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            ffprobe.chapters(),
+            vec![
+                VideoChapter {
+                    title: "Intro".to_string(),
+                    start: 0.0,
+                    end: 120.0,
+                },
+                VideoChapter {
+                    title: "Chapter 2".to_string(),
+                    start: 120.0,
+                    end: 300.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stored_info_without_chapters() {
+        let json = r#"{
+            "streams": [],
+            "format": {
+                "filename": "video.mp4",
+                "nb_streams": 1,
+                "nb_programs": 0,
+                "format_name": "mov,mp4,m4a,3gp,3g2,mj2",
+                "format_long_name": "QuickTime / MOV"
+            }
+        }"#;
+        let ffprobe: FfProbe = serde_json::from_str(json).unwrap();
+        assert!(ffprobe.chapters().is_empty());
+    }
+}
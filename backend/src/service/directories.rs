@@ -157,6 +157,15 @@ impl Directories {
         self.cache_dir().join("preview-images")
     }
 
+    /// Temporary WAV files that songs are converted to for analyzing them.
+    pub fn temp_audio_dir(&self) -> Utf8PathBuf {
+        self.cache_dir().join("temp-audio")
+    }
+
+    pub fn waveform_dir(&self) -> Utf8PathBuf {
+        self.cache_dir().join("waveforms")
+    }
+
     pub fn config_file_path(&self) -> Utf8PathBuf {
         self.config_dir().join("config.json")
     }
@@ -0,0 +1,314 @@
+use std::collections::{HashMap, HashSet};
+
+use camino::Utf8Path;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::data::database::videos::VideoHashes;
+use crate::data::database::Database;
+use crate::service::clip_similarity::frame_hash;
+use crate::service::commands::ffmpeg::FfmpegLocation;
+use crate::Result;
+
+/// Maximum number of differing bits for two perceptual hashes to count as the same video.
+const MAX_PERCEPTUAL_DISTANCE: u32 = 4;
+
+/// Maximum difference in seconds for two videos with similar frames to count as the same video.
+const MAX_DURATION_DIFFERENCE: f64 = 2.0;
+
+/// Computes the perceptual hash of a video from the frame in its middle. Unlike the file hash,
+/// it stays similar if the video was re-encoded.
+pub async fn perceptual_hash(
+    ffmpeg_location: &FfmpegLocation,
+    path: impl AsRef<Utf8Path>,
+    duration: f64,
+) -> Result<u64> {
+    frame_hash(ffmpeg_location, path.as_ref().as_str(), duration / 2.0).await
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum DuplicateReason {
+    /// The files are identical.
+    FileHash,
+    /// The videos look the same and have the same length, but the files differ.
+    PerceptualHash,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateVideo {
+    pub id: String,
+    pub file_path: String,
+    pub title: Option<String>,
+    pub duration: f64,
+    pub marker_count: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    pub reason: DuplicateReason,
+    pub videos: Vec<DuplicateVideo>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeDuplicatesBody {
+    /// The video that is kept.
+    pub keep_id: String,
+    /// The videos whose markers are moved to the kept video before they're removed.
+    pub remove_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeDuplicatesResult {
+    pub moved_markers: u64,
+    pub removed_videos: usize,
+}
+
+/// Groups the videos by identical file hash first. The remaining videos are grouped if their
+/// perceptual hashes are close and they have about the same duration.
+fn group_duplicates(videos: &[VideoHashes]) -> Vec<(DuplicateReason, Vec<usize>)> {
+    let mut by_file_hash: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, video) in videos.iter().enumerate() {
+        if let Some(hash) = &video.file_hash {
+            by_file_hash.entry(hash).or_default().push(index);
+        }
+    }
+    let mut groups: Vec<_> = by_file_hash
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|group| (DuplicateReason::FileHash, group))
+        .collect();
+    groups.sort_by_key(|(_, group)| group[0]);
+
+    let mut grouped: HashSet<usize> = groups.iter().flat_map(|(_, g)| g.clone()).collect();
+    for (index, video) in videos.iter().enumerate() {
+        let Some(hash) = video.perceptual_hash else {
+            continue;
+        };
+        if grouped.contains(&index) {
+            continue;
+        }
+
+        let mut group = vec![index];
+        for (other_index, other) in videos.iter().enumerate().skip(index + 1) {
+            let similar = other.perceptual_hash.is_some_and(|other_hash| {
+                (hash ^ other_hash).count_ones() <= MAX_PERCEPTUAL_DISTANCE
+                    && (video.duration - other.duration).abs() <= MAX_DURATION_DIFFERENCE
+            });
+            if similar && !grouped.contains(&other_index) {
+                group.push(other_index);
+            }
+        }
+        if group.len() > 1 {
+            grouped.extend(group.iter().copied());
+            groups.push((DuplicateReason::PerceptualHash, group));
+        }
+    }
+
+    groups
+}
+
+pub struct DuplicatesService {
+    database: Database,
+}
+
+impl DuplicatesService {
+    pub fn new(database: Database) -> Self {
+        DuplicatesService { database }
+    }
+
+    pub async fn find_duplicates(&self) -> Result<Vec<DuplicateGroup>> {
+        let videos = self.database.videos.list_video_hashes().await?;
+        let groups = group_duplicates(&videos);
+        info!("found {} groups of duplicate videos", groups.len());
+
+        Ok(groups
+            .into_iter()
+            .map(|(reason, indices)| DuplicateGroup {
+                reason,
+                videos: indices
+                    .into_iter()
+                    .map(|index| {
+                        let video = &videos[index];
+                        DuplicateVideo {
+                            id: video.id.clone(),
+                            file_path: video.file_path.clone(),
+                            title: video.title.clone(),
+                            duration: video.duration,
+                            marker_count: video.marker_count,
+                        }
+                    })
+                    .collect(),
+            })
+            .collect())
+    }
+
+    /// Moves the markers and performers of the duplicates to the kept video and removes the
+    /// duplicates from the library, all in one transaction. Their files are left on disk.
+    pub async fn merge(&self, body: &MergeDuplicatesBody) -> Result<MergeDuplicatesResult> {
+        let mut transaction = self.database.begin().await?;
+        let mut moved_markers = 0;
+        for id in &body.remove_ids {
+            moved_markers += self
+                .database
+                .markers
+                .move_markers(id, &body.keep_id, &mut transaction)
+                .await?;
+            self.database
+                .performers
+                .move_performers(id, &body.keep_id, &mut transaction)
+                .await?;
+            self.database
+                .videos
+                .delete_video_with(id, &mut transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        info!("merged videos {:?} into {}", body.remove_ids, body.keep_id);
+
+        Ok(MergeDuplicatesResult {
+            moved_markers,
+            removed_videos: body.remove_ids.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::data::database::performers::CreatePerformer;
+    use crate::service::fixtures::{persist_marker, persist_video};
+
+    fn video(
+        id: &str,
+        duration: f64,
+        file_hash: Option<&str>,
+        perceptual_hash: Option<u64>,
+    ) -> VideoHashes {
+        VideoHashes {
+            id: id.to_string(),
+            file_path: format!("/videos/{id}.mp4"),
+            title: None,
+            duration,
+            file_hash: file_hash.map(String::from),
+            perceptual_hash,
+            marker_count: 0,
+        }
+    }
+
+    #[test]
+    fn test_group_duplicates() {
+        let videos = vec![
+            video("a", 100.0, Some("aaaa"), Some(0b1111)),
+            video("b", 100.0, Some("aaaa"), Some(0b1111)),
+            video("c", 60.0, Some("cccc"), Some(0xff00)),
+            video("d", 61.0, Some("dddd"), Some(0xff01)),
+            video("e", 300.0, Some("eeee"), Some(0xff00)),
+            video("f", 60.0, None, Some(0x00ff)),
+        ];
+
+        let groups = group_duplicates(&videos);
+        assert_eq!(
+            groups,
+            vec![
+                (DuplicateReason::FileHash, vec![0, 1]),
+                (DuplicateReason::PerceptualHash, vec![2, 3]),
+            ]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_merge_moves_markers(pool: SqlitePool) -> Result<()> {
+        let database = Database::with_pool(pool);
+        let keep = persist_video(&database).await?;
+        let duplicate = persist_video(&database).await?;
+        persist_marker(&database, &keep.id, 0, 10.0, 20.0, false).await?;
+        persist_marker(&database, &duplicate.id, 0, 5.0, 8.0, false).await?;
+
+        let service = DuplicatesService::new(database.clone());
+        let result = service
+            .merge(&MergeDuplicatesBody {
+                keep_id: keep.id.clone(),
+                remove_ids: vec![duplicate.id.clone()],
+            })
+            .await?;
+        assert_eq!(result.moved_markers, 1);
+        assert_eq!(result.removed_videos, 1);
+
+        let markers = database.markers.get_markers_for_video(&keep.id).await?;
+        assert_eq!(markers.len(), 2);
+        assert_eq!(markers[0].start_time, 5.0);
+        assert_eq!(markers[0].index_within_video, 0);
+        assert_eq!(markers[1].index_within_video, 1);
+        assert!(database.videos.get_video(&duplicate.id).await?.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_merge_with_overlapping_markers_and_performers(pool: SqlitePool) -> Result<()> {
+        let database = Database::with_pool(pool);
+        let keep = persist_video(&database).await?;
+        let duplicate = persist_video(&database).await?;
+        persist_marker(&database, &keep.id, 0, 10.0, 20.0, false).await?;
+        persist_marker(&database, &duplicate.id, 0, 10.0, 20.0, false).await?;
+        persist_marker(&database, &duplicate.id, 1, 30.0, 40.0, false).await?;
+        let performers = vec![
+            CreatePerformer {
+                name: "Jane Doe".to_string(),
+                image_url: None,
+                stash_id: None,
+                gender: None,
+            },
+            CreatePerformer {
+                name: "John Roe".to_string(),
+                image_url: None,
+                stash_id: None,
+                gender: None,
+            },
+        ];
+        database
+            .performers
+            .insert_for_video(&performers[..1], &keep.id)
+            .await?;
+        database
+            .performers
+            .insert_for_video(&performers, &duplicate.id)
+            .await?;
+
+        let service = DuplicatesService::new(database.clone());
+        let result = service
+            .merge(&MergeDuplicatesBody {
+                keep_id: keep.id.clone(),
+                remove_ids: vec![duplicate.id.clone()],
+            })
+            .await?;
+        assert_eq!(result.moved_markers, 1);
+        assert_eq!(result.removed_videos, 1);
+
+        let markers = database.markers.get_markers_for_video(&keep.id).await?;
+        assert_eq!(markers.len(), 2);
+        assert_eq!(markers[1].start_time, 30.0);
+        assert_eq!(markers[1].index_within_video, 1);
+        assert!(database.videos.get_video(&duplicate.id).await?.is_none());
+
+        let names = database
+            .performers
+            .find_names_for_videos(&[keep.id.as_str(), duplicate.id.as_str()])
+            .await?;
+        assert_eq!(
+            names.get(&keep.id),
+            Some(&vec!["Jane Doe".to_string(), "John Roe".to_string()])
+        );
+        assert!(!names.contains_key(&duplicate.id));
+
+        Ok(())
+    }
+}
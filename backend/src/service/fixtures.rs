@@ -486,6 +486,9 @@ pub fn songs() -> Vec<Beats> {
                 169.25867, 169.79199, 170.29333, 170.79466, 171.296, 171.79733, 172.29333,
                 172.78934, 173.29066, 173.79199, 174.288, 174.784, 175.28532, 175.78667, 176.28267,
             ],
+            downbeats: None,
+            beats_per_measure: None,
+            energy: None,
         },
         Beats {
             length: 294.2475,
@@ -562,6 +565,9 @@ pub fn songs() -> Vec<Beats> {
                 286.69867, 287.21066, 287.728, 288.24533, 288.76266, 289.28, 289.78665, 290.29333,
                 290.8,
             ],
+            downbeats: None,
+            beats_per_measure: None,
+            energy: None,
         },
     ]
 }
@@ -1,6 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::eyre;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -86,6 +87,62 @@ impl FunScript {
     }
 }
 
+/// Finds the funscript next to a local video, e.g. `video.funscript` for `video.mp4`.
+/// The extension is matched case-insensitively.
+pub fn find_sidecar_funscript(video_path: &Utf8Path) -> Option<Utf8PathBuf> {
+    let exact = video_path.with_extension("funscript");
+    if exact.is_file() {
+        return Some(exact);
+    }
+
+    let stem = video_path.file_stem()?;
+    let entries = video_path.parent()?.read_dir_utf8().ok()?;
+    entries
+        .flatten()
+        .map(|entry| entry.into_path())
+        .find(|path| {
+            path.file_stem() == Some(stem)
+                && path
+                    .extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case("funscript"))
+                && path.is_file()
+        })
+}
+
+/// File stems of all funscripts in the folder. Lets callers that check many videos at once
+/// read each folder only once.
+pub fn funscript_stems(folder: &Utf8Path) -> HashSet<String> {
+    let Ok(entries) = folder.read_dir_utf8() else {
+        return HashSet::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("funscript"))
+        })
+        .filter_map(|path| path.file_stem().map(String::from))
+        .collect()
+}
+
+/// Loads the funscript next to a local video. Scripts that can't be parsed or don't
+/// have any actions are ignored.
+pub async fn load_sidecar_funscript(video_path: &Utf8Path) -> Option<FunScript> {
+    let path = find_sidecar_funscript(video_path)?;
+    match FunScript::load(&path).await {
+        Ok(script) if !script.actions.is_empty() => Some(script),
+        Ok(_) => {
+            warn!("funscript {path} has no actions, ignoring it");
+            None
+        }
+        Err(e) => {
+            warn!("failed to load funscript {path}: {e}");
+            None
+        }
+    }
+}
+
 impl Default for FunScript {
     fn default() -> Self {
         FunScript {
@@ -295,10 +352,10 @@ impl ScriptBuilder {
                     self.api.get_funscript(stash_id).await
                 }
                 VideoSource::Download | VideoSource::Folder => {
-                    let path =
-                        Utf8PathBuf::from(video.file_path.clone()).with_extension("funscript");
-                    info!("trying to load funscript from {}", path);
-                    FunScript::load(path).await
+                    info!("trying to load funscript for {}", video.file_path);
+                    load_sidecar_funscript(Utf8Path::new(&video.file_path))
+                        .await
+                        .ok_or_else(|| eyre!("no funscript found next to {}", video.file_path))
                 }
             };
 
@@ -349,15 +406,43 @@ impl ScriptBuilder {
 
 #[cfg(test)]
 mod test {
+    use std::collections::HashSet;
+
     use tracing_test::traced_test;
 
     use super::StrokeType;
     use crate::server::types::Beats;
+    use camino::Utf8PathBuf;
+
     use crate::service::funscript::{
-        combine_scripts, create_beat_funscript, FunScript, FunScriptSegment,
+        combine_scripts, create_beat_funscript, find_sidecar_funscript, funscript_stems,
+        load_sidecar_funscript, FunScript, FunScriptSegment,
     };
     use crate::Result;
 
+    #[tokio::test]
+    async fn test_load_sidecar_funscript() -> Result<()> {
+        let dir = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .unwrap()
+            .join("clip-mash-sidecar-funscript");
+        std::fs::create_dir_all(&dir)?;
+        let video = dir.join("video.mp4");
+        std::fs::write(&video, [])?;
+        assert_eq!(find_sidecar_funscript(&video), None);
+
+        let script = dir.join("video.FunScript");
+        std::fs::copy("data/funscripts/dokkaebi.funscript", &script)?;
+        assert_eq!(find_sidecar_funscript(&video), Some(script.clone()));
+        assert!(load_sidecar_funscript(&video).await.is_some());
+        assert_eq!(funscript_stems(&dir), HashSet::from(["video".to_string()]));
+
+        std::fs::write(&script, r#"{"actions": []}"#)?;
+        assert!(load_sidecar_funscript(&video).await.is_none());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn test_create_combined_script() -> Result<()> {
@@ -397,10 +482,16 @@ mod test {
             Beats {
                 length: 1.0,
                 offsets: vec![0.0, 0.5, 1.0],
+                downbeats: None,
+                beats_per_measure: None,
+                energy: None,
             },
             Beats {
                 length: 2.0,
                 offsets: vec![0.5, 1.0, 1.5, 2.0],
+                downbeats: None,
+                beats_per_measure: None,
+                energy: None,
             },
         ];
 
@@ -438,10 +529,16 @@ mod test {
             Beats {
                 length: len1,
                 offsets: (0..(len1 as usize)).map(|i| i as f32).collect(),
+                downbeats: None,
+                beats_per_measure: None,
+                energy: None,
             },
             Beats {
                 length: len2,
                 offsets: (0..(len2 as usize)).map(|i| i as f32).collect(),
+                downbeats: None,
+                beats_per_measure: None,
+                energy: None,
             },
         ];
 
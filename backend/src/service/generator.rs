@@ -1,12 +1,14 @@
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::time::Instant;
 
 use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::bail;
 use color_eyre::Section;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use tokio::process::Command;
-use tracing::{debug, info, Level};
+use tracing::{debug, info, warn, Level};
 use utoipa::ToSchema;
 
 use super::commands::ffmpeg::FfmpegLocation;
@@ -41,6 +43,7 @@ pub struct CompilationOptions {
     pub padding: PaddingType,
     pub force_re_encode: bool,
     pub include_original_file_name: bool,
+    pub broken_videos: BrokenVideoHandling,
 }
 
 fn get_clip_file_name(
@@ -83,6 +86,17 @@ impl Default for PaddingType {
     }
 }
 
+/// What to do with clips from videos that failed the integrity check.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BrokenVideoHandling {
+    /// Try to encode the clips anyway and show a warning in the progress messages.
+    #[default]
+    Warn,
+    /// Leave the clips out of the compilation.
+    Skip,
+}
+
 #[derive(Debug)]
 struct CreateClip<'a> {
     url: &'a str,
@@ -319,12 +333,68 @@ impl CompilationGenerator {
         ids
     }
 
+    /// Returns the clips that should be encoded, leaving out clips from broken videos
+    /// if the options say so.
+    async fn usable_clips<'a>(
+        &self,
+        options: &'a CompilationOptions,
+    ) -> Result<(Vec<&'a Clip>, HashMap<String, String>)> {
+        let video_ids = self.get_video_ids(options);
+        let broken_videos = self
+            .database
+            .integrity
+            .get_broken_videos(&video_ids)
+            .await?;
+        for (video_id, errors) in &broken_videos {
+            warn!("video {video_id} is flagged as broken: {errors}");
+        }
+
+        let clips: Vec<_> = options
+            .clips
+            .iter()
+            .filter(|clip| {
+                options.broken_videos == BrokenVideoHandling::Warn
+                    || !broken_videos.contains_key(&clip.video_id)
+            })
+            .collect();
+        if clips.len() < options.clips.len() {
+            info!(
+                "skipping {} clips from broken videos",
+                options.clips.len() - clips.len()
+            );
+        }
+        if clips.is_empty() && !options.clips.is_empty() {
+            bail!("all clips are from videos that are flagged as broken");
+        }
+
+        Ok((clips, broken_videos))
+    }
+
     pub async fn gather_clips(&self, options: &CompilationOptions) -> Result<Vec<Utf8PathBuf>> {
         let mut estimator = Estimator::new(Instant::now());
-        let clips = &options.clips;
+        let (clips, broken_videos) = match self.usable_clips(options).await {
+            Ok(result) => result,
+            Err(e) => {
+                self.database
+                    .progress
+                    .progress_error(&options.video_id, &e.to_string())
+                    .await?;
+                return Err(e);
+            }
+        };
         let total_duration = clips.iter().map(|c| c.duration()).sum();
         self.initialize_progress(&options.video_id, total_duration)
             .await?;
+        let broken_clips = clips
+            .iter()
+            .filter(|c| broken_videos.contains_key(&c.video_id))
+            .count();
+        if broken_clips > 0 {
+            let message =
+                format!("Warning: {broken_clips} clips are from videos that are flagged as broken");
+            self.increase_progress(&options.video_id, 0.0, 0.0, &message)
+                .await?;
+        }
         let video_dir = self.directories.temp_video_dir();
         tokio::fs::create_dir_all(&video_dir).await?;
         let video_dir = video_dir.canonicalize_utf8()?;
@@ -402,6 +472,12 @@ impl CompilationGenerator {
                     let e = e.with_note(|| {
                         format!("failed to create clip for video {}", marker.video_id)
                     });
+                    let e = match broken_videos.get(&marker.video_id) {
+                        Some(errors) => {
+                            e.with_note(|| format!("the video is flagged as broken: {errors}"))
+                        }
+                        None => e,
+                    };
                     let error = e.to_string();
                     self.database
                         .progress
@@ -413,12 +489,15 @@ impl CompilationGenerator {
             } else {
                 info!("clip {out_file} already exists, skipping");
             }
-            let message = format!(
+            let mut message = format!(
                 "Encoding clip for marker '{}' from {} to {}",
                 marker.title,
                 format_duration(*start),
                 format_duration(*end)
             );
+            if broken_videos.contains_key(&marker.video_id) {
+                message.push_str(" (warning: the video is flagged as broken)");
+            }
             completed += clip.duration();
             estimator.record(completed as u64, Instant::now());
 
@@ -558,6 +637,15 @@ impl CompilationGenerator {
         self.ffmpeg(args).await?;
 
         info!("finished assembling video, result at {destination}");
+        // the video is done at this point, so failing to record it shouldn't fail the render
+        if let Err(e) = self
+            .database
+            .compilations
+            .record_compilation(&options.video_id, file_name, &options.clips)
+            .await
+        {
+            warn!("failed to record compilation {}: {e:?}", options.video_id);
+        }
         self.increase_progress(&options.video_id, 1.0, 0.0, "Compiling clips together")
             .await?;
         self.finish_progress(&options.video_id).await?;
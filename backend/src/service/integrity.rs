@@ -0,0 +1,227 @@
+use std::time::Duration;
+
+use camino::Utf8Path;
+use itertools::Itertools;
+use tracing::{error, info, warn};
+
+use super::commands::ffmpeg::{Ffmpeg, FfmpegLocation};
+use crate::data::database::integrity::{VerificationMode, VideoIntegrity};
+use crate::data::database::Database;
+use crate::Result;
+
+/// How often new videos are checked in the background.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Number of samples that are decoded in [`VerificationMode::Sample`].
+const SAMPLE_COUNT: usize = 8;
+
+/// Length of each sample in seconds.
+const SAMPLE_DURATION: f64 = 2.0;
+
+/// Maximum number of error lines that are stored per video.
+const MAX_ERROR_LINES: usize = 20;
+
+/// Start times of the samples, spread evenly over the video. Short videos are decoded
+/// completely, so there are no samples for them.
+fn sample_starts(duration: f64) -> Vec<f64> {
+    if duration <= SAMPLE_DURATION * SAMPLE_COUNT as f64 {
+        return vec![];
+    }
+    let step = (duration - SAMPLE_DURATION) / (SAMPLE_COUNT - 1) as f64;
+    (0..SAMPLE_COUNT).map(|i| i as f64 * step).collect()
+}
+
+/// Combines the error output of all ffmpeg runs, dropping duplicate and empty lines.
+fn collect_errors<'a>(outputs: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let lines: Vec<_> = outputs
+        .into_iter()
+        .flat_map(|output| output.lines())
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .unique()
+        .take(MAX_ERROR_LINES)
+        .collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+pub fn run_async(database: Database, ffmpeg_location: FfmpegLocation) {
+    tokio::spawn(async move {
+        let verifier = IntegrityVerifier::new(database, ffmpeg_location);
+        loop {
+            if let Err(e) = verifier.verify_unchecked_videos().await {
+                error!("failed to verify videos: {e:?}");
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+/// Decodes videos with ffmpeg to find corrupt files before they break a compilation.
+pub struct IntegrityVerifier {
+    database: Database,
+    ffmpeg_location: FfmpegLocation,
+}
+
+impl IntegrityVerifier {
+    pub fn new(database: Database, ffmpeg_location: FfmpegLocation) -> Self {
+        Self {
+            database,
+            ffmpeg_location,
+        }
+    }
+
+    /// Decodes the video (or a part of it) and returns ffmpeg's error output. A file that
+    /// ffmpeg can't open at all counts as an error as well.
+    async fn decode(&self, file_path: &str, start: Option<f64>) -> Result<String> {
+        let mut ffmpeg = Ffmpeg::new(&self.ffmpeg_location, "-");
+        if let Some(start) = start {
+            ffmpeg
+                .start(start)
+                .extra_arg("-t")
+                .extra_arg(SAMPLE_DURATION.to_string());
+        }
+        let result = ffmpeg
+            .input(file_path)
+            .format("null")
+            .log_level("error")
+            .output()
+            .await;
+
+        match result {
+            Ok(output) => Ok(output),
+            // ffmpeg couldn't be started, that says nothing about the video
+            Err(e) if e.downcast_ref::<std::io::Error>().is_some() => Err(e),
+            Err(e) => Ok(e.to_string()),
+        }
+    }
+
+    async fn find_errors(
+        &self,
+        file_path: &str,
+        duration: f64,
+        mode: VerificationMode,
+    ) -> Result<Option<String>> {
+        if !Utf8Path::new(file_path).is_file() {
+            return Ok(Some(format!("file {file_path} does not exist")));
+        }
+
+        let starts = match mode {
+            VerificationMode::Full => vec![],
+            VerificationMode::Sample => sample_starts(duration),
+        };
+        let mut outputs = vec![];
+        if starts.is_empty() {
+            outputs.push(self.decode(file_path, None).await?);
+        }
+        for start in starts {
+            outputs.push(self.decode(file_path, Some(start)).await?);
+        }
+        Ok(collect_errors(outputs.iter().map(String::as_str)))
+    }
+
+    /// Checks the video and stores the result.
+    pub async fn verify_video(
+        &self,
+        video_id: &str,
+        file_path: &str,
+        duration: f64,
+        mode: VerificationMode,
+    ) -> Result<VideoIntegrity> {
+        info!("verifying video {video_id} at {file_path} ({mode:?})");
+        let errors = self.find_errors(file_path, duration, mode).await?;
+        if let Some(errors) = &errors {
+            warn!("video {video_id} at {file_path} is broken: {errors}");
+        }
+        self.database
+            .integrity
+            .set_integrity(video_id, mode, errors.as_deref())
+            .await?;
+
+        Ok(self
+            .database
+            .integrity
+            .get_integrity(video_id)
+            .await?
+            .expect("integrity must exist after storing it"))
+    }
+
+    /// Checks the given videos one after the other.
+    async fn verify_videos(&self, video_ids: &[&str], mode: VerificationMode) -> Result<()> {
+        let videos = self.database.videos.get_videos_by_ids(video_ids).await?;
+        for video in videos {
+            self.verify_video(&video.id, &video.file_path, video.duration, mode)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Checks all local videos, or only the given ones.
+    pub async fn verify_local_videos(
+        &self,
+        video_ids: Option<Vec<String>>,
+        mode: VerificationMode,
+    ) -> Result<()> {
+        let video_ids = match video_ids {
+            Some(ids) => ids,
+            None => self
+                .database
+                .videos
+                .list_local_video_files()
+                .await?
+                .into_iter()
+                .map(|v| v.id)
+                .collect(),
+        };
+        let video_ids: Vec<_> = video_ids.iter().map(String::as_str).collect();
+        self.verify_videos(&video_ids, mode).await
+    }
+
+    /// Samples all local videos that were never checked.
+    pub async fn verify_unchecked_videos(&self) -> Result<()> {
+        let videos = self.database.integrity.list_unchecked_videos().await?;
+        if !videos.is_empty() {
+            info!("verifying {} unchecked videos", videos.len());
+        }
+        for video in videos {
+            self.verify_video(
+                &video.id,
+                &video.file_path,
+                video.duration,
+                VerificationMode::Sample,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_starts() {
+        assert!(sample_starts(10.0).is_empty());
+        let starts = sample_starts(72.0);
+        assert_eq!(starts.len(), SAMPLE_COUNT);
+        assert_eq!(starts[0], 0.0);
+        assert_eq!(starts[1], 10.0);
+        assert_eq!(starts[SAMPLE_COUNT - 1], 70.0);
+    }
+
+    #[test]
+    fn test_collect_errors() {
+        assert_eq!(collect_errors(["", "\n  \n"]), None);
+        assert_eq!(
+            collect_errors([
+                "[h264 @ 0x1] error while decoding MB 10 2\n",
+                "[h264 @ 0x1] error while decoding MB 10 2\nInvalid data found\n",
+            ]),
+            Some("[h264 @ 0x1] error while decoding MB 10 2\nInvalid data found".to_string())
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::{HashMap, HashSet};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::data::database::videos::VideoFileStatus;
+use crate::data::database::{unix_timestamp_now, Database};
+use crate::helpers::oshash::oshash;
+use crate::service::video::VideoService;
+use crate::Result;
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingVideo {
+    pub id: String,
+    pub file_path: String,
+    pub missing_since: i64,
+    /// Whether the video can be found again by its content after it was moved.
+    pub has_file_hash: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryHealthReport {
+    /// Number of local videos that were checked.
+    pub checked: usize,
+    pub missing: Vec<MissingVideo>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RemapPathsBody {
+    /// The part of the path that changed, e.g. the old mount point of a drive.
+    pub from: String,
+    /// What `from` is replaced with.
+    pub to: String,
+    /// Only report which videos would be changed.
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RelinkVideosBody {
+    /// Folder to search for the moved files.
+    pub path: String,
+    pub recurse: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RelocatedVideo {
+    pub id: String,
+    pub old_path: String,
+    pub new_path: String,
+    /// Whether there is a file at the new path. Videos are only relocated if there is.
+    pub exists: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RelinkVideosResult {
+    pub relinked: Vec<RelocatedVideo>,
+    /// Number of missing videos whose file wasn't found.
+    pub unmatched: usize,
+}
+
+/// Replaces the `from` prefix of the path with `to`. The prefix must end at a path separator,
+/// so `/media/videos` doesn't match `/media/videos-old/a.mp4`.
+fn remap_path(path: &str, from: &str, to: &str) -> Option<String> {
+    let trim = |p: &str| p.trim_end_matches(['/', '\\']).to_string();
+    let (from, to) = (trim(from), trim(to));
+    let rest = path.strip_prefix(&from)?;
+    if from.is_empty() || !(rest.starts_with('/') || rest.starts_with('\\')) {
+        return None;
+    }
+    Some(format!("{to}{rest}"))
+}
+
+/// Finds the new location of missing videos by comparing their stored hash with the hashes
+/// of the candidate files. Returns pairs of video ID and new path.
+fn match_moved_files(
+    missing: &[&VideoFileStatus],
+    candidates: &[(Utf8PathBuf, String)],
+) -> Vec<(String, Utf8PathBuf)> {
+    let mut by_hash: HashMap<&str, &VideoFileStatus> = missing
+        .iter()
+        .filter_map(|video| video.file_hash.as_deref().map(|hash| (hash, *video)))
+        .collect();
+
+    candidates
+        .iter()
+        .filter_map(|(path, hash)| {
+            by_hash
+                .remove(hash.as_str())
+                .map(|video| (video.id.clone(), path.clone()))
+        })
+        .collect()
+}
+
+pub struct LibraryHealthService {
+    database: Database,
+}
+
+impl LibraryHealthService {
+    pub fn new(database: Database) -> Self {
+        LibraryHealthService { database }
+    }
+
+    /// Checks which local videos have a missing file and updates their missing flag.
+    /// Videos that exist but weren't hashed yet are hashed, so they can be found again
+    /// if they're moved later.
+    pub async fn check(&self) -> Result<LibraryHealthReport> {
+        let videos = self.database.videos.list_local_video_files().await?;
+        let now = unix_timestamp_now();
+        let mut missing = vec![];
+
+        for video in &videos {
+            let path = Utf8Path::new(&video.file_path);
+            let exists = path.is_file();
+            match (exists, video.missing_since) {
+                (false, None) => {
+                    self.database
+                        .videos
+                        .set_missing_since(&video.id, Some(now))
+                        .await?
+                }
+                (true, Some(_)) => {
+                    self.database
+                        .videos
+                        .set_missing_since(&video.id, None)
+                        .await?
+                }
+                _ => {}
+            }
+
+            if exists && video.file_hash.is_none() {
+                match oshash(path) {
+                    Ok(hash) => self.database.videos.set_file_hash(&video.id, &hash).await?,
+                    Err(e) => warn!("failed to hash video file {path}: {e}"),
+                }
+            }
+            if !exists {
+                missing.push(MissingVideo {
+                    id: video.id.clone(),
+                    file_path: video.file_path.clone(),
+                    missing_since: video.missing_since.unwrap_or(now),
+                    has_file_hash: video.file_hash.is_some(),
+                });
+            }
+        }
+
+        info!(
+            "checked {} videos, {} are missing",
+            videos.len(),
+            missing.len()
+        );
+        Ok(LibraryHealthReport {
+            checked: videos.len(),
+            missing,
+        })
+    }
+
+    /// Replaces the start of the paths of all local videos, e.g. after a drive was mounted
+    /// somewhere else. Only videos whose file exists at the new path are changed.
+    pub async fn remap_paths(&self, body: &RemapPathsBody) -> Result<Vec<RelocatedVideo>> {
+        let videos = self.database.videos.list_local_video_files().await?;
+        let mut relocated = vec![];
+        for video in videos {
+            let Some(new_path) = remap_path(&video.file_path, &body.from, &body.to) else {
+                continue;
+            };
+            let exists = Utf8Path::new(&new_path).is_file();
+            if exists && !body.dry_run {
+                self.database
+                    .videos
+                    .set_file_path(&video.id, &new_path)
+                    .await?;
+            }
+            relocated.push(RelocatedVideo {
+                id: video.id,
+                old_path: video.file_path,
+                new_path,
+                exists,
+            });
+        }
+
+        info!(
+            "remapped paths from {} to {} for {} videos (dry run: {})",
+            body.from,
+            body.to,
+            relocated.iter().filter(|v| v.exists).count(),
+            body.dry_run
+        );
+        Ok(relocated)
+    }
+
+    /// Searches the folder for the files of missing videos by their content hash and points
+    /// the videos to them. Since the videos keep their ID, their markers are kept as well.
+    pub async fn relink_by_hash(&self, body: &RelinkVideosBody) -> Result<RelinkVideosResult> {
+        let videos = self.database.videos.list_local_video_files().await?;
+        let missing: Vec<_> = videos
+            .iter()
+            .filter(|v| !Utf8Path::new(&v.file_path).is_file())
+            .collect();
+        if missing.is_empty() {
+            return Ok(RelinkVideosResult {
+                relinked: vec![],
+                unmatched: 0,
+            });
+        }
+
+        let known_paths: HashSet<_> = videos.iter().map(|v| v.file_path.as_str()).collect();
+        let files = VideoService::gather_files(body.path.clone().into(), body.recurse).await?;
+        let mut candidates = vec![];
+        for file in files {
+            if known_paths.contains(file.as_str()) {
+                continue;
+            }
+            match oshash(&file) {
+                Ok(hash) => candidates.push((file, hash)),
+                Err(e) => warn!("failed to hash file {file}: {e}"),
+            }
+        }
+
+        let mut relinked = vec![];
+        for (id, new_path) in match_moved_files(&missing, &candidates) {
+            self.database
+                .videos
+                .set_file_path(&id, new_path.as_str())
+                .await?;
+            let old_path = missing
+                .iter()
+                .find(|v| v.id == id)
+                .map(|v| v.file_path.clone())
+                .unwrap_or_default();
+            info!("relinked video {id} from {old_path} to {new_path}");
+            relinked.push(RelocatedVideo {
+                id,
+                old_path,
+                new_path: new_path.to_string(),
+                exists: true,
+            });
+        }
+
+        Ok(RelinkVideosResult {
+            unmatched: missing.len() - relinked.len(),
+            relinked,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::service::fixtures::persist_video;
+
+    fn video(id: &str, file_hash: Option<&str>) -> VideoFileStatus {
+        VideoFileStatus {
+            id: id.to_string(),
+            file_path: format!("/old/{id}.mp4"),
+            missing_since: None,
+            file_hash: file_hash.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_remap_path() {
+        assert_eq!(
+            remap_path("/media/old/videos/a.mp4", "/media/old", "/mnt/new/"),
+            Some("/mnt/new/videos/a.mp4".to_string())
+        );
+        assert_eq!(
+            remap_path("D:\\videos\\a.mp4", "D:\\", "E:\\"),
+            Some("E:\\videos\\a.mp4".to_string())
+        );
+        assert_eq!(remap_path("/media/old-2/a.mp4", "/media/old", "/mnt"), None);
+        assert_eq!(remap_path("/other/a.mp4", "/media/old", "/mnt"), None);
+        assert_eq!(remap_path("/a.mp4", "/", "/mnt"), None);
+    }
+
+    #[test]
+    fn test_match_moved_files() {
+        let videos = [
+            video("a", Some("aaaa")),
+            video("b", Some("bbbb")),
+            video("c", None),
+        ];
+        let missing: Vec<_> = videos.iter().collect();
+        let candidates = vec![
+            (Utf8PathBuf::from("/new/x.mp4"), "bbbb".to_string()),
+            (Utf8PathBuf::from("/new/y.mp4"), "cccc".to_string()),
+            (Utf8PathBuf::from("/new/z.mp4"), "bbbb".to_string()),
+        ];
+
+        let matches = match_moved_files(&missing, &candidates);
+        assert_eq!(
+            matches,
+            vec![("b".to_string(), Utf8PathBuf::from("/new/x.mp4"))]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_check_flags_missing_videos(pool: SqlitePool) -> Result<()> {
+        let database = Database::with_pool(pool);
+        let video = persist_video(&database).await?;
+        let service = LibraryHealthService::new(database.clone());
+
+        let report = service.check().await?;
+        assert_eq!(report.checked, 1);
+        assert_eq!(report.missing.len(), 1);
+        assert_eq!(report.missing[0].id, video.id);
+
+        let stored = database.videos.list_local_video_files().await?;
+        assert!(stored[0].missing_since.is_some());
+
+        Ok(())
+    }
+}
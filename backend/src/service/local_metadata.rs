@@ -0,0 +1,282 @@
+use camino::Utf8Path;
+use lazy_static::lazy_static;
+use regex::Regex;
+use tracing::{debug, warn};
+
+lazy_static! {
+    static ref PLACEHOLDER_REGEX: Regex = Regex::new(r"\{(\w+)\}").unwrap();
+    static ref PERFORMER_SEPARATOR_REGEX: Regex = Regex::new(r"\s*(?:,|&|\band\b)\s*").unwrap();
+    static ref ACTOR_REGEX: Regex = Regex::new(r"(?s)<actor>(.*?)</actor>").unwrap();
+    static ref TITLE_REGEX: Regex = element_regex("title");
+    static ref STUDIO_REGEX: Regex = element_regex("studio");
+    static ref TAG_REGEX: Regex = element_regex("tag");
+    static ref GENRE_REGEX: Regex = element_regex("genre");
+    static ref NAME_REGEX: Regex = element_regex("name");
+}
+
+/// Placeholders that can be used in file name templates.
+const PLACEHOLDERS: &[&str] = &["title", "studio", "performer", "tag", "ignore"];
+
+/// Metadata of a local video that's read from an NFO file next to it or from its file name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LocalMetadata {
+    pub title: Option<String>,
+    pub studio: Option<String>,
+    pub performers: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+impl LocalMetadata {
+    /// Fills in everything that's missing from `other`.
+    fn merge(mut self, other: LocalMetadata) -> Self {
+        self.title = self.title.or(other.title);
+        self.studio = self.studio.or(other.studio);
+        if self.performers.is_empty() {
+            self.performers = other.performers;
+        }
+        for tag in other.tags {
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+        self
+    }
+
+    /// The tags of the video. The studio is stored as a tag, like for stash videos.
+    pub fn all_tags(&self) -> Vec<String> {
+        let mut tags = self.tags.clone();
+        if let Some(studio) = &self.studio {
+            if !tags.contains(studio) {
+                tags.push(studio.clone());
+            }
+        }
+        tags
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Matches simple elements with the given name, without attributes or nested elements,
+/// which is all Kodi and Jellyfin write for the fields that are read here.
+fn element_regex(name: &str) -> Regex {
+    Regex::new(&format!(r"(?s)<{name}>\s*(.*?)\s*</{name}>")).unwrap()
+}
+
+/// Returns the text of all elements matched by the regex from [`element_regex`].
+fn element_texts(xml: &str, regex: &Regex) -> Vec<String> {
+    regex
+        .captures_iter(xml)
+        .map(|c| decode_entities(&c[1]))
+        .filter(|text| !text.is_empty() && !text.contains('<'))
+        .collect()
+}
+
+/// Reads the title, tags, genres, performers and studio from a Kodi or Jellyfin NFO file.
+fn parse_nfo(xml: &str) -> LocalMetadata {
+    let mut tags = element_texts(xml, &TAG_REGEX);
+    for genre in element_texts(xml, &GENRE_REGEX) {
+        if !tags.contains(&genre) {
+            tags.push(genre);
+        }
+    }
+    let performers = ACTOR_REGEX
+        .captures_iter(xml)
+        .filter_map(|c| element_texts(&c[1], &NAME_REGEX).into_iter().next())
+        .collect();
+
+    LocalMetadata {
+        title: element_texts(xml, &TITLE_REGEX).into_iter().next(),
+        studio: element_texts(xml, &STUDIO_REGEX).into_iter().next(),
+        performers,
+        tags,
+    }
+}
+
+/// A user-defined pattern like `{studio} - {performer} - {title}` that the file names of
+/// videos are matched against.
+#[derive(Debug, Clone)]
+pub struct FileNameTemplate {
+    regex: Regex,
+}
+
+impl FileNameTemplate {
+    pub fn parse(template: &str) -> Result<Self, &'static str> {
+        let mut pattern = String::from("^");
+        let mut used = vec![];
+        let mut last_end = 0;
+        for captures in PLACEHOLDER_REGEX.captures_iter(template) {
+            let placeholder = captures.get(0).unwrap();
+            let name = captures.get(1).unwrap().as_str();
+            if !PLACEHOLDERS.contains(&name) {
+                return Err("Unknown placeholder in file name template");
+            }
+            if name != "ignore" && used.contains(&name) {
+                return Err("Placeholders can only be used once per template");
+            }
+            used.push(name);
+
+            pattern.push_str(&regex::escape(&template[last_end..placeholder.start()]));
+            if name == "ignore" {
+                pattern.push_str(".*?");
+            } else {
+                pattern.push_str(&format!("(?P<{name}>.+?)"));
+            }
+            last_end = placeholder.end();
+        }
+        if used.is_empty() {
+            return Err("File name template must contain at least one placeholder");
+        }
+        pattern.push_str(&regex::escape(&template[last_end..]));
+        pattern.push('$');
+
+        let regex = Regex::new(&pattern).map_err(|_| "Invalid file name template")?;
+        Ok(FileNameTemplate { regex })
+    }
+
+    /// Matches the file name (without extension) against the template.
+    pub fn apply(&self, file_stem: &str) -> Option<LocalMetadata> {
+        let captures = self.regex.captures(file_stem)?;
+        let value = |name: &str| {
+            captures
+                .name(name)
+                .map(|m| m.as_str().trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+
+        Some(LocalMetadata {
+            title: value("title"),
+            studio: value("studio"),
+            performers: value("performer")
+                .map(|p| {
+                    PERFORMER_SEPARATOR_REGEX
+                        .split(&p)
+                        .filter(|s| !s.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            tags: value("tag").into_iter().collect(),
+        })
+    }
+}
+
+/// Parses the templates, skipping (and logging) invalid ones.
+pub fn parse_templates(templates: &[String]) -> Vec<FileNameTemplate> {
+    templates
+        .iter()
+        .filter_map(|template| {
+            FileNameTemplate::parse(template)
+                .inspect_err(|e| warn!("ignoring file name template '{template}': {e}"))
+                .ok()
+        })
+        .collect()
+}
+
+/// Reads the metadata of the video file. Values from an NFO file with the same name take
+/// precedence over the first template that matches the file name.
+pub fn read_local_metadata(path: &Utf8Path, templates: &[FileNameTemplate]) -> LocalMetadata {
+    let nfo_path = path.with_extension("nfo");
+    let nfo = match std::fs::read_to_string(&nfo_path) {
+        Ok(xml) => parse_nfo(&xml),
+        Err(_) => LocalMetadata::default(),
+    };
+
+    let file_stem = path.file_stem().unwrap_or_default();
+    let from_file_name = templates
+        .iter()
+        .find_map(|template| template.apply(file_stem))
+        .unwrap_or_default();
+
+    let metadata = nfo.merge(from_file_name);
+    debug!("read metadata {metadata:?} for video {path}");
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nfo() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes" ?>
+<movie>
+    <title>Day &amp; Night</title>
+    <originaltitle>Other title</originaltitle>
+    <studio>Some Studio</studio>
+    <genre>Drama</genre>
+    <tag>outdoor</tag>
+    <tag>Drama</tag>
+    <actor>
+        <name>Jane Doe</name>
+        <role>Herself</role>
+        <thumb>http://example.com/jane.jpg</thumb>
+    </actor>
+    <actor>
+        <name>John Roe</name>
+    </actor>
+</movie>"#;
+
+        assert_eq!(
+            parse_nfo(xml),
+            LocalMetadata {
+                title: Some("Day & Night".to_string()),
+                studio: Some("Some Studio".to_string()),
+                performers: vec!["Jane Doe".to_string(), "John Roe".to_string()],
+                tags: vec!["outdoor".to_string(), "Drama".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_file_name_template() {
+        let template = FileNameTemplate::parse("{studio} - {performer} - {title}").unwrap();
+        assert_eq!(
+            template.apply("Studio - Jane Doe & John Roe - The Title - Part 2"),
+            Some(LocalMetadata {
+                title: Some("The Title - Part 2".to_string()),
+                studio: Some("Studio".to_string()),
+                performers: vec!["Jane Doe".to_string(), "John Roe".to_string()],
+                tags: vec![],
+            })
+        );
+        assert_eq!(template.apply("no separators here"), None);
+
+        let template = FileNameTemplate::parse("[{tag}] {title} {ignore}").unwrap();
+        let metadata = template.apply("[pov] Title (1080p)").unwrap();
+        assert_eq!(metadata.title, Some("Title".to_string()));
+        assert_eq!(metadata.tags, vec!["pov".to_string()]);
+    }
+
+    #[test]
+    fn test_invalid_file_name_templates() {
+        assert!(FileNameTemplate::parse("just text").is_err());
+        assert!(FileNameTemplate::parse("{title} - {title}").is_err());
+        assert!(FileNameTemplate::parse("{name}").is_err());
+    }
+
+    #[test]
+    fn test_merge_prefers_nfo() {
+        let nfo = LocalMetadata {
+            title: Some("From NFO".to_string()),
+            tags: vec!["a".to_string()],
+            ..Default::default()
+        };
+        let file_name = LocalMetadata {
+            title: Some("From file name".to_string()),
+            studio: Some("Studio".to_string()),
+            performers: vec!["Jane".to_string()],
+            tags: vec!["a".to_string(), "b".to_string()],
+        };
+
+        let merged = nfo.merge(file_name);
+        assert_eq!(merged.title, Some("From NFO".to_string()));
+        assert_eq!(merged.performers, vec!["Jane".to_string()]);
+        assert_eq!(merged.all_tags(), vec!["a", "b", "Studio"]);
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::{HashMap, HashSet};
 use std::time::Instant;
 
 use camino::{Utf8Path, Utf8PathBuf};
@@ -11,8 +12,11 @@ use crate::data::database::performers::CreatePerformer;
 use crate::data::database::videos::{AllVideosFilter, VideoUpdate};
 use crate::data::database::{Database, Settings};
 use crate::data::stash_api::StashApi;
+use crate::helpers::oshash::oshash;
 use crate::helpers::parallelize;
 use crate::service::commands::ffprobe;
+use crate::service::duplicates::perceptual_hash;
+use crate::service::funscript::funscript_stems;
 use crate::Result;
 
 fn video_id_from_path(path: &Utf8Path) -> Option<&str> {
@@ -316,6 +320,70 @@ impl Migrator {
         Ok(())
     }
 
+    /// Computes the file and perceptual hashes of videos that were added before they existed.
+    async fn backfill_video_hashes(&self) -> Result<()> {
+        info!("computing video hashes if necessary");
+        let videos = self.database.videos.list_video_hashes().await?;
+        for video in videos {
+            let path = Utf8Path::new(&video.file_path);
+            if !path.is_file() || (video.file_hash.is_some() && video.perceptual_hash.is_some()) {
+                continue;
+            }
+
+            if video.file_hash.is_none() {
+                match oshash(path) {
+                    Ok(hash) => self.database.videos.set_file_hash(&video.id, &hash).await?,
+                    Err(e) => warn!("failed to hash video file {path}: {e}"),
+                }
+            }
+            if video.perceptual_hash.is_none() {
+                match perceptual_hash(&self.ffmpeg_location, path, video.duration).await {
+                    Ok(hash) => {
+                        self.database
+                            .videos
+                            .set_perceptual_hash(&video.id, hash)
+                            .await?
+                    }
+                    Err(e) => warn!("failed to compute perceptual hash for {path}: {e}"),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Flags local videos as interactive if a funscript was added next to them after they
+    /// were imported, and the other way round if it was removed. Only checks whether the
+    /// scripts exist, they're validated when importing videos and building scripts.
+    async fn detect_local_funscripts(&self) -> Result<()> {
+        info!("detecting funscripts for local videos");
+        let videos = self.database.videos.list_local_video_files().await?;
+        let mut funscripts: HashMap<Utf8PathBuf, HashSet<String>> = HashMap::new();
+        for video in videos {
+            let path = Utf8Path::new(&video.file_path);
+            let (Some(folder), Some(stem)) = (path.parent(), path.file_stem()) else {
+                continue;
+            };
+            if !path.is_file() {
+                continue;
+            }
+            let stems = funscripts
+                .entry(folder.to_owned())
+                .or_insert_with(|| funscript_stems(folder));
+            let interactive = stems.contains(stem);
+            if self
+                .database
+                .videos
+                .set_video_interactive(&video.id, interactive)
+                .await?
+            {
+                info!("video {} is now interactive: {interactive}", video.id);
+            }
+        }
+
+        Ok(())
+    }
+
     pub async fn run(&self) -> Result<()> {
         info!("running migrations");
         let start = Instant::now();
@@ -323,7 +391,7 @@ impl Migrator {
         futures::try_join!(
             self.database
                 .music
-                .generate_all_beats(self.ffmpeg_location.clone()),
+                .generate_all_beats(self.ffmpeg_location.clone(), self.directories.clone()),
             self.set_video_durations(),
             self.generate_video_preview_images(),
             self.generate_marker_preview_images(),
@@ -334,6 +402,8 @@ impl Migrator {
             self.migrate_settings(),
             self.set_performers_from_stash(),
             self.migrate_video_tags_to_json(),
+            self.backfill_video_hashes(),
+            self.detect_local_funscripts(),
         )?;
 
         let elapsed = start.elapsed();
@@ -1,20 +1,30 @@
 pub mod clip;
+pub mod clip_editor;
+pub mod clip_similarity;
+pub mod clip_statistics;
 pub mod commands;
 pub mod description_generator;
 pub mod directories;
+pub mod duplicates;
 pub mod encoding_optimization;
 pub mod funscript;
 pub mod generator;
 pub mod handy;
+pub mod integrity;
+pub mod library_health;
+pub mod local_metadata;
 pub mod migrations;
+pub mod motion;
 pub mod music;
 pub mod new_version_checker;
 pub mod options_converter;
 pub mod preview_image;
 pub mod scene_detection;
+pub mod scheduler;
 pub mod stash_config;
 pub mod streams;
 pub mod video;
+pub mod watch_folders;
 
 #[cfg(test)]
 pub mod fixtures;
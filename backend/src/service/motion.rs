@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use camino::Utf8Path;
+use lazy_static::lazy_static;
+use regex::Regex;
+use tracing::{debug, error, info, warn};
+
+use super::commands::ffmpeg::{Ffmpeg, FfmpegLocation};
+use crate::data::database::motion::VideoMotion;
+use crate::data::database::videos::DbVideo;
+use crate::data::database::Database;
+use crate::Result;
+
+lazy_static! {
+    static ref SCENE_SCORE_REGEX: Regex = Regex::new(r"lavfi\.scene_score=([\d\.]+)").unwrap();
+}
+
+/// How often (in seconds) the video is sampled.
+const SAMPLE_INTERVAL: f64 = 0.5;
+
+/// How often new videos are analyzed in the background.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+fn parse_scene_scores(output: &str) -> Vec<f64> {
+    output
+        .lines()
+        .filter_map(|line| SCENE_SCORE_REGEX.captures(line))
+        .filter_map(|captures| captures.get(1).unwrap().as_str().parse().ok())
+        .collect()
+}
+
+pub fn run_async(database: Database, ffmpeg_location: FfmpegLocation) {
+    tokio::spawn(async move {
+        let analyzer = MotionAnalyzer::new(database, ffmpeg_location);
+        loop {
+            if let Err(e) = analyzer.analyze_unanalyzed_videos().await {
+                error!("failed to analyze motion of videos: {e:?}");
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+pub struct MotionAnalyzer {
+    database: Database,
+    ffmpeg_location: FfmpegLocation,
+}
+
+impl MotionAnalyzer {
+    pub fn new(database: Database, ffmpeg_location: FfmpegLocation) -> Self {
+        Self {
+            database,
+            ffmpeg_location,
+        }
+    }
+
+    /// Samples the video at a low frame rate and resolution and uses ffmpeg's scene score
+    /// (the difference between two consecutive samples) as a measure of motion.
+    async fn sample_scene_scores(&self, file_path: &str) -> Result<Vec<f64>> {
+        let output = Ffmpeg::new(&self.ffmpeg_location, "-")
+            .input(file_path)
+            .format("null")
+            .video_filter(format!(
+                "fps={},scale=160:-2,select='gte(scene,0)',metadata=print:key=lavfi.scene_score",
+                1.0 / SAMPLE_INTERVAL
+            ))
+            .extra_arg("-an")
+            .log_level("info")
+            .output()
+            .await?;
+        debug!("output: {}", output);
+
+        Ok(parse_scene_scores(&output))
+    }
+
+    /// Returns the motion analysis for the video, running the analysis if it isn't cached yet
+    /// (or if `force` is set).
+    pub async fn analyze_video(&self, video: &DbVideo, force: bool) -> Result<VideoMotion> {
+        if !force {
+            if let Some(motion) = self.database.motion.get_motion(&video.id).await? {
+                return Ok(motion);
+            }
+        }
+
+        info!("analyzing motion for video {}", video.id);
+        let scores = self.sample_scene_scores(&video.file_path).await?;
+        let motion = VideoMotion {
+            video_id: video.id.clone(),
+            sample_interval: SAMPLE_INTERVAL,
+            scores,
+        };
+        self.database.motion.set_motion(&motion).await?;
+
+        Ok(motion)
+    }
+
+    /// Analyzes all local videos that weren't analyzed yet, so the motion is known before
+    /// it's needed for picking clips. Only runs if it's enabled in the settings.
+    pub async fn analyze_unanalyzed_videos(&self) -> Result<()> {
+        let settings = self.database.settings.fetch().await?;
+        if !settings.background_motion_analysis {
+            return Ok(());
+        }
+        let video_ids = self.database.motion.list_unanalyzed_videos().await?;
+        if video_ids.is_empty() {
+            return Ok(());
+        }
+        info!("analyzing motion of {} videos", video_ids.len());
+        let video_ids: Vec<_> = video_ids.iter().map(String::as_str).collect();
+        let videos = self.database.videos.get_videos_by_ids(&video_ids).await?;
+        for video in videos {
+            if !Utf8Path::new(&video.file_path).is_file() {
+                continue;
+            }
+            if let Err(e) = self.analyze_video(&video, false).await {
+                warn!("failed to analyze motion of video {}: {e:?}", video.id);
+                self.database
+                    .motion
+                    .set_failure(&video.id, &e.to_string())
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_scene_scores;
+
+    #[test]
+    fn test_parse_scene_scores() {
+        let output = "[Parsed_metadata_3 @ 0x1] frame:0    pts:0       pts_time:0
+[Parsed_metadata_3 @ 0x1] lavfi.scene_score=0.000000
+[Parsed_metadata_3 @ 0x1] frame:1    pts:1       pts_time:0.5
+[Parsed_metadata_3 @ 0x1] lavfi.scene_score=0.123456
+frame=    2 fps=0.0 q=-0.0 Lsize=N/A time=00:00:01.00 bitrate=N/A speed=10x";
+
+        assert_eq!(parse_scene_scores(output), vec![0.0, 0.123456]);
+    }
+}
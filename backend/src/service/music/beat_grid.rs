@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+
+use crate::server::error::ValidationErrors;
+use crate::server::types::{BeatGridEdit, Beats};
+
+/// Beats closer together than this (in seconds) are considered the same beat.
+const EPSILON: f32 = 0.001;
+const MAX_BPM: f32 = 400.0;
+const MIN_TAPS: usize = 4;
+
+fn error(field: &'static str, message: &'static str) -> ValidationErrors {
+    HashMap::from([(field, message)])
+}
+
+/// All offsets within the song that are a multiple of `interval` away from `phase`.
+fn regular_grid(phase: f32, interval: f32, length: f32) -> Vec<f32> {
+    let first = phase - (phase / interval).floor() * interval;
+    let mut offsets = vec![];
+    let mut index = 0;
+    loop {
+        let offset = first + index as f32 * interval;
+        if offset >= length {
+            break;
+        }
+        offsets.push(offset);
+        index += 1;
+    }
+    offsets
+}
+
+fn set_tempo(
+    beats: &mut Beats,
+    bpm: f32,
+    phase: f32,
+    beats_per_measure: Option<usize>,
+) -> Result<(), ValidationErrors> {
+    if !(bpm > 0.0 && bpm <= MAX_BPM) {
+        return Err(error("bpm", "BPM must be between 0 and 400"));
+    }
+    if beats_per_measure == Some(0) {
+        return Err(error(
+            "beatsPerMeasure",
+            "Beats per measure must be at least 1",
+        ));
+    }
+
+    let interval = 60.0 / bpm;
+    let beats_per_measure = beats_per_measure.or(beats.beats_per_measure);
+    beats.offsets = regular_grid(phase, interval, beats.length);
+    beats.downbeats =
+        beats_per_measure.map(|n| regular_grid(phase, interval * n as f32, beats.length));
+    beats.beats_per_measure = beats_per_measure;
+    Ok(())
+}
+
+/// Fits a line through the taps (least squares), returning the beat interval and the
+/// offset of the first tap.
+fn fit_taps(taps: &[f32]) -> Option<(f32, f32)> {
+    let n = taps.len() as f32;
+    let mean_index = (n - 1.0) / 2.0;
+    let mean_tap = taps.iter().sum::<f32>() / n;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (index, tap) in taps.iter().enumerate() {
+        let dx = index as f32 - mean_index;
+        covariance += dx * (tap - mean_tap);
+        variance += dx * dx;
+    }
+    let interval = covariance / variance;
+    if interval > 0.0 && interval.is_finite() {
+        Some((interval, mean_tap - interval * mean_index))
+    } else {
+        None
+    }
+}
+
+/// Applies a single correction to the beat grid of a song.
+pub fn apply_edit(beats: &Beats, edit: &BeatGridEdit) -> Result<Beats, ValidationErrors> {
+    let mut beats = beats.clone();
+    let in_song = |offset: f32| (0.0..beats.length).contains(&offset);
+
+    match edit {
+        BeatGridEdit::SetTempo {
+            bpm,
+            phase,
+            beats_per_measure,
+        } => {
+            if !in_song(*phase) {
+                return Err(error("phase", "Phase must be within the song"));
+            }
+            set_tempo(&mut beats, *bpm, *phase, *beats_per_measure)?;
+        }
+        BeatGridEdit::Nudge { offset } => {
+            let length = beats.length;
+            let shift = |offsets: &mut Vec<f32>| {
+                offsets.iter_mut().for_each(|o| *o += offset);
+                offsets.retain(|o| (0.0..length).contains(o));
+            };
+            shift(&mut beats.offsets);
+            if let Some(downbeats) = &mut beats.downbeats {
+                shift(downbeats);
+            }
+        }
+        BeatGridEdit::InsertBeat { offset } => {
+            if !in_song(*offset) {
+                return Err(error("offset", "Beat must be within the song"));
+            }
+            if beats.offsets.iter().any(|o| (o - offset).abs() < EPSILON) {
+                return Err(error("offset", "There already is a beat at this offset"));
+            }
+            let index = beats.offsets.partition_point(|o| o < offset);
+            beats.offsets.insert(index, *offset);
+        }
+        BeatGridEdit::DeleteBeat { index } => {
+            if *index >= beats.offsets.len() {
+                return Err(error("index", "Beat index is out of bounds"));
+            }
+            let removed = beats.offsets.remove(*index);
+            if let Some(downbeats) = &mut beats.downbeats {
+                downbeats.retain(|d| (d - removed).abs() >= EPSILON);
+            }
+        }
+        BeatGridEdit::TapTempo {
+            taps,
+            beats_per_measure,
+        } => {
+            if taps.len() < MIN_TAPS || !taps.iter().all(|t| in_song(*t)) {
+                return Err(error(
+                    "taps",
+                    "At least four taps within the song are required",
+                ));
+            }
+            let Some((interval, phase)) = fit_taps(taps) else {
+                return Err(error("taps", "Taps must be in ascending order"));
+            };
+            set_tempo(&mut beats, 60.0 / interval, phase, *beats_per_measure)?;
+        }
+    }
+
+    Ok(beats)
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::assert_approx_eq;
+
+    use super::*;
+
+    fn beats() -> Beats {
+        Beats {
+            offsets: vec![0.1, 0.6, 1.1, 1.6, 2.1, 2.6, 3.1, 3.6],
+            length: 4.0,
+            downbeats: Some(vec![0.1, 2.1]),
+            beats_per_measure: Some(4),
+            energy: None,
+        }
+    }
+
+    fn assert_offsets(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?} != {expected:?}");
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert_approx_eq!(f32, *actual, *expected, epsilon = 0.0001);
+        }
+    }
+
+    #[test]
+    fn test_set_tempo() {
+        let edit = BeatGridEdit::SetTempo {
+            bpm: 60.0,
+            phase: 1.5,
+            beats_per_measure: Some(2),
+        };
+        let result = apply_edit(&beats(), &edit).unwrap();
+        assert_offsets(&result.offsets, &[0.5, 1.5, 2.5, 3.5]);
+        assert_offsets(result.downbeats.as_ref().unwrap(), &[1.5, 3.5]);
+        assert_eq!(result.beats_per_measure, Some(2));
+
+        let edit = BeatGridEdit::SetTempo {
+            bpm: 0.0,
+            phase: 0.0,
+            beats_per_measure: None,
+        };
+        assert!(apply_edit(&beats(), &edit).unwrap_err().contains_key("bpm"));
+    }
+
+    #[test]
+    fn test_nudge() {
+        let result = apply_edit(&beats(), &BeatGridEdit::Nudge { offset: -0.2 }).unwrap();
+        assert_offsets(&result.offsets, &[0.4, 0.9, 1.4, 1.9, 2.4, 2.9, 3.4]);
+        assert_offsets(result.downbeats.as_ref().unwrap(), &[1.9]);
+    }
+
+    #[test]
+    fn test_insert_and_delete_beats() {
+        let result = apply_edit(&beats(), &BeatGridEdit::InsertBeat { offset: 0.3 }).unwrap();
+        assert_offsets(&result.offsets[..3], &[0.1, 0.3, 0.6]);
+        assert!(apply_edit(&result, &BeatGridEdit::InsertBeat { offset: 0.3 }).is_err());
+        assert!(apply_edit(&result, &BeatGridEdit::InsertBeat { offset: 5.0 }).is_err());
+
+        let result = apply_edit(&result, &BeatGridEdit::DeleteBeat { index: 0 }).unwrap();
+        assert_offsets(&result.offsets[..2], &[0.3, 0.6]);
+        assert_offsets(result.downbeats.as_ref().unwrap(), &[2.1]);
+        assert!(apply_edit(&result, &BeatGridEdit::DeleteBeat { index: 100 }).is_err());
+    }
+
+    #[test]
+    fn test_tap_tempo() {
+        let edit = BeatGridEdit::TapTempo {
+            taps: vec![1.02, 1.49, 2.01, 2.48],
+            beats_per_measure: None,
+        };
+        let result = apply_edit(&beats(), &edit).unwrap();
+        let interval = result.offsets[1] - result.offsets[0];
+        assert_approx_eq!(f32, interval, 0.49, epsilon = 0.001);
+        assert!(result.offsets.iter().any(|o| (o - 1.0).abs() < 0.05));
+        assert_eq!(result.beats_per_measure, Some(4));
+
+        let edit = BeatGridEdit::TapTempo {
+            taps: vec![2.0, 1.5, 1.0, 0.5],
+            beats_per_measure: None,
+        };
+        assert!(apply_edit(&beats(), &edit).is_err());
+    }
+}
@@ -5,31 +5,175 @@ use aubio::{OnsetMode, Smpl, Tempo};
 use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::eyre::eyre;
 use hound::WavReader;
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::server::types::Beats;
+use crate::helpers::random::generate_id;
+use crate::server::types::{Beats, SongEnergy};
 use crate::service::commands::ffmpeg::FfmpegLocation;
+use crate::service::directories::Directories;
 use crate::util::commandline_error;
 use crate::Result as AppResult;
 
 const BUF_SIZE: usize = 512;
 const HOP_SIZE: usize = 256;
 const I16_TO_SMPL: Smpl = 1.0 / (1 << 16) as Smpl;
+/// Cutoff frequency (in Hz) of the low-pass filter used to measure how accented a beat is.
+/// Bass and kick drums usually mark the first beat of a measure.
+const ACCENT_CUTOFF: f32 = 150.0;
+/// Length of the window (in seconds) after every beat that is used to measure its accent.
+const ACCENT_WINDOW: f32 = 0.1;
+/// Candidate numbers of beats per measure, 4/4 wins unless another meter is clearly better.
+const METERS: [usize; 2] = [4, 3];
+const METER_PREFERENCE: f32 = 1.2;
 
-fn convert_to_wav(source: impl AsRef<Utf8Path>, ffmpeg: &FfmpegLocation) -> AppResult<Utf8PathBuf> {
+/// Length of the frames (in seconds) that the song energy is measured over.
+const ENERGY_INTERVAL: f32 = 0.5;
+/// Number of frames the energy is averaged over, so that it describes whole sections
+/// (verse, chorus) instead of single notes.
+const ENERGY_SMOOTHING: usize = 16;
+
+fn percentile(sorted: &[f32], percentile: f32) -> f32 {
+    let index = ((sorted.len() - 1) as f32 * percentile).round() as usize;
+    sorted[index]
+}
+
+/// Measures the energy of the song over time, from its loudness and from how much
+/// high-frequency content (the difference between consecutive samples) it has.
+/// The result is smoothed and scaled to the range 0 (calmest) to 1 (most intense).
+fn song_energy(samples: &[Smpl], sample_rate: u32) -> Option<SongEnergy> {
+    let frame_size = (ENERGY_INTERVAL * sample_rate as f32) as usize;
+    if frame_size == 0 || samples.len() < frame_size {
+        return None;
+    }
+
+    let frames: Vec<f32> = samples
+        .chunks(frame_size)
+        .map(|frame| {
+            let loudness = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+            let brightness = frame
+                .windows(2)
+                .map(|w| (w[1] - w[0]) * (w[1] - w[0]))
+                .sum::<f32>()
+                / frame.len() as f32;
+            loudness.sqrt() + brightness.sqrt()
+        })
+        .collect();
+
+    let smoothed: Vec<f32> = (0..frames.len())
+        .map(|index| {
+            let start = index.saturating_sub(ENERGY_SMOOTHING / 2);
+            let end = (index + ENERGY_SMOOTHING / 2).min(frames.len());
+            frames[start..end].iter().sum::<f32>() / (end - start) as f32
+        })
+        .collect();
+
+    let mut sorted = smoothed.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let low = percentile(&sorted, 0.1);
+    let high = percentile(&sorted, 0.9);
+    let range = (high - low).max(f32::EPSILON);
+
+    Some(SongEnergy {
+        interval: ENERGY_INTERVAL,
+        values: smoothed
+            .into_iter()
+            .map(|value| ((value - low) / range).clamp(0.0, 1.0))
+            .collect(),
+    })
+}
+
+/// Measures how much low-frequency energy follows each beat.
+fn beat_accents(samples: &[Smpl], sample_rate: u32, offsets: &[f32]) -> Vec<f32> {
+    let dt = 1.0 / sample_rate as f32;
+    let rc = 1.0 / (2.0 * std::f32::consts::PI * ACCENT_CUTOFF);
+    let alpha = dt / (rc + dt);
+    let mut low_passed = Vec::with_capacity(samples.len());
+    let mut previous = 0.0;
+    for sample in samples {
+        previous += alpha * (sample - previous);
+        low_passed.push(previous);
+    }
+
+    let window = (ACCENT_WINDOW * sample_rate as f32) as usize;
+    offsets
+        .iter()
+        .map(|offset| {
+            let start = ((offset * sample_rate as f32) as usize).min(low_passed.len());
+            let end = (start + window).min(low_passed.len());
+            let energy: f32 = low_passed[start..end].iter().map(|s| s * s).sum();
+            energy / (end - start).max(1) as f32
+        })
+        .collect()
+}
+
+/// Finds the number of beats per measure and the index of the first downbeat by checking
+/// which regular pattern of beats has the strongest accents compared to the others.
+fn detect_meter(accents: &[f32]) -> Option<(usize, usize)> {
+    let mean = accents.iter().sum::<f32>() / accents.len() as f32;
+    if accents.len() < METERS[0] * 2 || mean <= 0.0 {
+        return None;
+    }
+
+    let mut best: Option<(usize, usize, f32)> = None;
+    for (index, &meter) in METERS.iter().enumerate() {
+        for phase in 0..meter {
+            let downbeats: Vec<_> = accents.iter().skip(phase).step_by(meter).collect();
+            let strength = downbeats.iter().copied().sum::<f32>() / downbeats.len() as f32 / mean;
+            let strength = if index == 0 {
+                strength * METER_PREFERENCE
+            } else {
+                strength
+            };
+            if best.is_none_or(|(_, _, best)| strength > best) {
+                best = Some((meter, phase, strength));
+            }
+        }
+    }
+
+    best.map(|(meter, phase, _)| (meter, phase))
+}
+
+/// A mono WAV copy of a song in the app's temporary audio directory, for analyzing the
+/// song. The file is deleted when this is dropped, also when the analysis fails.
+pub(super) struct TempWav {
+    path: Utf8PathBuf,
+}
+
+impl TempWav {
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+}
+
+impl Drop for TempWav {
+    fn drop(&mut self) {
+        if !self.path.is_file() {
+            return;
+        }
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!("failed to remove temporary WAV file {}: {e}", self.path);
+        }
+    }
+}
+
+pub(super) fn convert_to_wav(
+    source: impl AsRef<Utf8Path>,
+    directories: &Directories,
+    ffmpeg: &FfmpegLocation,
+) -> AppResult<TempWav> {
     let source = source.as_ref();
     let file_stem = source
         .file_stem()
         .ok_or(eyre!("input file must have a filename"))?;
-    let destination = source.parent().unwrap().join(format!("{file_stem}.wav"));
+    let temp_dir = directories.temp_audio_dir();
+    std::fs::create_dir_all(&temp_dir)?;
+    // songs can be analyzed for beats and waveform at the same time, so every
+    // conversion gets a file of its own
+    let destination = temp_dir.join(format!("{file_stem}-{}.wav", generate_id()));
     info!(
         "converting file at {} to WAV, destination = {}",
         source, destination
     );
-    if destination.is_file() {
-        info!("wav file already exists, returning early");
-        return Ok(destination);
-    }
 
     let output = Command::new(ffmpeg.ffmpeg())
         .args(vec![
@@ -40,18 +184,23 @@ fn convert_to_wav(source: impl AsRef<Utf8Path>, ffmpeg: &FfmpegLocation) -> AppR
             destination.as_str(),
         ])
         .output()?;
+    let wav = TempWav { path: destination };
     if !output.status.success() {
         commandline_error("ffmpeg", output)
     } else {
-        Ok(destination)
+        Ok(wav)
     }
 }
 
-pub fn detect_beats(file: impl AsRef<Utf8Path>, ffmpeg: &FfmpegLocation) -> AppResult<Beats> {
+pub fn detect_beats(
+    file: impl AsRef<Utf8Path>,
+    directories: &Directories,
+    ffmpeg: &FfmpegLocation,
+) -> AppResult<Beats> {
     let start = Instant::now();
     let file = file.as_ref();
-    let wav_file = convert_to_wav(file, ffmpeg)?;
-    let reader = WavReader::open(wav_file)?;
+    let wav_file = convert_to_wav(file, directories, ffmpeg)?;
+    let reader = WavReader::open(wav_file.path())?;
     let format = reader.spec();
     let duration = reader.duration();
     let period = 1.0 / format.sample_rate as Smpl;
@@ -78,11 +227,100 @@ pub fn detect_beats(file: impl AsRef<Utf8Path>, ffmpeg: &FfmpegLocation) -> AppR
         time = offset as Smpl * period;
     }
 
+    let accents = beat_accents(&samples, format.sample_rate, &offsets);
+    let meter = detect_meter(&accents);
+    let downbeats = meter.map(|(meter, phase)| {
+        offsets
+            .iter()
+            .skip(phase)
+            .step_by(meter)
+            .copied()
+            .collect::<Vec<_>>()
+    });
+
     let elapsed = start.elapsed();
-    info!("detected {} beats in {:?}", offsets.len(), elapsed);
+    info!(
+        "detected {} beats in {:?}, beats per measure: {:?}",
+        offsets.len(),
+        elapsed,
+        meter.map(|(meter, _)| meter)
+    );
 
     Ok(Beats {
         offsets,
         length: duration as f32 / format.sample_rate as f32,
+        downbeats,
+        beats_per_measure: meter.map(|(meter, _)| meter),
+        energy: song_energy(&samples, format.sample_rate),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_wav_is_removed() {
+        let path = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .unwrap()
+            .join(format!("clip-mash-{}.wav", generate_id()));
+        std::fs::write(&path, b"RIFF").unwrap();
+        let wav = TempWav { path: path.clone() };
+        assert!(wav.path().is_file());
+        drop(wav);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_detect_meter() {
+        // accents on every fourth beat, starting with the second one
+        let accents: Vec<_> = (0..32)
+            .map(|i| if i % 4 == 1 { 1.0 } else { 0.2 })
+            .collect();
+        assert_eq!(detect_meter(&accents), Some((4, 1)));
+
+        // waltz
+        let accents: Vec<_> = (0..30)
+            .map(|i| if i % 3 == 0 { 1.0 } else { 0.1 })
+            .collect();
+        assert_eq!(detect_meter(&accents), Some((3, 0)));
+
+        assert_eq!(detect_meter(&[1.0, 0.5]), None);
+        assert_eq!(detect_meter(&[0.0; 16]), None);
+    }
+
+    #[test]
+    fn test_song_energy() {
+        let sample_rate = 1000;
+        // 20 seconds of a quiet, low tone followed by 20 seconds of a loud, bright one
+        let samples: Vec<_> = (0..40_000)
+            .map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                if i < 20_000 {
+                    0.05 * (t * 2.0 * std::f32::consts::PI * 5.0).sin()
+                } else {
+                    0.5 * (t * 2.0 * std::f32::consts::PI * 200.0).sin()
+                }
+            })
+            .collect();
+
+        let energy = song_energy(&samples, sample_rate).unwrap();
+        assert_eq!(energy.values.len(), 80);
+        assert!(energy.at(5.0).unwrap() < 0.01);
+        assert!(energy.at(35.0).unwrap() > 0.99);
+        assert!(energy.values.windows(2).all(|w| w[0] <= w[1] + 0.001));
+        assert_eq!(song_energy(&[0.0; 10], sample_rate), None);
+    }
+
+    #[test]
+    fn test_beat_accents() {
+        let sample_rate = 1000;
+        // low-frequency "kick" at 1 second, silence everywhere else
+        let samples: Vec<_> = (0..3000)
+            .map(|i| if (1000..1100).contains(&i) { 0.5 } else { 0.0 })
+            .collect();
+        let accents = beat_accents(&samples, sample_rate, &[0.5, 1.0, 2.0]);
+        assert!(accents[1] > accents[0] * 10.0);
+        assert!(accents[1] > accents[2] * 10.0);
+    }
+}
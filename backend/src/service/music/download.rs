@@ -12,6 +12,7 @@ use crate::data::database::Database;
 use crate::helpers::random::generate_id;
 use crate::server::handlers::AppState;
 use crate::service::commands::ffmpeg::FfmpegLocation;
+use crate::service::commands::ffprobe::SongTags;
 use crate::service::commands::{ffprobe, YtDlp, YtDlpOptions};
 use crate::service::directories::{Directories, FolderType};
 use crate::service::music;
@@ -21,6 +22,7 @@ use crate::Result;
 pub struct SongInfo {
     pub path: Utf8PathBuf,
     pub duration: f64,
+    pub tags: SongTags,
 }
 
 pub struct MusicDownloadService {
@@ -76,6 +78,7 @@ impl MusicDownloadService {
         Ok(SongInfo {
             path: result.downloaded_file,
             duration,
+            tags: ffprobe_result.song_tags(),
         })
     }
 
@@ -98,7 +101,8 @@ impl MusicDownloadService {
             }
         } else {
             let downloaded_song = self.download_to_file(url.clone()).await?;
-            let beats = music::detect_beats(&downloaded_song.path, &self.ffmpeg_location).ok();
+            let beats =
+                music::detect_beats(&downloaded_song.path, &self.dirs, &self.ffmpeg_location).ok();
             let result = self
                 .db
                 .music
@@ -107,6 +111,9 @@ impl MusicDownloadService {
                     file_path: downloaded_song.path.to_string(),
                     url: url.to_string(),
                     beats,
+                    title: downloaded_song.tags.title,
+                    artist: downloaded_song.tags.artist,
+                    album: downloaded_song.tags.album,
                 })
                 .await?;
             Ok(result)
@@ -125,7 +132,8 @@ impl MusicDownloadService {
         }
 
         let ffprobe_result = ffprobe(path.as_str(), &self.ffmpeg_location).await?;
-        let beats = music::detect_beats(&path, &self.ffmpeg_location).ok();
+        let beats = music::detect_beats(&path, &self.dirs, &self.ffmpeg_location).ok();
+        let tags = ffprobe_result.song_tags();
 
         let result = self
             .db
@@ -135,6 +143,9 @@ impl MusicDownloadService {
                 file_path: path.to_string(),
                 url: format!("file:{path}"),
                 beats,
+                title: tags.title,
+                artist: tags.artist,
+                album: tags.album,
             })
             .await?;
         Ok(result)
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use tokio::task::spawn_blocking;
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+use crate::data::database::music::{CreateSong, DbSong};
+use crate::data::database::Database;
+use crate::server::handlers::AppState;
+use crate::service::commands::ffmpeg::FfmpegLocation;
+use crate::service::commands::ffprobe;
+use crate::service::directories::Directories;
+use crate::service::music;
+use crate::Result;
+
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "m4a", "aac", "ogg", "opus", "flac", "wav"];
+
+fn is_audio_file(path: &Utf8Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| AUDIO_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+/// Beat detection used to convert every song to a WAV file next to it, those shouldn't be
+/// imported as songs of their own.
+fn is_converted_wav(path: &Utf8Path, files: &[Utf8PathBuf]) -> bool {
+    path.extension() == Some("wav")
+        && files
+            .iter()
+            .any(|other| other != path && other.with_extension("wav") == path)
+}
+
+pub struct MusicImportService {
+    db: Database,
+    dirs: Directories,
+    ffmpeg_location: FfmpegLocation,
+}
+
+impl From<Arc<AppState>> for MusicImportService {
+    fn from(value: Arc<AppState>) -> Self {
+        Self {
+            db: value.database.clone(),
+            dirs: value.directories.clone(),
+            ffmpeg_location: value.ffmpeg_location.clone(),
+        }
+    }
+}
+
+impl MusicImportService {
+    async fn gather_files(&self, path: Utf8PathBuf, recurse: bool) -> Result<Vec<Utf8PathBuf>> {
+        spawn_blocking(move || {
+            let files: Vec<_> = WalkDir::new(path)
+                .max_depth(if recurse { usize::MAX } else { 1 })
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+                .filter_map(|e| Utf8PathBuf::from_path_buf(e.into_path()).ok())
+                .filter(|e| is_audio_file(e))
+                .collect();
+
+            Ok(files
+                .iter()
+                .filter(|file| !is_converted_wav(file, &files))
+                .cloned()
+                .collect())
+        })
+        .await?
+    }
+
+    async fn import_song(&self, path: &Utf8Path) -> Result<Option<DbSong>> {
+        if self.db.music.song_exists_by_path(path.as_str()).await? {
+            info!("song at path '{path}' is already imported, skipping");
+            return Ok(None);
+        }
+
+        let ffprobe = match ffprobe(path.as_str(), &self.ffmpeg_location).await {
+            Ok(ffprobe) => ffprobe,
+            Err(e) => {
+                warn!("skipping song {path} because ffprobe failed with error {e}");
+                return Ok(None);
+            }
+        };
+        let tags = ffprobe.song_tags();
+        let song = self
+            .db
+            .music
+            .persist_song(CreateSong {
+                url: format!("file:{path}"),
+                file_path: path.to_string(),
+                duration: ffprobe.duration().unwrap_or_default(),
+                beats: None,
+                title: tags.title,
+                artist: tags.artist,
+                album: tags.album,
+            })
+            .await?;
+        info!("imported song {song:?}");
+        Ok(Some(song))
+    }
+
+    /// Detects the beats of the songs one after another, so imports of large folders
+    /// don't start dozens of ffmpeg processes at once.
+    fn detect_beats_in_background(&self, songs: Vec<(i64, String)>) {
+        if songs.is_empty() {
+            return;
+        }
+        let db = self.db.clone();
+        let dirs = self.dirs.clone();
+        let ffmpeg = self.ffmpeg_location.clone();
+        tokio::spawn(async move {
+            info!("detecting beats for {} imported songs", songs.len());
+            for (song_id, file_path) in songs {
+                let dirs = dirs.clone();
+                let ffmpeg = ffmpeg.clone();
+                let path = file_path.clone();
+                let beats = spawn_blocking(move || music::detect_beats(path, &dirs, &ffmpeg)).await;
+                let result = match beats {
+                    Ok(Ok(beats)) => db.music.persist_beats(song_id, &beats).await,
+                    Ok(Err(e)) => Err(e),
+                    Err(e) => Err(e.into()),
+                };
+                if let Err(e) = result {
+                    warn!("failed to detect beats for song {file_path}: {e}");
+                }
+            }
+        });
+    }
+
+    /// Imports all audio files in the folder that aren't in the library yet, reading their
+    /// metadata from their tags. Beats are detected in the background afterwards.
+    pub async fn import_folder(&self, path: Utf8PathBuf, recurse: bool) -> Result<Vec<DbSong>> {
+        info!("importing songs from {path} (recurse: {recurse})");
+        let files = self.gather_files(path, recurse).await?;
+        let mut songs = vec![];
+        for file in files {
+            if let Some(song) = self.import_song(&file).await? {
+                songs.push(song);
+            }
+        }
+
+        self.detect_beats_in_background(
+            songs
+                .iter()
+                .map(|song| (song.rowid.expect("must have rowid"), song.file_path.clone()))
+                .collect(),
+        );
+        Ok(songs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_audio_file() {
+        assert!(is_audio_file(Utf8Path::new("/music/song.mp3")));
+        assert!(is_audio_file(Utf8Path::new("/music/song.FLAC")));
+        assert!(!is_audio_file(Utf8Path::new("/music/cover.jpg")));
+        assert!(!is_audio_file(Utf8Path::new("/music/README")));
+    }
+
+    #[test]
+    fn test_is_converted_wav() {
+        let files = vec![
+            Utf8PathBuf::from("/music/a.mp3"),
+            Utf8PathBuf::from("/music/a.wav"),
+            Utf8PathBuf::from("/music/b.wav"),
+        ];
+        assert!(is_converted_wav(&files[1], &files));
+        assert!(!is_converted_wav(&files[0], &files));
+        assert!(!is_converted_wav(&files[2], &files));
+    }
+}
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use tokio::fs;
+use tracing::{info, warn};
+
+use super::waveform::cache_file_prefix;
+use crate::data::database::music::DbSong;
+use crate::data::database::Database;
+use crate::server::handlers::AppState;
+use crate::service::directories::Directories;
+use crate::Result;
+
+/// Files that belong to the song: the WAV file that beat detection used to create next to
+/// it and the cached waveforms, wherever the song is. The song file itself is only included
+/// if the song was downloaded or uploaded, songs imported from a local folder belong to
+/// the user.
+async fn song_files(
+    song: &DbSong,
+    music_dir: &Utf8Path,
+    waveform_dir: &Utf8Path,
+) -> Result<Vec<Utf8PathBuf>> {
+    let path = Utf8Path::new(&song.file_path);
+    let mut files = vec![];
+    if path.starts_with(music_dir) {
+        files.push(path.to_owned());
+    }
+    let wav_file = path.with_extension("wav");
+    if wav_file != path {
+        files.push(wav_file);
+    }
+
+    if let Some(song_id) = song.rowid {
+        let waveform_prefix = cache_file_prefix(song_id);
+        if waveform_dir.is_dir() {
+            let mut entries = fs::read_dir(waveform_dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                if name.starts_with(&waveform_prefix) && name.ends_with(".json") {
+                    files.push(waveform_dir.join(name.as_ref()));
+                }
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+pub struct MusicLibraryService {
+    db: Database,
+    dirs: Directories,
+}
+
+impl From<Arc<AppState>> for MusicLibraryService {
+    fn from(value: Arc<AppState>) -> Self {
+        Self {
+            db: value.database.clone(),
+            dirs: value.directories.clone(),
+        }
+    }
+}
+
+impl MusicLibraryService {
+    /// Deletes the song from the library and all playlists and removes its files.
+    pub async fn delete_song(&self, song: DbSong) -> Result<()> {
+        let song_id = song.rowid.expect("must have rowid");
+        self.db.playlists.remove_song(song_id).await?;
+        self.db.music.delete_song(song_id).await?;
+        info!("deleted song {song_id} at {}", song.file_path);
+
+        let music_dir = self.dirs.music_dir();
+        let waveform_dir = self.dirs.waveform_dir();
+        for file in song_files(&song, &music_dir, &waveform_dir).await? {
+            if file.is_file() {
+                info!("removing file {file}");
+                if let Err(e) = fs::remove_file(&file).await {
+                    warn!("failed to remove file {file}: {e}");
+                }
+            }
+        }
+
+        // downloaded and uploaded songs get a directory of their own
+        let parent = Utf8Path::new(&song.file_path).parent();
+        if let Some(parent) = parent.filter(|p| p.starts_with(&music_dir) && *p != music_dir) {
+            if fs::remove_dir(parent).await.is_ok() {
+                info!("removed empty directory {parent}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(file_path: &str) -> DbSong {
+        DbSong {
+            rowid: Some(1),
+            url: format!("file:{file_path}"),
+            file_path: file_path.to_string(),
+            duration: 120.0,
+            beats: None,
+            title: None,
+            artist: None,
+            album: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_song_files() -> Result<()> {
+        let music_dir = Utf8Path::new("/cache/music");
+        let waveform_dir = Utf8Path::new("/cache/waveforms");
+        let files = song_files(&song("/cache/music/abc/song.mp3"), music_dir, waveform_dir).await?;
+        assert_eq!(
+            files,
+            vec![
+                Utf8PathBuf::from("/cache/music/abc/song.mp3"),
+                Utf8PathBuf::from("/cache/music/abc/song.wav"),
+            ]
+        );
+
+        let files = song_files(&song("/home/user/music/song.wav"), music_dir, waveform_dir).await?;
+        assert!(files.is_empty());
+
+        let files = song_files(&song("/home/user/music/song.mp3"), music_dir, waveform_dir).await?;
+        assert_eq!(files, vec![Utf8PathBuf::from("/home/user/music/song.wav")]);
+
+        Ok(())
+    }
+}
@@ -1,5 +1,15 @@
+mod beat_grid;
 mod beats;
 mod download;
+mod import;
+mod library;
+mod soundtrack;
+mod waveform;
 
+pub use self::beat_grid::apply_edit as apply_beat_grid_edit;
 pub use self::beats::detect_beats;
 pub use self::download::MusicDownloadService;
+pub use self::import::MusicImportService;
+pub use self::library::MusicLibraryService;
+pub use self::soundtrack::VideoSoundtrackService;
+pub use self::waveform::song_waveform;
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use lazy_static::lazy_static;
+use tokio::fs;
+use tokio::sync::Mutex;
+use tokio::task::spawn_blocking;
+use tracing::info;
+
+use crate::data::database::music::{CreateSong, DbSong};
+use crate::data::database::videos::DbVideo;
+use crate::data::database::Database;
+use crate::helpers::random::generate_id;
+use crate::server::handlers::AppState;
+use crate::service::commands::ffmpeg::{Ffmpeg, FfmpegLocation};
+use crate::service::commands::ffprobe;
+use crate::service::directories::Directories;
+use crate::service::music;
+use crate::Result;
+
+lazy_static! {
+    /// One lock per video whose audio is being turned into a song, so concurrent requests
+    /// for the same video wait for the first one instead of extracting the audio again.
+    static ref EXTRACTION_LOCKS: Mutex<HashMap<String, Arc<Mutex<()>>>> =
+        Mutex::new(HashMap::new());
+}
+
+async fn extraction_lock(video_id: &str) -> Arc<Mutex<()>> {
+    let mut locks = EXTRACTION_LOCKS.lock().await;
+    locks.entry(video_id.to_string()).or_default().clone()
+}
+
+/// Forgets the video's lock once no other request is waiting for it.
+async fn release_extraction_lock(video_id: &str, lock: Arc<Mutex<()>>) {
+    let mut locks = EXTRACTION_LOCKS.lock().await;
+    drop(lock);
+    if locks
+        .get(video_id)
+        .is_some_and(|lock| Arc::strong_count(lock) == 1)
+    {
+        locks.remove(video_id);
+    }
+}
+
+/// The URL that songs extracted from a video are stored under, so every video's
+/// soundtrack is only extracted once.
+fn soundtrack_url(video_id: &str) -> String {
+    format!("video:{video_id}")
+}
+
+/// Turns the audio of library videos into songs, so they can be used like any other song
+/// to cut clips to its beats and as the music of a compilation.
+pub struct VideoSoundtrackService {
+    db: Database,
+    dirs: Directories,
+    ffmpeg_location: FfmpegLocation,
+}
+
+impl From<Arc<AppState>> for VideoSoundtrackService {
+    fn from(value: Arc<AppState>) -> Self {
+        Self {
+            db: value.database.clone(),
+            dirs: value.directories.clone(),
+            ffmpeg_location: value.ffmpeg_location.clone(),
+        }
+    }
+}
+
+impl VideoSoundtrackService {
+    async fn extract_audio(&self, video: &DbVideo) -> Result<Utf8PathBuf> {
+        let output_dir = self.dirs.music_dir().join(generate_id());
+        fs::create_dir_all(&output_dir).await?;
+        let file_stem = Utf8Path::new(&video.file_path)
+            .file_stem()
+            .unwrap_or(&video.id)
+            .to_string();
+        let destination = output_dir.join(format!("{file_stem}.m4a"));
+        info!(
+            "extracting audio of video {} to {destination}",
+            video.file_path
+        );
+
+        Ffmpeg::new(&self.ffmpeg_location, destination.as_str())
+            .input(&video.file_path)
+            .extra_arg("-vn")
+            .extra_arg("-c:a")
+            .extra_arg("aac")
+            .extra_arg("-b:a")
+            .extra_arg("192k")
+            .run()
+            .await?;
+
+        Ok(destination)
+    }
+
+    /// Returns the song made from the video's audio, extracting the audio and detecting
+    /// its beats if that hasn't been done yet. Requests for a video whose audio is already
+    /// being extracted wait for that extraction and return its song.
+    pub async fn song_from_video(&self, video: &DbVideo) -> Result<DbSong> {
+        let lock = extraction_lock(&video.id).await;
+        let guard = lock.lock().await;
+        let result = self.find_or_extract_song(video).await;
+        drop(guard);
+        release_extraction_lock(&video.id, lock).await;
+
+        result
+    }
+
+    async fn find_or_extract_song(&self, video: &DbVideo) -> Result<DbSong> {
+        let url = soundtrack_url(&video.id);
+        let existing_song = self.db.music.get_song_by_url(&url).await?;
+        if let Some(mut song) = existing_song {
+            if Utf8Path::new(&song.file_path).is_file() {
+                return Ok(song);
+            }
+            let path = self.extract_audio(video).await?;
+            let song_id = song.rowid.expect("must have rowid");
+            self.db
+                .music
+                .update_song_file_path(song_id, path.as_str())
+                .await?;
+            song.file_path = path.to_string();
+            return Ok(song);
+        }
+
+        let path = self.extract_audio(video).await?;
+        let ffprobe_result = ffprobe(path.as_str(), &self.ffmpeg_location).await?;
+        let ffmpeg = self.ffmpeg_location.clone();
+        let dirs = self.dirs.clone();
+        let beats_path = path.clone();
+        let beats = spawn_blocking(move || music::detect_beats(beats_path, &dirs, &ffmpeg))
+            .await?
+            .ok();
+
+        self.db
+            .music
+            .persist_song(CreateSong {
+                url,
+                file_path: path.to_string(),
+                duration: ffprobe_result.format.duration().unwrap_or(video.duration),
+                beats,
+                title: video.video_title.clone(),
+                artist: None,
+                album: None,
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use sqlx::SqlitePool;
+    use tracing_test::traced_test;
+
+    use super::{extraction_lock, soundtrack_url, VideoSoundtrackService, EXTRACTION_LOCKS};
+    use crate::data::database::music::CreateSong;
+    use crate::data::database::Database;
+    use crate::service::commands::ffmpeg::FfmpegLocation;
+    use crate::service::directories::Directories;
+    use crate::service::fixtures::persist_video;
+
+    #[test]
+    fn test_soundtrack_url() {
+        assert_eq!(soundtrack_url("abc123"), "video:abc123");
+        assert_ne!(soundtrack_url("abc"), soundtrack_url("abc1"));
+        // not a URL that can be downloaded from
+        assert!(!soundtrack_url("abc").starts_with("http"));
+    }
+
+    #[sqlx::test]
+    #[traced_test]
+    async fn test_song_from_video_uses_existing_song(pool: SqlitePool) {
+        let database = Database::with_pool(pool);
+        let video = persist_video(&database).await.unwrap();
+        let song = database
+            .music
+            .persist_song(CreateSong {
+                url: soundtrack_url(&video.id),
+                // any existing file works, the audio isn't read again
+                file_path: "testfiles/infinite-loop.json".into(),
+                duration: 50.0,
+                beats: None,
+                title: None,
+                artist: None,
+                album: None,
+            })
+            .await
+            .unwrap();
+        let service = VideoSoundtrackService {
+            db: database.clone(),
+            dirs: Directories::new().unwrap(),
+            // extracting would fail, so the existing song has to be used
+            ffmpeg_location: FfmpegLocation::Local(Arc::new("/does/not/exist".into())),
+        };
+
+        let (first, second) = tokio::join!(
+            service.song_from_video(&video),
+            service.song_from_video(&video)
+        );
+        assert_eq!(first.unwrap().rowid, song.rowid);
+        assert_eq!(second.unwrap().rowid, song.rowid);
+        assert!(!EXTRACTION_LOCKS.lock().await.contains_key(&video.id));
+    }
+
+    #[tokio::test]
+    async fn test_extraction_lock_is_shared() {
+        let first = extraction_lock("shared-video").await;
+        let second = extraction_lock("shared-video").await;
+        let other = extraction_lock("other-video").await;
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &other));
+
+        let _guard = first.lock().await;
+        assert!(second.try_lock().is_err());
+    }
+}
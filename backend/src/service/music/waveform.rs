@@ -0,0 +1,124 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use hound::WavReader;
+use tracing::{info, warn};
+
+use super::beats::convert_to_wav;
+use crate::server::types::SongWaveform;
+use crate::service::commands::ffmpeg::FfmpegLocation;
+use crate::service::directories::Directories;
+use crate::Result as AppResult;
+
+/// Smallest number of buckets a waveform is computed with.
+const MIN_RESOLUTION: usize = 100;
+/// Largest number of buckets a waveform is computed with.
+const MAX_RESOLUTION: usize = 100_000;
+
+fn clamp_resolution(resolution: usize) -> usize {
+    resolution.clamp(MIN_RESOLUTION, MAX_RESOLUTION)
+}
+
+fn cache_path(waveform_dir: &Utf8Path, song_id: i64, resolution: usize) -> Utf8PathBuf {
+    waveform_dir.join(format!("{song_id}-{resolution}.json"))
+}
+
+/// Name prefix of all cached waveform files of the song.
+pub fn cache_file_prefix(song_id: i64) -> String {
+    format!("{song_id}-")
+}
+
+/// Splits the samples into `resolution` buckets of equal length and returns the
+/// smallest and largest sample of every bucket.
+fn compute_peaks(samples: &[f32], resolution: usize) -> (Vec<f32>, Vec<f32>) {
+    let bucket_size = samples.len().div_ceil(resolution.max(1)).max(1);
+    samples
+        .chunks(bucket_size)
+        .map(|bucket| {
+            bucket
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(min, max), sample| {
+                    (min.min(*sample), max.max(*sample))
+                })
+        })
+        .unzip()
+}
+
+/// Returns the waveform of a song with (at most) `resolution` buckets, where the resolution
+/// is clamped to between 100 and 100000. The result is cached in the app's waveform directory.
+pub fn song_waveform(
+    file: impl AsRef<Utf8Path>,
+    song_id: i64,
+    resolution: usize,
+    directories: &Directories,
+    ffmpeg: &FfmpegLocation,
+) -> AppResult<SongWaveform> {
+    let file = file.as_ref();
+    let waveform_dir = directories.waveform_dir();
+    let resolution = clamp_resolution(resolution);
+    let cache_file = cache_path(&waveform_dir, song_id, resolution);
+    if cache_file.is_file() {
+        match serde_json::from_str(&std::fs::read_to_string(&cache_file)?) {
+            Ok(waveform) => return Ok(waveform),
+            Err(e) => warn!("ignoring invalid cached waveform at {cache_file}: {e}"),
+        }
+    }
+
+    let wav_file = convert_to_wav(file, directories, ffmpeg)?;
+    let reader = WavReader::open(wav_file.path())?;
+    let sample_rate = reader.spec().sample_rate;
+    let samples = reader
+        .into_samples()
+        .map(|sample| sample.map(|sample: i16| sample as f32 / i16::MAX as f32))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (min, max) = compute_peaks(&samples, resolution);
+    let length = samples.len() as f32 / sample_rate as f32;
+    let waveform = SongWaveform {
+        length,
+        bucket_duration: length / min.len().max(1) as f32,
+        min,
+        max,
+    };
+    info!(
+        "computed waveform with {} buckets for {file}, caching at {cache_file}",
+        waveform.min.len()
+    );
+    std::fs::create_dir_all(&waveform_dir)?;
+    std::fs::write(&cache_file, serde_json::to_string(&waveform)?)?;
+
+    Ok(waveform)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_peaks() {
+        let samples = [0.0, 0.5, -0.5, 1.0, -1.0, 0.25, 0.1];
+        let (min, max) = compute_peaks(&samples, 3);
+        assert_eq!(min, vec![-0.5, -1.0, 0.1]);
+        assert_eq!(max, vec![0.5, 1.0, 0.1]);
+
+        let (min, max) = compute_peaks(&samples, 100);
+        assert_eq!(min.len(), samples.len());
+        assert_eq!(max, samples.to_vec());
+    }
+
+    #[test]
+    fn test_cache_path() {
+        let path = cache_path(Utf8Path::new("/cache/waveforms"), 12, 500);
+        assert_eq!(path, Utf8PathBuf::from("/cache/waveforms/12-500.json"));
+        assert!(path
+            .file_name()
+            .unwrap()
+            .starts_with(&cache_file_prefix(12)));
+    }
+
+    #[test]
+    fn test_clamp_resolution() {
+        assert_eq!(clamp_resolution(0), 100);
+        assert_eq!(clamp_resolution(500), 500);
+        assert_eq!(clamp_resolution(3000), 3000);
+        assert_eq!(clamp_resolution(200_000), 100_000);
+    }
+}
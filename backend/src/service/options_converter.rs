@@ -1,9 +1,15 @@
 use std::collections::{HashMap, HashSet};
 
+use crate::data::database::markers::ListMarkersFilter;
 use crate::data::database::music::DbSong;
 use crate::data::database::videos::DbVideo;
 use crate::data::database::Database;
-use crate::server::types::{Clip, CreateClipsBody, CreateVideoBody, SelectedMarker};
+use crate::server::types::{
+    Clip, CreateClipsBody, CreateVideoBody, FreshnessOptions, InteractiveClipsQuery, MotionOptions,
+    SelectedMarker,
+};
+use crate::service::clip::freshness::Freshness;
+use crate::service::clip::motion::Motion;
 use crate::service::clip::CreateClipsOptions;
 use crate::service::generator::CompilationOptions;
 use crate::service::Marker;
@@ -87,18 +93,88 @@ impl OptionsConverterService {
             padding: body.padding.unwrap_or_default(),
             force_re_encode: body.force_re_encode,
             include_original_file_name: body.include_original_file_name,
+            broken_videos: body.broken_videos.unwrap_or_default(),
         })
     }
 
+    /// Lists all markers matching the query, with their full range selected.
+    pub async fn query_markers(&self, query: InteractiveClipsQuery) -> Result<Vec<SelectedMarker>> {
+        let filter = match query {
+            InteractiveClipsQuery::MarkerTitles { data } => ListMarkersFilter::MarkerTitles(data),
+            InteractiveClipsQuery::Performers { data } => ListMarkersFilter::VideoPerformers(data),
+            InteractiveClipsQuery::VideoTags { data } => ListMarkersFilter::VideoTags(data),
+        };
+
+        let markers = self
+            .db
+            .markers
+            .list_markers(Some(filter), None)
+            .await?
+            .into_iter()
+            .map(|m| SelectedMarker {
+                id: m.rowid.unwrap(),
+                video_id: m.video_id,
+                selected_range: (m.start_time, m.end_time),
+                index_within_video: m.index_within_video as usize,
+                selected: Some(true),
+                title: m.title,
+                loops: 1,
+                source: m.source,
+            })
+            .collect();
+
+        Ok(markers)
+    }
+
     async fn resolve_songs(&self, song_ids: &[i64]) -> Result<Vec<DbSong>> {
         self.db.music.get_songs(song_ids).await
     }
 
+    async fn resolve_freshness(&self, options: FreshnessOptions) -> Result<Freshness> {
+        let used_ranges = self
+            .db
+            .compilations
+            .get_recently_used_ranges(options.recent_compilations as i64)
+            .await?;
+
+        Ok(Freshness {
+            mode: options.mode,
+            used_ranges,
+        })
+    }
+
+    async fn resolve_motion(
+        &self,
+        options: MotionOptions,
+        markers: &[SelectedMarker],
+    ) -> Result<Motion> {
+        let video_ids: HashSet<_> = markers.iter().map(|m| m.video_id.as_str()).collect();
+        let video_ids: Vec<_> = video_ids.into_iter().collect();
+        let videos = self.db.motion.get_motion_for_videos(&video_ids).await?;
+
+        Ok(Motion {
+            static_share: options.static_share,
+            videos,
+        })
+    }
+
     pub async fn convert_clip_options(&self, body: CreateClipsBody) -> Result<CreateClipsOptions> {
+        let freshness = match body.freshness {
+            Some(options) => Some(self.resolve_freshness(options).await?),
+            None => None,
+        };
+        let motion = match body.motion {
+            Some(options) => Some(self.resolve_motion(options, &body.markers).await?),
+            None => None,
+        };
+
         Ok(CreateClipsOptions {
             markers: self.convert_selected_markers(body.markers),
             seed: body.seed,
             clip_options: body.clips,
+            pinned_clips: body.pinned_clips.unwrap_or_default(),
+            freshness,
+            motion,
         })
     }
 }
@@ -0,0 +1,205 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use color_eyre::eyre::eyre;
+use lazy_static::lazy_static;
+use sanitise_file_name::sanitise;
+use time::OffsetDateTime;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use crate::data::database::schedules::{CreateScheduleRun, DbSchedule};
+use crate::data::database::{unix_timestamp_now, Database};
+use crate::helpers::cron::CronSchedule;
+use crate::helpers::random::{generate_id, get_random_word};
+use crate::server::types::{CreateClipsBody, ScheduleOptions};
+use crate::service::clip::ClipsResult;
+use crate::service::clip_similarity;
+use crate::service::commands::ffmpeg::FfmpegLocation;
+use crate::service::directories::Directories;
+use crate::service::generator::CompilationGenerator;
+use crate::service::options_converter::OptionsConverterService;
+use crate::Result;
+
+/// How often to check for schedules that are due.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    /// Only one scheduled compilation is rendered at a time.
+    static ref RUN_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Calculates the next time (as unix timestamp) the cron expression matches after `after`.
+pub fn next_run(cron: &str, after: i64) -> Result<Option<i64>> {
+    let schedule: CronSchedule = cron.parse()?;
+    let after = OffsetDateTime::from_unix_timestamp(after)?;
+    Ok(schedule.next_after(after).map(|t| t.unix_timestamp()))
+}
+
+pub fn run_async(database: Database, directories: Directories, ffmpeg_location: FfmpegLocation) {
+    tokio::spawn(async move {
+        let scheduler = Scheduler::new(database, directories, ffmpeg_location);
+        loop {
+            if let Err(e) = scheduler.run_due_schedules().await {
+                error!("failed to run scheduled compilations: {e:?}");
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+#[derive(Clone)]
+pub struct Scheduler {
+    database: Database,
+    directories: Directories,
+    ffmpeg_location: FfmpegLocation,
+}
+
+impl Scheduler {
+    pub fn new(
+        database: Database,
+        directories: Directories,
+        ffmpeg_location: FfmpegLocation,
+    ) -> Self {
+        Scheduler {
+            database,
+            directories,
+            ffmpeg_location,
+        }
+    }
+
+    pub async fn run_due_schedules(&self) -> Result<()> {
+        let now = unix_timestamp_now();
+        let schedules = self.database.schedules.get_due_schedules(now).await?;
+        for schedule in schedules {
+            // move the next run forward first, so a failing schedule isn't retried every minute
+            let next = next_run(&schedule.cron, now).unwrap_or_else(|e| {
+                warn!("invalid cron expression for schedule {}: {e}", schedule.id);
+                None
+            });
+            self.database
+                .schedules
+                .set_run_times(schedule.id, now, next)
+                .await?;
+            self.run_schedule(&schedule).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates a compilation for the schedule and records the outcome (with the seed used)
+    /// as a run of that schedule.
+    pub async fn run_schedule(&self, schedule: &DbSchedule) -> Result<i64> {
+        let _guard = RUN_LOCK.lock().await;
+        let seed = get_random_word();
+        info!(
+            "running schedule {} ('{}') with seed '{seed}'",
+            schedule.id, schedule.name
+        );
+
+        let result = match serde_json::from_str::<ScheduleOptions>(&schedule.options_json) {
+            Ok(options) => {
+                self.create_compilation(&schedule.name, options, &seed)
+                    .await
+            }
+            Err(e) => Err(e.into()),
+        };
+        let run = match result {
+            Ok((video_id, file_name)) => CreateScheduleRun {
+                schedule_id: schedule.id,
+                video_id: Some(video_id),
+                file_name: Some(file_name),
+                seed,
+                error: None,
+            },
+            Err(e) => {
+                error!("scheduled compilation {} failed: {e:?}", schedule.id);
+                CreateScheduleRun {
+                    schedule_id: schedule.id,
+                    video_id: None,
+                    file_name: None,
+                    seed,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+
+        self.database.schedules.insert_run(&run).await
+    }
+
+    async fn create_compilation(
+        &self,
+        name: &str,
+        options: ScheduleOptions,
+        seed: &str,
+    ) -> Result<(String, String)> {
+        let converter = OptionsConverterService::new(self.database.clone());
+        let mut markers = converter.query_markers(options.query).await?;
+        if let Some(days) = options.added_within_days {
+            let video_ids: HashSet<_> = markers.iter().map(|m| m.video_id.as_str()).collect();
+            let video_ids: Vec<_> = video_ids.into_iter().collect();
+            let cutoff = unix_timestamp_now() - days as i64 * 24 * 60 * 60;
+            let recent_videos = self
+                .database
+                .videos
+                .get_imported_since(&video_ids, cutoff)
+                .await?;
+            markers.retain(|m| recent_videos.contains(&m.video_id));
+        }
+        if markers.is_empty() {
+            return Err(eyre!("no markers found for the schedule's query"));
+        }
+
+        let clip_options = converter
+            .convert_clip_options(CreateClipsBody {
+                markers: markers.clone(),
+                seed: Some(seed.to_string()),
+                clips: options.clips,
+                pinned_clips: None,
+                freshness: None,
+                motion: None,
+                render_settings: None,
+            })
+            .await?;
+        let ClipsResult { clips, .. } =
+            clip_similarity::arrange_clips(&self.database, &self.ffmpeg_location, clip_options)
+                .await?;
+
+        let video_id = generate_id();
+        let date = OffsetDateTime::now_utc().date();
+        let file_name = sanitise(&format!("{name} {date} {seed}.mp4"));
+        let body =
+            options
+                .render
+                .to_video_body(video_id.clone(), file_name.clone(), clips, markers);
+        let compilation_options = converter.convert_compilation_options(body).await?;
+
+        let generator = CompilationGenerator::new(
+            self.directories.clone(),
+            &self.ffmpeg_location,
+            self.database.clone(),
+        )
+        .await?;
+        let clips = generator.gather_clips(&compilation_options).await?;
+        let path = generator.compile_clips(&compilation_options, clips).await?;
+        info!("finished scheduled compilation at {path}");
+
+        Ok((video_id, file_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::next_run;
+
+    #[test]
+    fn test_next_run() {
+        // 2026-10-18 12:00 UTC, a Sunday
+        let now = 1792324800;
+        // every Monday at 10:00
+        let next = next_run("0 10 * * 1", now).unwrap().unwrap();
+        assert_eq!(next - now, 22 * 60 * 60);
+
+        assert!(next_run("not a cron expression", now).is_err());
+    }
+}
@@ -5,7 +5,7 @@ use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::eyre::{bail, eyre};
 use futures::future;
 use itertools::Itertools;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 use tokio::task::spawn_blocking;
@@ -18,15 +18,22 @@ use super::commands::ffmpeg::FfmpegLocation;
 use super::directories::Directories;
 use crate::data::database::markers::DbMarker;
 use crate::data::database::performers::CreatePerformer;
-use crate::data::database::videos::{CreateVideo, DbVideo, VideoSource, VideoUpdate};
+use crate::data::database::videos::{
+    tags_from_string, CreateVideo, DbVideo, VideoSource, VideoUpdate,
+};
 use crate::data::database::Database;
 use crate::data::stash_api::{MarkerLike, StashApi, StashMarker};
+use crate::helpers::oshash::oshash;
 use crate::helpers::parallelize;
 use crate::helpers::random::generate_id;
 use crate::server::handlers::AppState;
 use crate::server::types::{CreateMarker, ListVideoDto, UpdateMarker};
+use crate::service::commands::ffprobe::VideoChapter;
 use crate::service::commands::{ffprobe, YtDlp, YtDlpOptions};
 use crate::service::directories::FolderType;
+use crate::service::duplicates::perceptual_hash;
+use crate::service::funscript::load_sidecar_funscript;
+use crate::service::local_metadata::{self, FileNameTemplate};
 use crate::service::preview_image::PreviewGenerator;
 use crate::Result;
 
@@ -35,10 +42,14 @@ const VIDEO_EXTENSIONS: &[&str] = &["mp4", "m4v", "webm"];
 #[derive(Debug, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum AddVideosRequest {
+    #[serde(rename_all = "camelCase")]
     Local {
         path: String,
         recurse: bool,
         tags: Option<Vec<String>>,
+        folder_tags: Option<FolderTagOptions>,
+        /// Create markers from the chapters that are embedded in the video files.
+        chapter_markers: Option<bool>,
     },
     Download {
         urls: Vec<String>,
@@ -48,6 +59,48 @@ pub enum AddVideosRequest {
     Stash { scene_ids: Vec<i64> },
 }
 
+/// Tags imported videos with the names of the folders they're in, relative to the folder
+/// that is imported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderTagOptions {
+    /// Only the first `depth` folders below the imported folder become tags. All of them
+    /// are used if not set.
+    pub depth: Option<usize>,
+}
+
+/// Returns the names of the folders between `root` and the file.
+pub fn folder_tags(root: &Utf8Path, file: &Utf8Path, options: &FolderTagOptions) -> Vec<String> {
+    let Some(relative) = file.parent().and_then(|p| p.strip_prefix(root).ok()) else {
+        return vec![];
+    };
+    relative
+        .components()
+        .map(|c| c.as_str().trim().to_string())
+        .filter(|c| !c.is_empty())
+        .take(options.depth.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// Adds the tags that are missing from `tags`.
+pub(crate) fn merge_tags(tags: &[String], new_tags: Vec<String>) -> Vec<String> {
+    let mut tags = tags.to_vec();
+    for tag in new_tags {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+pub(crate) fn is_in_folder(path: &Utf8Path, folder: &Utf8Path, recurse: bool) -> bool {
+    if recurse {
+        path.starts_with(folder)
+    } else {
+        path.parent() == Some(folder)
+    }
+}
+
 fn tags_to_string(tags: &[String]) -> String {
     serde_json::to_string(tags).expect("tags must be serializable")
 }
@@ -58,6 +111,7 @@ pub struct VideoService {
     ffmpeg_location: FfmpegLocation,
     stash_api: StashApi,
     preview_generator: PreviewGenerator,
+    file_name_templates: Vec<FileNameTemplate>,
 }
 
 impl VideoService {
@@ -65,16 +119,19 @@ impl VideoService {
         let stash_api = state.stash_api().await?;
         let preview_generator =
             PreviewGenerator::new(state.directories.clone(), state.ffmpeg_location.clone());
+        let settings = state.database.settings.fetch().await?;
         Ok(VideoService {
             database: state.database.clone(),
             directories: state.directories.clone(),
             ffmpeg_location: state.ffmpeg_location.clone(),
             stash_api,
             preview_generator,
+            file_name_templates: local_metadata::parse_templates(&settings.file_name_templates),
         })
     }
 
-    async fn gather_files(&self, path: Utf8PathBuf, recurse: bool) -> Result<Vec<Utf8PathBuf>> {
+    /// Lists all video files in the folder.
+    pub async fn gather_files(path: Utf8PathBuf, recurse: bool) -> Result<Vec<Utf8PathBuf>> {
         spawn_blocking(move || {
             let files = WalkDir::new(path)
                 .max_depth(if recurse { usize::MAX } else { 1 })
@@ -96,7 +153,41 @@ impl VideoService {
         .await?
     }
 
-    async fn add_local_video(&self, path: Utf8PathBuf, tags: &[String]) -> Result<Option<DbVideo>> {
+    /// Checks if the file is already in the library under a different path. If the video's old
+    /// file is gone, the file was moved and the video is pointed to it. Returns whether the file
+    /// must not be imported.
+    async fn relink_or_skip_duplicate(&self, path: &Utf8Path, file_hash: &str) -> Result<bool> {
+        let existing = self.database.videos.find_by_file_hash(file_hash).await?;
+        if let Some(moved) = existing
+            .iter()
+            .find(|v| !Utf8Path::new(&v.file_path).is_file())
+        {
+            info!(
+                "video {} was moved from {} to {path}",
+                moved.id, moved.file_path
+            );
+            self.database
+                .videos
+                .set_file_path(&moved.id, path.as_str())
+                .await?;
+            return Ok(true);
+        }
+        if let Some(duplicate) = existing.first() {
+            info!(
+                "skipping {path}, it is the same file as video {} at {}",
+                duplicate.id, duplicate.file_path
+            );
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    pub async fn add_local_video(
+        &self,
+        path: Utf8PathBuf,
+        tags: &[String],
+        chapter_markers: bool,
+    ) -> Result<Option<DbVideo>> {
         let video_exists = self
             .database
             .videos
@@ -104,7 +195,16 @@ impl VideoService {
             .await?;
         info!("video at path '{path}' exists: {video_exists}");
         if !video_exists {
-            let interactive = path.with_extension("funscript").is_file();
+            let file_hash = oshash(&path)
+                .inspect_err(|e| warn!("failed to hash video file {path}: {e}"))
+                .ok();
+            if let Some(file_hash) = &file_hash {
+                if self.relink_or_skip_duplicate(&path, file_hash).await? {
+                    return Ok(None);
+                }
+            }
+
+            let interactive = load_sidecar_funscript(&path).await.is_some();
             let ffprobe = ffprobe(path.as_str(), &self.ffmpeg_location).await;
             if let Err(e) = ffprobe {
                 warn!("skipping video {path} because ffprobe failed with error {e}");
@@ -117,6 +217,11 @@ impl VideoService {
                 .preview_generator
                 .generate_preview(&id, &path, duration.map_or(0.0, |d| d / 2.0))
                 .await?;
+            let metadata = local_metadata::read_local_metadata(&path, &self.file_name_templates);
+            let all_tags = merge_tags(tags, metadata.all_tags());
+            let title = metadata
+                .title
+                .unwrap_or_else(|| path.file_stem().unwrap().to_string());
             let file_created = path.metadata()?.created().ok().map(|time| {
                 time.duration_since(SystemTime::UNIX_EPOCH)
                     .unwrap()
@@ -131,13 +236,56 @@ impl VideoService {
                 duration: duration.unwrap_or_default(),
                 video_preview_image: Some(image_path.to_string()),
                 stash_scene_id: None,
-                title: Some(path.file_stem().unwrap().to_string()),
-                tags: Some(tags_to_string(tags)),
+                title: Some(title),
+                tags: Some(tags_to_string(&all_tags)),
                 created_on: file_created,
             };
             info!("inserting new video {create_video:#?}");
             let video = self.database.videos.persist_video(&create_video).await?;
             self.database.ffprobe.set_info(&video.id, &ffprobe).await?;
+            if !metadata.performers.is_empty() {
+                let performers: Vec<_> = metadata
+                    .performers
+                    .into_iter()
+                    .unique()
+                    .map(|name| CreatePerformer {
+                        name,
+                        image_url: None,
+                        stash_id: None,
+                        gender: None,
+                    })
+                    .collect();
+                self.database
+                    .performers
+                    .insert_for_video(&performers, &video.id)
+                    .await?;
+            }
+            if let Some(file_hash) = &file_hash {
+                self.database
+                    .videos
+                    .set_file_hash(&video.id, file_hash)
+                    .await?;
+            }
+            match perceptual_hash(&self.ffmpeg_location, &path, video.duration).await {
+                Ok(hash) => {
+                    self.database
+                        .videos
+                        .set_perceptual_hash(&video.id, hash)
+                        .await?
+                }
+                Err(e) => warn!("failed to compute perceptual hash for {path}: {e}"),
+            }
+            if chapter_markers {
+                let chapters = ffprobe.chapters();
+                if !chapters.is_empty() {
+                    if let Err(e) = self.create_chapter_markers(&video, &chapters).await {
+                        warn!(
+                            "failed to create chapter markers for video {}: {e:?}",
+                            video.id
+                        );
+                    }
+                }
+            }
             Ok(Some(video))
         } else {
             Ok(None)
@@ -149,13 +297,20 @@ impl VideoService {
         path: impl AsRef<Utf8Path>,
         recurse: bool,
         tags: Vec<String>,
+        folder_tag_options: Option<FolderTagOptions>,
+        chapter_markers: bool,
     ) -> Result<Vec<DbVideo>> {
         let start = Instant::now();
-        let entries = self.gather_files(path.as_ref().to_owned(), recurse).await?;
+        let root = path.as_ref();
+        let entries = Self::gather_files(root.to_owned(), recurse).await?;
         debug!("found files {entries:?} (recurse = {recurse})");
-        let futures = entries
-            .into_iter()
-            .map(|path| self.add_local_video(path, &tags));
+        let futures = entries.into_iter().map(|path| {
+            let tags = match &folder_tag_options {
+                Some(options) => merge_tags(&tags, folder_tags(root, &path, options)),
+                None => tags.clone(),
+            };
+            async move { self.add_local_video(path, &tags, chapter_markers).await }
+        });
         let videos = parallelize(futures)
             .await
             .into_iter()
@@ -171,6 +326,50 @@ impl VideoService {
         Ok(videos)
     }
 
+    /// Adds the folder tags to the videos that are already in the library. Returns the number
+    /// of videos that got new tags.
+    pub async fn apply_folder_tags(
+        &self,
+        root: &Utf8Path,
+        recurse: bool,
+        options: &FolderTagOptions,
+    ) -> Result<usize> {
+        let video_ids: Vec<_> = self
+            .database
+            .videos
+            .list_local_video_files()
+            .await?
+            .into_iter()
+            .filter(|v| is_in_folder(Utf8Path::new(&v.file_path), root, recurse))
+            .map(|v| v.id)
+            .collect();
+        let video_ids: Vec<_> = video_ids.iter().map(String::as_str).collect();
+        let videos = self.database.videos.get_videos_by_ids(&video_ids).await?;
+
+        let mut updated = 0;
+        for video in videos {
+            let tags = tags_from_string(video.video_tags.as_deref());
+            let new_tags = folder_tags(root, Utf8Path::new(&video.file_path), options);
+            let all_tags = merge_tags(&tags, new_tags);
+            if all_tags.len() > tags.len() {
+                self.database
+                    .videos
+                    .update_video(
+                        &video.id,
+                        VideoUpdate {
+                            title: None,
+                            tags: Some(all_tags),
+                        },
+                    )
+                    .await?;
+                updated += 1;
+            }
+        }
+        info!("added folder tags to {updated} videos in {root}");
+
+        Ok(updated)
+    }
+
     async fn download_video(&self, url: Url) -> Result<(String, Utf8PathBuf)> {
         info!("downloading video {url}");
         let downloader = YtDlp::new(self.directories.clone());
@@ -205,6 +404,81 @@ impl VideoService {
         self.database.markers.create_new_marker(create_marker).await
     }
 
+    /// Reads the chapters of the video with ffprobe and updates the stored ffprobe info,
+    /// because videos that were added earlier were probed without chapters.
+    pub async fn read_chapters(&self, video: &DbVideo) -> Result<Vec<VideoChapter>> {
+        let ffprobe = ffprobe(&video.file_path, &self.ffmpeg_location).await?;
+        self.database.ffprobe.set_info(&video.id, &ffprobe).await?;
+        Ok(ffprobe.chapters())
+    }
+
+    /// Creates a marker with a preview image for each chapter. Chapters that start where
+    /// the video already has a marker are skipped, so importing them twice is harmless.
+    pub async fn create_chapter_markers(
+        &self,
+        video: &DbVideo,
+        chapters: &[VideoChapter],
+    ) -> Result<Vec<DbMarker>> {
+        let existing_markers = self
+            .database
+            .markers
+            .get_markers_for_video(&video.id)
+            .await?;
+        let mut markers = vec![];
+        for chapter in chapters {
+            if existing_markers
+                .iter()
+                .any(|m| (m.start_time - chapter.start).abs() < 0.1)
+            {
+                info!(
+                    "skipping chapter '{}' of video {}, there already is a marker at {}",
+                    chapter.title, video.id, chapter.start
+                );
+                continue;
+            }
+
+            // a marker without a preview gets one from the migrator on the next start
+            let preview_path = match self
+                .preview_generator
+                .generate_preview(&video.id, &video.file_path, chapter.start)
+                .await
+            {
+                Ok(path) => Some(path.to_string()),
+                Err(e) => {
+                    warn!(
+                        "failed to generate preview for chapter '{}' of video {}: {e:?}",
+                        chapter.title, video.id
+                    );
+                    None
+                }
+            };
+            let create_marker = CreateMarker {
+                video_id: video.id.clone(),
+                start: chapter.start,
+                end: chapter.end,
+                title: chapter.title.clone(),
+                index_within_video: markers.len() as i64,
+                preview_image_path: preview_path,
+                video_interactive: video.interactive,
+                created_on: None,
+                marker_stash_id: None,
+            };
+            markers.push(
+                self.database
+                    .markers
+                    .create_new_marker(create_marker)
+                    .await?,
+            );
+        }
+        info!(
+            "created {} markers from chapters for video {}",
+            markers.len(),
+            video.id
+        );
+
+        Ok(markers)
+    }
+
     async fn persist_downloaded_video(
         &self,
         id: String,
@@ -341,9 +615,17 @@ impl VideoService {
                 path,
                 recurse,
                 tags,
+                folder_tags,
+                chapter_markers,
             } => {
-                self.add_new_local_videos(path, recurse, tags.unwrap_or_default())
-                    .await
+                self.add_new_local_videos(
+                    path,
+                    recurse,
+                    tags.unwrap_or_default(),
+                    folder_tags,
+                    chapter_markers.unwrap_or(false),
+                )
+                .await
             }
             AddVideosRequest::Download { urls, tags } => {
                 let futures = future::try_join_all(urls.into_iter().map(|url| {
@@ -531,9 +813,38 @@ impl VideoService {
 
 #[cfg(test)]
 mod tests {
+    use camino::Utf8Path;
+
+    use super::{folder_tags, FolderTagOptions};
     use crate::service::fixtures;
     use crate::Result;
 
+    #[test]
+    fn test_folder_tags() {
+        let root = Utf8Path::new("/videos");
+        let file = Utf8Path::new("/videos/Studio/Series 1/video.mp4");
+        assert_eq!(
+            folder_tags(root, file, &FolderTagOptions::default()),
+            vec!["Studio", "Series 1"]
+        );
+        assert_eq!(
+            folder_tags(root, file, &FolderTagOptions { depth: Some(1) }),
+            vec!["Studio"]
+        );
+        assert!(folder_tags(
+            root,
+            Utf8Path::new("/videos/video.mp4"),
+            &FolderTagOptions::default()
+        )
+        .is_empty());
+        assert!(folder_tags(
+            root,
+            Utf8Path::new("/other/a/video.mp4"),
+            &FolderTagOptions::default()
+        )
+        .is_empty());
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_add_local_videos() -> Result<()> {
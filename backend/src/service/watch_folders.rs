@@ -0,0 +1,476 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use lazy_static::lazy_static;
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+
+use crate::data::database::unix_timestamp_now;
+use crate::data::database::videos::{tags_from_string, VideoFileStatus};
+use crate::data::database::watch_folders::DbWatchFolder;
+use crate::server::handlers::AppState;
+use crate::service::video::{
+    folder_tags, is_in_folder, merge_tags, FolderTagOptions, VideoService,
+};
+use crate::Result;
+
+/// How often all watch folders are rescanned, in case the file system watcher missed a
+/// change (network shares, for example, often don't report any).
+const POLL_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// How often the file system watcher picks up added, removed or disabled watch folders.
+const WATCHER_SYNC_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long to wait after a file system event before scanning, so copying a file triggers
+/// one scan instead of one per write.
+const EVENT_DELAY: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    /// Sizes of new files from the last scan. A file is only imported once its size
+    /// stopped changing, so files that are still being copied aren't imported half-finished.
+    /// Also makes sure only one scan runs at a time.
+    static ref PENDING_FILES: Mutex<HashMap<Utf8PathBuf, u64>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchFolderScanResult {
+    /// Number of videos that were imported.
+    pub added: usize,
+    /// Number of videos whose file was removed since the last scan.
+    pub missing: usize,
+    /// Number of videos whose file is back after being missing.
+    pub restored: usize,
+    /// Number of existing videos that got new folder tags.
+    pub tagged: usize,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct ScanPlan {
+    import: Vec<Utf8PathBuf>,
+    pending: HashMap<Utf8PathBuf, u64>,
+    missing: Vec<String>,
+    restored: Vec<String>,
+}
+
+/// Decides which files in the folder are imported and which videos are flagged as missing
+/// or restored. `videos` are the known videos in the folder, along with whether their file
+/// currently exists.
+fn plan_scan(
+    files: &[Utf8PathBuf],
+    videos: &[(VideoFileStatus, bool)],
+    previous_sizes: &HashMap<Utf8PathBuf, u64>,
+    wait_for_stable_size: bool,
+    file_size: impl Fn(&Utf8Path) -> Option<u64>,
+) -> ScanPlan {
+    let known_paths: HashSet<_> = videos
+        .iter()
+        .map(|(video, _)| Utf8Path::new(&video.file_path))
+        .collect();
+    let mut plan = ScanPlan::default();
+
+    for file in files.iter().filter(|f| !known_paths.contains(f.as_path())) {
+        if !wait_for_stable_size {
+            plan.import.push(file.clone());
+            continue;
+        }
+        let Some(size) = file_size(file) else {
+            continue;
+        };
+        if previous_sizes.get(file) == Some(&size) {
+            plan.import.push(file.clone());
+        } else {
+            plan.pending.insert(file.clone(), size);
+        }
+    }
+
+    for (video, exists) in videos {
+        match (exists, video.missing_since) {
+            (false, None) => plan.missing.push(video.id.clone()),
+            (true, Some(_)) => plan.restored.push(video.id.clone()),
+            _ => {}
+        }
+    }
+
+    plan
+}
+
+/// Scans the folder for new and removed videos. If `wait_for_stable_size` is set, new files
+/// are only imported once they didn't grow since the previous scan.
+pub async fn scan_folder(
+    state: Arc<AppState>,
+    folder: &DbWatchFolder,
+    wait_for_stable_size: bool,
+) -> Result<WatchFolderScanResult> {
+    let mut pending_files = PENDING_FILES.lock().await;
+    let folder_path = Utf8PathBuf::from(&folder.path);
+    if !folder_path.is_dir() {
+        warn!("watch folder {folder_path} does not exist, skipping scan");
+        return Ok(WatchFolderScanResult::default());
+    }
+
+    let database = state.database.clone();
+    let video_service = VideoService::new(state).await?;
+    let files = VideoService::gather_files(folder_path.clone(), folder.recurse).await?;
+    let videos: Vec<_> = database
+        .videos
+        .list_local_video_files()
+        .await?
+        .into_iter()
+        .filter(|v| is_in_folder(Utf8Path::new(&v.file_path), &folder_path, folder.recurse))
+        .map(|v| {
+            let exists = Utf8Path::new(&v.file_path).is_file();
+            (v, exists)
+        })
+        .collect();
+
+    let previous_sizes: HashMap<_, _> = pending_files
+        .iter()
+        .filter(|(path, _)| path.starts_with(&folder_path))
+        .map(|(path, size)| (path.clone(), *size))
+        .collect();
+    let plan = plan_scan(
+        &files,
+        &videos,
+        &previous_sizes,
+        wait_for_stable_size,
+        |path| path.metadata().ok().map(|m| m.len()),
+    );
+    pending_files.retain(|path, _| !path.starts_with(&folder_path));
+    pending_files.extend(plan.pending);
+
+    let tags = tags_from_string(Some(&folder.tags));
+    let folder_tag_options = folder_tag_options(folder);
+    let mut result = WatchFolderScanResult::default();
+    for path in plan.import {
+        let tags = match &folder_tag_options {
+            Some(options) => merge_tags(&tags, folder_tags(&folder_path, &path, options)),
+            None => tags.clone(),
+        };
+        match video_service
+            .add_local_video(path.clone(), &tags, false)
+            .await
+        {
+            Ok(Some(_)) => result.added += 1,
+            Ok(None) => {}
+            Err(e) => warn!("failed to import video {path} from watch folder: {e:?}"),
+        }
+    }
+
+    let now = unix_timestamp_now();
+    for id in &plan.missing {
+        database.videos.set_missing_since(id, Some(now)).await?;
+    }
+    for id in &plan.restored {
+        database.videos.set_missing_since(id, None).await?;
+    }
+    // manual scans also tag the videos that were imported before folder tags were enabled
+    if let (Some(options), false) = (&folder_tag_options, wait_for_stable_size) {
+        result.tagged = video_service
+            .apply_folder_tags(&folder_path, folder.recurse, options)
+            .await?;
+    }
+    result.missing = plan.missing.len();
+    result.restored = plan.restored.len();
+    database.watch_folders.set_last_scan(folder.id, now).await?;
+
+    if result.added > 0 || result.missing > 0 || result.restored > 0 || result.tagged > 0 {
+        info!("scanned watch folder {folder_path}: {result:?}");
+    }
+    Ok(result)
+}
+
+pub fn folder_tag_options(folder: &DbWatchFolder) -> Option<FolderTagOptions> {
+    folder.folder_tags.then(|| FolderTagOptions {
+        depth: folder.folder_tag_depth.map(|d| d as usize),
+    })
+}
+
+/// Scans all enabled folders. Returns the new files that are still being copied.
+async fn scan_all_folders(state: &Arc<AppState>) -> Result<Vec<Utf8PathBuf>> {
+    let folders = state.database.watch_folders.list_watch_folders().await?;
+    for folder in folders.iter().filter(|f| f.enabled) {
+        if let Err(e) = scan_folder(state.clone(), folder, true).await {
+            error!("failed to scan watch folder {}: {e:?}", folder.path);
+        }
+    }
+    Ok(PENDING_FILES.lock().await.keys().cloned().collect())
+}
+
+/// Scans the enabled folders that contain any of the changed paths. Returns the new files
+/// that are still being copied.
+async fn scan_changed_folders(
+    state: &Arc<AppState>,
+    changed_paths: &HashSet<Utf8PathBuf>,
+) -> Result<Vec<Utf8PathBuf>> {
+    let folders = state.database.watch_folders.list_watch_folders().await?;
+    let mut pending = vec![];
+    for folder in folders.iter().filter(|f| f.enabled) {
+        let folder_path = Utf8Path::new(&folder.path);
+        if !changed_paths
+            .iter()
+            .any(|path| is_in_folder(path, folder_path, folder.recurse))
+        {
+            continue;
+        }
+        if let Err(e) = scan_folder(state.clone(), folder, true).await {
+            error!("failed to scan watch folder {}: {e:?}", folder.path);
+        }
+        pending.extend(
+            PENDING_FILES
+                .lock()
+                .await
+                .keys()
+                .filter(|path| path.starts_with(folder_path))
+                .cloned(),
+        );
+    }
+    Ok(pending)
+}
+
+/// Whether the event could mean that a video was added, removed or is still being copied.
+fn is_relevant_event(event: &Event) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Create(_)
+            | EventKind::Remove(_)
+            | EventKind::Modify(ModifyKind::Name(_) | ModifyKind::Data(_) | ModifyKind::Any)
+    )
+}
+
+/// Watches the enabled watch folders for changes.
+struct FolderWatcher {
+    watcher: Option<RecommendedWatcher>,
+    watched: HashMap<Utf8PathBuf, bool>,
+}
+
+impl FolderWatcher {
+    fn new(sender: mpsc::UnboundedSender<Event>) -> Self {
+        let watcher = notify::recommended_watcher(move |event| match event {
+            Ok(event) => {
+                let _ = sender.send(event);
+            }
+            Err(e) => warn!("error while watching folders: {e}"),
+        })
+        .inspect_err(|e| warn!("failed to start file watcher, only polling watch folders: {e}"))
+        .ok();
+
+        FolderWatcher {
+            watcher,
+            watched: HashMap::new(),
+        }
+    }
+
+    /// Starts watching newly added or enabled folders and stops watching removed ones.
+    async fn sync(&mut self, state: &Arc<AppState>) -> Result<()> {
+        let Some(watcher) = &mut self.watcher else {
+            return Ok(());
+        };
+        let folders: HashMap<_, _> = state
+            .database
+            .watch_folders
+            .list_watch_folders()
+            .await?
+            .into_iter()
+            .filter(|f| f.enabled && Utf8Path::new(&f.path).is_dir())
+            .map(|f| (Utf8PathBuf::from(f.path), f.recurse))
+            .collect();
+
+        for (path, recurse) in &self.watched {
+            if folders.get(path) != Some(recurse) {
+                if let Err(e) = watcher.unwatch(path.as_std_path()) {
+                    warn!("failed to stop watching folder {path}: {e}");
+                }
+            }
+        }
+        self.watched
+            .retain(|path, recurse| folders.get(path) == Some(recurse));
+
+        for (path, recurse) in folders {
+            if self.watched.contains_key(&path) {
+                continue;
+            }
+            let mode = if recurse {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            };
+            match watcher.watch(path.as_std_path(), mode) {
+                Ok(()) => {
+                    info!("watching folder {path} for changes");
+                    self.watched.insert(path, recurse);
+                }
+                Err(e) => warn!("failed to watch folder {path}, only polling it: {e}"),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Scans the watch folders when the file system watcher reports changes, and all of them
+/// every [`POLL_INTERVAL`] as a fallback.
+pub fn run_async(state: Arc<AppState>) {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let mut watcher = FolderWatcher::new(sender);
+
+    tokio::spawn(async move {
+        let mut next_poll = Instant::now();
+        let mut next_sync = Instant::now();
+        let mut changed_paths = HashSet::new();
+        let mut scan_changes_at = None;
+        loop {
+            let deadline = scan_changes_at.map_or(next_poll, |at: Instant| at.min(next_poll));
+            let deadline = deadline.min(next_sync);
+            tokio::select! {
+                Some(event) = receiver.recv() => {
+                    if is_relevant_event(&event) {
+                        changed_paths.extend(
+                            event
+                                .paths
+                                .into_iter()
+                                .filter_map(|path| Utf8PathBuf::from_path_buf(path).ok()),
+                        );
+                        scan_changes_at = Some(Instant::now() + EVENT_DELAY);
+                    }
+                }
+                _ = tokio::time::sleep_until(deadline) => {
+                    let now = Instant::now();
+                    if now >= next_sync || now >= next_poll {
+                        if let Err(e) = watcher.sync(&state).await {
+                            error!("failed to update watched folders: {e:?}");
+                        }
+                        next_sync = Instant::now() + WATCHER_SYNC_INTERVAL;
+                    }
+
+                    let result = if now >= next_poll {
+                        changed_paths.clear();
+                        next_poll = Instant::now() + POLL_INTERVAL;
+                        Some(scan_all_folders(&state).await)
+                    } else if scan_changes_at.is_some_and(|at| now >= at) {
+                        let paths = std::mem::take(&mut changed_paths);
+                        Some(scan_changed_folders(&state, &paths).await)
+                    } else {
+                        None
+                    };
+                    if let Some(result) = result {
+                        scan_changes_at = None;
+                        match result {
+                            // check again soon whether the files are done copying
+                            Ok(pending) if !pending.is_empty() => {
+                                changed_paths.extend(pending);
+                                scan_changes_at = Some(Instant::now() + EVENT_DELAY);
+                            }
+                            Ok(_) => {}
+                            Err(e) => error!("failed to scan watch folders: {e:?}"),
+                        }
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(id: &str, file_path: &str, missing_since: Option<i64>) -> VideoFileStatus {
+        VideoFileStatus {
+            id: id.to_string(),
+            file_path: file_path.to_string(),
+            missing_since,
+            file_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_is_in_folder() {
+        let folder = Utf8Path::new("/videos");
+        assert!(is_in_folder(Utf8Path::new("/videos/a.mp4"), folder, false));
+        assert!(!is_in_folder(
+            Utf8Path::new("/videos/sub/a.mp4"),
+            folder,
+            false
+        ));
+        assert!(is_in_folder(
+            Utf8Path::new("/videos/sub/a.mp4"),
+            folder,
+            true
+        ));
+        assert!(!is_in_folder(
+            Utf8Path::new("/videos-old/a.mp4"),
+            folder,
+            true
+        ));
+    }
+
+    #[test]
+    fn test_plan_scan_waits_for_stable_size() {
+        let files = vec![
+            Utf8PathBuf::from("/videos/known.mp4"),
+            Utf8PathBuf::from("/videos/copying.mp4"),
+            Utf8PathBuf::from("/videos/done.mp4"),
+        ];
+        let videos = vec![(video("1", "/videos/known.mp4", None), true)];
+        let previous = HashMap::from([
+            (Utf8PathBuf::from("/videos/copying.mp4"), 100),
+            (Utf8PathBuf::from("/videos/done.mp4"), 500),
+        ]);
+        let sizes = HashMap::from([("/videos/copying.mp4", 200), ("/videos/done.mp4", 500)]);
+
+        let plan = plan_scan(&files, &videos, &previous, true, |path| {
+            sizes.get(path.as_str()).copied()
+        });
+        assert_eq!(plan.import, vec![Utf8PathBuf::from("/videos/done.mp4")]);
+        assert_eq!(
+            plan.pending,
+            HashMap::from([(Utf8PathBuf::from("/videos/copying.mp4"), 200)])
+        );
+
+        let plan = plan_scan(&files, &videos, &HashMap::new(), false, |_| None);
+        assert_eq!(plan.import.len(), 2);
+        assert!(plan.pending.is_empty());
+    }
+
+    #[test]
+    fn test_plan_scan_flags_missing_files() {
+        let videos = vec![
+            (video("gone", "/videos/gone.mp4", None), false),
+            (
+                video("still-gone", "/videos/still-gone.mp4", Some(10)),
+                false,
+            ),
+            (video("back", "/videos/back.mp4", Some(10)), true),
+            (video("fine", "/videos/fine.mp4", None), true),
+        ];
+        let plan = plan_scan(&[], &videos, &HashMap::new(), true, |_| None);
+        assert_eq!(plan.missing, vec!["gone".to_string()]);
+        assert_eq!(plan.restored, vec!["back".to_string()]);
+        assert!(plan.import.is_empty());
+    }
+
+    #[test]
+    fn test_is_relevant_event() {
+        use notify::event::{AccessKind, CreateKind, MetadataKind};
+
+        assert!(is_relevant_event(&Event::new(EventKind::Create(
+            CreateKind::File
+        ))));
+        assert!(is_relevant_event(&Event::new(EventKind::Modify(
+            ModifyKind::Data(notify::event::DataChange::Size)
+        ))));
+        assert!(!is_relevant_event(&Event::new(EventKind::Access(
+            AccessKind::Read
+        ))));
+        assert!(!is_relevant_event(&Event::new(EventKind::Modify(
+            ModifyKind::Metadata(MetadataKind::AccessTime)
+        ))));
+    }
+}